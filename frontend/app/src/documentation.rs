@@ -103,19 +103,28 @@ pub fn documentation(props: &DocumentationProps) -> Html {
               <li><code>{ "radar_min_distance() -> f64" }</code>{ ": Get current minimum distance filter." }</li>
               <li><code>{ "set_radar_max_distance(dist: f64)" }</code>{ ": Set the maximum distance filter." }</li>
               <li><code>{ "radar_max_distance() -> f64" }</code>{ ": Get current maximum distance filter." }</li>
+              <li><code>{ "set_radar_max_contacts(n: usize)" }</code>{ ": Track up to n contacts at once, at the cost of a higher noise floor." }</li>
+              <li><code>{ "scan_all() → Vec<ScanResult>" }</code>{ ": Get all tracked contacts, strongest first." }</li>
               <li><code>{ "set_radar_ecm_mode(mode: EcmMode)" }</code>{ ": Set the Electronic Counter Measures (ECM) mode." }</li>
               <li><code>{ "EcmMode::None" }</code>{ ": No ECM, radar will operate normally." }</li>
               <li><code>{ "EcmMode::Noise" }</code>{ ": Decrease the enemy radar's signal to noise ratio, making it more difficult to detect targets and reducing accuracy of returned contacts." }</li>
+              <li><code>{ "EcmMode::RangeGatePullOff" }</code>{ ": Make enemy radars report this ship further away than it really is." }</li>
+              <li><code>{ "EcmMode::FalseTargets" }</code>{ ": Inject false contacts near this ship into enemy radar scans." }</li>
             </ul>
 
             <h2>{ "Radio" }</h2>
             <ul>
               <li><code>{ "set_radio_channel(channel: usize)" }</code>{ ": Change the radio channel (0 to 9). Takes effect next tick." }</li>
               <li><code>{ "get_radio_channel() -> usize" }</code>{ ": Get the radio channel." }</li>
+              <li><code>{ "set_radio_key(key: u64)" }</code>{ ": Encrypt messages so that only radios using the same key can read them. Zero means no encryption." }</li>
               <li><code>{ "send(data: [f64; 4])" }</code>{ ": Send a message on a channel." }</li>
-              <li><code>{ "receive() -> Option<[f64; 4]>" }</code>{ ": Receive a message from the channel. The message with the strongest signal is returned." }</li>
-              <li><code>{ "send_bytes(data: &[u8])" }</code>{ ": Send a message on a channel as bytes, the data will be zero-filled or truncated to a length of 32 bytes." }</li>
-              <li><code>{ "receive_bytes() -> Option<[u8; 32]>" }</code>{ ": Just like receive, but instead the message will be returned as a byte array." }</li>
+              <li><code>{ "receive() -> Option<[f64; 4]>" }</code>{ ": Receive a message from the channel. The message with the strongest signal is returned. In scenarios with radio interference, nothing is received if other transmissions or jamming are nearly as strong." }</li>
+              <li><code>{ "send_bytes(data: &[u8])" }</code>{ ": Send a message on a channel as bytes, truncated to the radio's maximum message size. Long messages take multiple ticks to transmit." }</li>
+              <li><code>{ "receive_bytes() -> Option<[u8; 32]>" }</code>{ ": Just like receive, but instead the message will be returned as a byte array, zero-filled or truncated to 32 bytes." }</li>
+              <li><code>{ "receive_message_bytes() -> Option<&[u8]>" }</code>{ ": Receive the full message as bytes." }</li>
+              <li><code>{ "get_radio_bandwidth() -> usize" }</code>{ ": Get the number of bytes the radio transmits per tick." }</li>
+              <li><code>{ "get_radio_max_message_size() -> usize" }</code>{ ": Get the maximum length of a message sent with send_bytes. Cruisers support 1024 bytes, other ships 256." }</li>
+              <li><code>{ "get_radio_transmit_ticks() -> u32" }</code>{ ": Get the number of ticks until the current transmission completes. Messages sent while the radio is busy are dropped." }</li>
               <li><code>{ "select_radio(index: usize)" }</code>{ ": Select the radio to control with subsequent API calls. Frigates have 4 radios and cruisers have 8." }</li>
             </ul>
 
@@ -129,6 +138,7 @@ pub fn documentation(props: &DocumentationProps) -> Html {
                   <li><code>{ "Ability::Boost" }</code>{ ": Fighter and missile only. Applies a 100 m/s² forward acceleration for 2s. Reloads in 10s." }</li>
                   <li><code>{ "Ability::Decoy" }</code>{ ": Torpedo only. Mimics the radar signature of a Cruiser for 0.5s. Reloads in 10s." }</li>
                   <li><code>{ "Ability::Shield" }</code>{ ": Cruiser only. Deflects damage for 1s. Reloads in 5s." }</li>
+                  <li><code>{ "Ability::RadioJam" }</code>{ ": Frigate only, in scenarios with radio interference. Jams radio reception of enemy ships for 2s. Reloads in 10s." }</li>
                 </ul>
               </li>
             </ul>
@@ -178,6 +188,9 @@ pub fn documentation(props: &DocumentationProps) -> Html {
             <ul>
               <li><code>{ "current_tick() → u32" }</code>{ ": Returns the number of ticks elapsed since the simulation started." }</li>
               <li><code>{ "current_time() → f64" }</code>{ ": Returns the number of seconds elapsed since the simulation started." }</li>
+              <li><code>{ "gas_remaining() → u32" }</code>{ ": Returns the amount of gas (roughly, instructions) left this tick. Ships that run out crash." }</li>
+              <li><code>{ "gas_budget() → u32" }</code>{ ": Returns the amount of gas available at the start of each tick. Some scenarios give larger ships a bigger budget." }</li>
+              <li><code>{ "gas_used() → u32" }</code>{ ": Returns the amount of gas used during the previous tick." }</li>
               <li><code>{ "angle_diff(a: f64, b: f64) → f64" }</code>{ ": Returns the shortest (possibly negative) distance between two angles." }</li>
               <li><code>{ "rand(low: f64, high: f64) → f64" }</code>{ ": Get a random number." }</li>
               <li><code>{ "target() → Vec2" }</code>{ ": Used in some scenarios, returns the position of the target." }</li>
@@ -15,6 +15,7 @@ use crate::userid;
 use crate::versions_window::VersionsWindow;
 use crate::welcome::Welcome;
 use monaco::yew::CodeEditorLink;
+use oort_proto::compiler::{CompileErrors, CompileResponse, Severity};
 use oort_proto::{LeaderboardSubmission, Telemetry};
 use oort_simulation_worker::SimAgent;
 use oort_simulator::scenario::{self, Status, MAX_TICKS};
@@ -43,6 +44,9 @@ fn empty() -> JsValue {
     js_sys::Object::new().into()
 }
 
+/// Compiled code and its warnings, or the errors that stopped it compiling.
+pub type CompileResult = Result<(Code, CompileErrors), CompileErrors>;
+
 #[derive(Debug)]
 pub enum Msg {
     RegisterSimulationWindowLink(Scope<SimulationWindow>),
@@ -52,7 +56,7 @@ pub enum Msg {
     EditorAction { team: usize, action: String },
     ShowFeedback,
     DismissOverlay,
-    CompileFinished(Vec<Result<Code, String>>, ExecutionMode),
+    CompileFinished(Vec<CompileResult>, ExecutionMode),
     SubmitToTournament,
     UploadShortcode,
     FormattedCode { team: usize, text: String },
@@ -355,8 +359,9 @@ impl Component for Game {
                 let mut teams_with_errors = vec![];
                 for (team, result) in results.iter().enumerate() {
                     match result {
-                        Ok(code) => {
-                            self.team_mut(team).display_compiler_errors(&[]);
+                        Ok((code, warnings)) => {
+                            self.team_mut(team)
+                                .display_compiler_errors(&make_editor_errors(warnings));
                             self.team_mut(team).running_compiled_code = code.clone();
                             self.compilation_cache
                                 .insert(self.team(team).running_source_code.clone(), code.clone());
@@ -372,7 +377,7 @@ impl Component for Game {
                 let errors: Vec<_> = results
                     .iter()
                     .filter_map(|x| x.as_ref().err())
-                    .cloned()
+                    .map(|e| e.rendered.clone())
                     .collect();
                 if errors.is_empty() {
                     services::send_telemetry(Telemetry::StartScenario {
@@ -670,7 +675,7 @@ impl Game {
         }
 
         if self.execution_mode == ExecutionMode::Run {
-            if let Status::Victory { team: 0 } = status {
+            if status.winner() == Some(0) {
                 self.background_agents.clear();
                 self.background_snapshots.clear();
                 self.background_nonce = rand::thread_rng().gen();
@@ -801,7 +806,7 @@ impl Game {
             return None;
         }
 
-        let is_victory = |status: &scenario::Status| matches!(*status, Status::Victory { team: 0 });
+        let is_victory = |status: &scenario::Status| status.winner() == Some(0);
         let mut failed_seeds: Vec<u32> = self
             .background_snapshots
             .iter()
@@ -1040,36 +1045,58 @@ impl Game {
             .link()
             .callback(move |results| Msg::CompileFinished(results, execution_mode));
 
-        async fn compile(text: String) -> Result<Code, String> {
+        fn text_errors(rendered: String) -> CompileErrors {
+            CompileErrors {
+                diagnostics: vec![],
+                rendered,
+            }
+        }
+
+        /// Returns the compiled code along with any warnings.
+        async fn compile(text: String) -> CompileResult {
             if text.trim().is_empty() {
-                return Ok(Code::None);
+                return Ok((Code::None, CompileErrors::default()));
             }
 
             let start_time = instant::Instant::now();
 
             let url = format!("{}/compile", services::compiler_url());
-            let result = Request::post(&url).body(text).send().await;
+            let result = Request::post(&url)
+                .header("Accept", "application/json")
+                .body(text)
+                .send()
+                .await;
             if let Err(e) = result {
                 log::error!("Compile error: {}", e);
-                return Err(e.to_string());
+                return Err(text_errors(e.to_string()));
             }
 
             let response = result.unwrap();
             if !response.ok() {
+                let is_json = response
+                    .headers()
+                    .get("content-type")
+                    .map_or(false, |t| t.contains("json"));
                 let error = response.text().await.unwrap();
                 log::error!("Compile error: {}", error);
-                return Err(error);
+                if is_json {
+                    if let Ok(errors) = serde_json::from_str::<CompileErrors>(&error) {
+                        return Err(errors);
+                    }
+                }
+                return Err(text_errors(error));
             }
 
-            let wasm = response.binary().await;
-            if let Err(e) = wasm {
+            let response = response.json::<CompileResponse>().await;
+            if let Err(e) = response {
                 log::error!("Compile error: {}", e);
-                return Err(e.to_string());
+                return Err(text_errors(e.to_string()));
             }
+            let response = response.unwrap();
 
             let elapsed = instant::Instant::now() - start_time;
             log::info!("Compile succeeded in {:?}", elapsed);
-            Ok(Code::Wasm(wasm.unwrap()))
+            Ok((Code::Wasm(response.wasm), response.warnings))
         }
 
         let source_codes: Vec<_> = self
@@ -1097,8 +1124,10 @@ impl Game {
             for source_code in source_codes {
                 let result = match source_code {
                     Code::Rust(text) => compile(text).await,
-                    Code::Builtin(name) => oort_simulator::vm::builtin::load_compiled(&name),
-                    other => Ok(other),
+                    Code::Builtin(name) => oort_simulator::vm::builtin::load_compiled(&name)
+                        .map(|code| (code, CompileErrors::default()))
+                        .map_err(text_errors),
+                    other => Ok((other, CompileErrors::default())),
                 };
                 results.push(result);
             }
@@ -1325,11 +1354,24 @@ impl Team {
             .map(|error| {
                 let decoration: IModelDeltaDecoration = empty().into();
                 decoration.set_range(
-                    &Range::new(error.line as f64, 1.0, error.line as f64, 1.0).unchecked_into(),
+                    &Range::new(
+                        error.start_line as f64,
+                        error.start_column as f64,
+                        error.end_line as f64,
+                        error.end_column as f64,
+                    )
+                    .unchecked_into(),
                 );
                 let options: IModelDecorationOptions = empty().into();
-                options.set_is_whole_line(Some(true));
-                options.set_class_name("errorDecoration".into());
+                options.set_is_whole_line(Some(error.whole_line));
+                options.set_class_name(
+                    if error.severity == Severity::Error {
+                        "errorDecoration"
+                    } else {
+                        "warningDecoration"
+                    }
+                    .into(),
+                );
                 let hover_message: IMarkdownString = empty().into();
                 js_sys::Reflect::set(
                     &hover_message,
@@ -1379,18 +1421,78 @@ pub fn str_to_code(s: &str) -> Code {
 
 #[derive(Debug, Clone)]
 pub struct CompilerError {
-    pub line: usize,
+    pub severity: Severity,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub whole_line: bool,
     pub msg: String,
 }
 
-fn make_editor_errors(error: &str) -> Vec<CompilerError> {
-    let re = Regex::new(r"(?m)error.*?: (.*?)$\n.*?ai/src/user.rs:(\d+):").unwrap();
-    re.captures_iter(error)
-        .map(|m| CompilerError {
-            line: m[2].parse().unwrap(),
-            msg: m[1].to_string(),
-        })
-        .collect()
+fn make_editor_errors(errors: &CompileErrors) -> Vec<CompilerError> {
+    if errors.diagnostics.is_empty() {
+        // Plain text from an older compiler service.
+        let re = Regex::new(r"(?m)error.*?: (.*?)$\n.*?ai/src/user.rs:(\d+):").unwrap();
+        return re
+            .captures_iter(&errors.rendered)
+            .map(|m| {
+                let line = m[2].parse().unwrap();
+                CompilerError {
+                    severity: Severity::Error,
+                    start_line: line,
+                    start_column: 1,
+                    end_line: line,
+                    end_column: 1,
+                    whole_line: true,
+                    msg: m[1].to_string(),
+                }
+            })
+            .collect();
+    }
+
+    let mut result = vec![];
+    for diagnostic in errors.diagnostics.iter() {
+        let mut msg = diagnostic.message.clone();
+        if let Some(code) = diagnostic.code.as_ref() {
+            msg = format!("{msg} [{code}]");
+        }
+        for note in diagnostic.notes.iter() {
+            msg.push_str(&format!("\n\n{note}"));
+        }
+        for suggestion in diagnostic.suggestions.iter() {
+            msg.push_str(&format!(
+                "\n\nhelp: {}\n```rust\n{}\n```",
+                suggestion.message, suggestion.replacement
+            ));
+        }
+        for span in diagnostic
+            .spans
+            .iter()
+            .filter(|span| span.file_name.ends_with("ai/src/user.rs"))
+        {
+            let msg = match span.label.as_ref() {
+                Some(label) if !span.is_primary => label.clone(),
+                Some(label) => format!("{msg}\n\n{label}"),
+                None => msg.clone(),
+            };
+            result.push(CompilerError {
+                severity: diagnostic.severity,
+                start_line: span.start_line as usize,
+                start_column: span.start_column as usize,
+                end_line: span.end_line as usize,
+                end_column: if span.start_line == span.end_line {
+                    // Make empty spans visible.
+                    span.end_column.max(span.start_column + 1) as usize
+                } else {
+                    span.end_column as usize
+                },
+                whole_line: false,
+                msg,
+            });
+        }
+    }
+    result
 }
 
 pub(crate) fn is_encrypted(code: &Code) -> bool {
@@ -7,6 +7,7 @@ use nalgebra::{point, vector, Point2};
 use oort_renderer::Renderer;
 use oort_simulator::model;
 use oort_simulator::scenario::Status;
+use oort_simulator::ship_definition::ShipClasses;
 use oort_simulator::simulation::{self, PHYSICS_TICK_LENGTH};
 use oort_simulator::snapshot::{self, ShipSnapshot, Snapshot};
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -46,6 +47,8 @@ pub struct UI {
     last_status_msg: String,
     snapshot_requests_in_flight: usize,
     nonce: u32,
+    /// Loaded from the first snapshot.
+    ship_classes: ShipClasses,
     request_snapshot: yew::Callback<()>,
     picked_ship_id: Option<u64>,
     status_ref: NodeRef,
@@ -116,6 +119,7 @@ impl UI {
             last_status_msg: "".to_owned(),
             snapshot_requests_in_flight: 0,
             nonce,
+            ship_classes: ShipClasses::new(),
             request_snapshot,
             picked_ship_id: None,
             status_ref,
@@ -233,6 +237,7 @@ impl UI {
                 self.camera_target,
                 self.zoom,
                 self.snapshot.as_ref().unwrap(),
+                &self.ship_classes,
             );
 
             if self.snapshot.as_ref().unwrap().cheats {
@@ -253,6 +258,16 @@ impl UI {
             Status::Draw => {
                 status_msgs.push("DRAW".to_string());
             }
+            Status::Ranked { placements } => match placements.rank(0) {
+                Some(1) if placements.winner() == Some(0) => {
+                    status_msgs.push(format!(
+                        "VICTORY in {:.3}s",
+                        self.snapshot.as_ref().unwrap().time
+                    ));
+                }
+                Some(rank) => status_msgs.push(format!("PLACED #{rank}")),
+                None => status_msgs.push("DEFEAT".to_string()),
+            },
             _ if self.paused => {
                 status_msgs.push("PAUSED".to_string());
             }
@@ -314,6 +329,12 @@ impl UI {
             return;
         }
 
+        // The simulation runs in a worker, which sends the definitions of
+        // custom ship classes with the first snapshot.
+        if let Err(e) = self.ship_classes.load(&snapshot.ship_classes) {
+            log::error!("Failed to load ship classes: {}", e);
+        }
+
         self.pending_snapshots.push_back(snapshot);
         if self.snapshot_requests_in_flight > 0 {
             self.snapshot_requests_in_flight -= 1;
@@ -474,7 +495,10 @@ impl UI {
                         .unwrap_or_default();
                     let radiuses = classes
                         .iter()
-                        .map(|&class| (class, model::radius(class) as f64 + extra_radius))
+                        .map(|&class| {
+                            let radius = model::radius(class, &self.ship_classes);
+                            (class, radius as f64 + extra_radius)
+                        })
                         .collect::<HashMap<_, _>>();
                     self.picked_ship_id = self.snapshot.as_ref().and_then(|snapshot| {
                         snapshot
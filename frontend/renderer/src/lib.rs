@@ -21,6 +21,7 @@ use grid_renderer::GridRenderer;
 use line_renderer::LineRenderer;
 use nalgebra::{point, vector, Matrix4, Point2};
 use oort_api::Text;
+use oort_simulator::ship_definition::ShipClasses;
 use oort_simulator::simulation::Line;
 use oort_simulator::snapshot::Snapshot;
 use particle_renderer::ParticleRenderer;
@@ -133,7 +134,13 @@ impl Renderer {
         point![coords.x as f64, coords.y as f64]
     }
 
-    pub fn render(&mut self, camera_target: Point2<f32>, zoom: f32, snapshot: &Snapshot) {
+    pub fn render(
+        &mut self,
+        camera_target: Point2<f32>,
+        zoom: f32,
+        snapshot: &Snapshot,
+        ship_classes: &ShipClasses,
+    ) {
         let dpr = gloo_utils::window().device_pixel_ratio();
         let new_width = (self.canvas.client_width() as f64 * dpr) as u32;
         let new_height = (self.canvas.client_height() as f64 * dpr) as u32;
@@ -165,6 +172,7 @@ impl Renderer {
         let ship_drawset = self.ship_renderer.upload(
             &self.projection_matrix,
             snapshot,
+            ship_classes,
             self.base_line_width,
             zoom,
             self.nlips_enabled,
@@ -200,6 +208,10 @@ impl Renderer {
             .line_renderer
             .upload(&self.projection_matrix, &snapshot.scenario_lines);
 
+        let beam_drawset = self
+            .line_renderer
+            .upload(&self.projection_matrix, &snapshot.beams);
+
         let debug_line_drawset = {
             let mut lines: Vec<Line> = Vec::new();
             if self.debug {
@@ -235,6 +247,7 @@ impl Renderer {
             self.particle_renderer
                 .draw(&particle_drawset, 10.0 * self.base_line_width);
             self.line_renderer.draw(&scenario_line_drawset);
+            self.line_renderer.draw(&beam_drawset);
             self.ship_renderer.draw(&ship_drawset);
             self.blur.finish();
         }
@@ -254,6 +267,7 @@ impl Renderer {
             self.particle_renderer
                 .draw(&particle_drawset, 5.0 * self.base_line_width);
             self.line_renderer.draw(&scenario_line_drawset);
+            self.line_renderer.draw(&beam_drawset);
             self.line_renderer.draw(&debug_line_drawset);
             self.ship_renderer.draw(&ship_drawset);
             self.text_renderer.draw(&text_drawset);
@@ -3,6 +3,7 @@ use glutil::VertexAttribBuilder;
 use nalgebra::{vector, Matrix4, Vector4};
 use oort_simulator::model;
 use oort_simulator::ship::ShipClass;
+use oort_simulator::ship_definition::ShipClasses;
 use oort_simulator::snapshot::{ShipSnapshot, Snapshot};
 use wasm_bindgen::prelude::*;
 use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation, WebGlVertexArrayObject};
@@ -104,6 +105,7 @@ void main() {
         &mut self,
         projection_matrix: &Matrix4<f32>,
         snapshot: &Snapshot,
+        ship_classes: &ShipClasses,
         base_line_width: f32,
         zoom: f32,
         nlips_enabled: bool,
@@ -123,7 +125,7 @@ void main() {
         let zoom_factor = 2e-3 / zoom;
 
         for (&class, ships) in ships_by_class.iter() {
-            let model = model::load(class);
+            let model = model::load(class, ship_classes);
             let radius: f32 = model
                 .iter()
                 .max_by_key(|v| v.norm_squared() as i32)
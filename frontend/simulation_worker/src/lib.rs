@@ -55,7 +55,9 @@ impl yew_agent::Worker for SimAgent {
                 nonce,
             } => {
                 self.sim = Some(Simulation::new(&scenario_name, seed, &codes));
-                let snapshot = self.sim().snapshot(nonce);
+                let mut snapshot = self.sim().snapshot(nonce);
+                // Later snapshots refer to these by ID.
+                snapshot.ship_classes = self.sim().ship_classes().definitions();
                 self.errored = !snapshot.errors.is_empty();
                 self.link.respond(who, Response::Snapshot { snapshot });
             }
@@ -0,0 +1,244 @@
+//! Content-addressed cache of compiler output, keyed by a hash of the sanitized
+//! source code and the toolchain that compiles it. The service stores the
+//! JSON-serialized `CompileResponse`, so warnings are returned on hits too.
+//!
+//! Entries are stored as files in a local directory so the cache survives
+//! restarts. Once the total size exceeds the limit the least recently used
+//! entries are evicted.
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Part of every key. Bumped when the format of cached entries changes.
+const ENTRY_VERSION: u32 = 2;
+
+#[derive(Clone, Copy, Default, Debug, Serialize, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+struct Entry {
+    size: u64,
+    last_used: u64,
+}
+
+pub struct CompileCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    toolchain: String,
+    entries: HashMap<String, Entry>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl CompileCache {
+    /// Opens the cache in `dir`, picking up entries written by previous runs.
+    ///
+    /// `toolchain` identifies the compiler (e.g. the output of `rustc -vV`)
+    /// and is part of every key.
+    pub fn new(dir: &Path, max_bytes: u64, toolchain: &str) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let mut cache = Self {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            toolchain: toolchain.to_string(),
+            entries: HashMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        };
+
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().map_or(true, |ext| ext != "wasm") {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            files.push((metadata.modified()?, key.to_string(), metadata.len()));
+        }
+        // Oldest files are evicted first.
+        files.sort();
+        for (_, key, size) in files {
+            cache.add_entry(key, size);
+        }
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// Returns the cache key for the given source code.
+    ///
+    /// The entry format, service version, oort_api ABI version, toolchain and
+    /// codegen options are included so that changing any of them doesn't
+    /// return stale output.
+    pub fn key(&self, code: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(ENTRY_VERSION.to_le_bytes());
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update([0]);
+        hasher.update(oort_api::ABI_VERSION.to_le_bytes());
+        hasher.update(&self.toolchain);
+        hasher.update([0]);
+        for option in oort_compiler::CODEGEN_OPTIONS {
+            hasher.update(option);
+            hasher.update([0]);
+        }
+        hasher.update(code);
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<Vec<u8>> {
+        if !self.entries.contains_key(key) {
+            self.stats.misses += 1;
+            return None;
+        }
+        match std::fs::read(self.path(key)) {
+            Ok(wasm) => {
+                self.clock += 1;
+                self.entries.get_mut(key).unwrap().last_used = self.clock;
+                self.stats.hits += 1;
+                Some(wasm)
+            }
+            Err(e) => {
+                log::warn!("Failed to read cache entry {}: {}", key, e);
+                self.remove_entry(key);
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: &str, wasm: &[u8]) -> anyhow::Result<()> {
+        let size = wasm.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+        // Write to a temporary file first so a crash can't leave a truncated
+        // entry behind.
+        let tmp_path = self.dir.join(format!("{key}.tmp"));
+        std::fs::write(&tmp_path, wasm)?;
+        std::fs::rename(&tmp_path, self.path(key))?;
+        self.remove_entry(key);
+        self.add_entry(key.to_string(), size);
+        self.evict();
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.wasm"))
+    }
+
+    fn add_entry(&mut self, key: String, size: u64) {
+        self.clock += 1;
+        self.stats.bytes += size;
+        self.entries.insert(
+            key,
+            Entry {
+                size,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    fn remove_entry(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.stats.bytes -= entry.size;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.stats.bytes > self.max_bytes {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove_entry(&key);
+            if let Err(e) = std::fs::remove_file(self.path(&key)) {
+                log::warn!("Failed to remove cache entry {}: {}", key, e);
+            }
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = CompileCache::new(dir.path(), 1000, "rustc 1.0.0").unwrap();
+        let key = cache.key("fn main() {}");
+        assert_eq!(cache.get(&key), None);
+        cache.insert(&key, b"wasm").unwrap();
+        assert_eq!(cache.get(&key), Some(b"wasm".to_vec()));
+        assert_ne!(key, cache.key("fn main() { }"));
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.bytes, 4);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = CompileCache::new(dir.path(), 20, "rustc 1.0.0").unwrap();
+        cache.insert("a", &[0; 8]).unwrap();
+        cache.insert("b", &[1; 8]).unwrap();
+        assert!(cache.get("a").is_some());
+        cache.insert("c", &[2; 8]).unwrap();
+
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+        assert!(!dir.path().join("b.wasm").exists());
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.stats().bytes, 16);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut cache = CompileCache::new(dir.path(), 1000, "rustc 1.0.0").unwrap();
+            cache.insert("a", b"wasm").unwrap();
+        }
+        let mut cache = CompileCache::new(dir.path(), 1000, "rustc 1.0.0").unwrap();
+        assert_eq!(cache.get("a"), Some(b"wasm".to_vec()));
+    }
+
+    #[test]
+    fn test_toolchain_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = CompileCache::new(dir.path(), 1000, "rustc 1.0.0").unwrap();
+        let new = CompileCache::new(dir.path(), 1000, "rustc 1.1.0").unwrap();
+        assert_eq!(old.key("fn main() {}"), old.key("fn main() {}"));
+        assert_ne!(old.key("fn main() {}"), new.key("fn main() {}"));
+    }
+}
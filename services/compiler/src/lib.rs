@@ -1,18 +1,34 @@
+pub mod cache;
+pub mod pool;
 pub mod sanitizer;
 
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 
 pub fn error(status_code: StatusCode, msg: String) -> Error {
     Error {
         status_code,
         err: anyhow::anyhow!(msg),
+        json: None,
+    }
+}
+
+/// Returns an error with a JSON body instead of plain text.
+pub fn json_error<T: serde::Serialize>(status_code: StatusCode, value: &T) -> Error {
+    match serde_json::to_string(value) {
+        Ok(json) => Error {
+            status_code,
+            err: anyhow::anyhow!("{}", json),
+            json: Some(json),
+        },
+        Err(e) => e.into(),
     }
 }
 
 pub struct Error {
     status_code: StatusCode,
     err: anyhow::Error,
+    json: Option<String>,
 }
 
 impl IntoResponse for Error {
@@ -20,6 +36,14 @@ impl IntoResponse for Error {
         if self.status_code == StatusCode::INTERNAL_SERVER_ERROR {
             log::error!("Internal server error: {:?}", self.err);
         }
+        if let Some(json) = self.json {
+            return (
+                self.status_code,
+                [(header::CONTENT_TYPE, "application/json")],
+                json,
+            )
+                .into_response();
+        }
         (self.status_code, self.err.to_string()).into_response()
     }
 }
@@ -32,6 +56,7 @@ where
         Self {
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
             err: err.into(),
+            json: None,
         }
     }
 }
@@ -1,58 +1,115 @@
 use axum::extract::State;
-use axum::Router;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
 use bytes::Bytes;
 use clap::Parser as _;
-use http::{Method, StatusCode};
+use http::{header, HeaderMap, Method, StatusCode};
 use once_cell::sync::Lazy;
+use oort_compiler::diagnostics::CompileError;
 use oort_compiler::Compiler;
-use oort_compiler_service::{error, Error};
+use oort_compiler_service::cache::{CacheStats, CompileCache};
+use oort_compiler_service::pool::{self, CompilerPool};
+use oort_compiler_service::sanitizer;
+use oort_compiler_service::{error, json_error, Error};
+use oort_proto::compiler::CompileResponse;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
 use tempfile::NamedTempFile;
 use tokio::process::Command;
 use tower_http::cors::{Any, CorsLayer};
 
-const MAX_CONCURRENCY: usize = 3;
 static FORMAT_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
-static SEMAPHORE: Lazy<tokio::sync::Semaphore> =
-    Lazy::new(|| tokio::sync::Semaphore::new(MAX_CONCURRENCY));
+
+struct AppState {
+    pool: CompilerPool,
+    sanitizer_config: sanitizer::Config,
+    // Shared with blocking tasks, since cache lookups and inserts do file I/O.
+    cache: Arc<Mutex<CompileCache>>,
+}
+
+/// Returns the wasm along with any warnings as JSON if the client accepts it,
+/// otherwise just the wasm.
+fn compile_response(response: CompileResponse, json: bool) -> Result<Response, Error> {
+    if json {
+        Ok((
+            [(header::CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&response)?,
+        )
+            .into_response())
+    } else {
+        Ok(Bytes::from(response.wasm).into_response())
+    }
+}
 
 async fn post_compile(
-    State(compiler): State<Arc<Mutex<Compiler>>>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     mut code: String,
-) -> Result<Bytes, Error> {
-    let permit = SEMAPHORE.try_acquire();
-    if permit.is_err() {
-        return Err(error(
-            StatusCode::SERVICE_UNAVAILABLE,
-            "Service overloaded".to_string(),
-        ));
-    }
+) -> Result<Response, Error> {
+    let json = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(false, |accept| accept.contains("application/json"));
 
     if oort_code_encryption::is_encrypted(&code) {
         log::debug!("Encrypted code: {}", code);
         code = oort_code_encryption::decrypt(&code)?;
     }
     log::debug!("Code: {}", code);
-    oort_compiler_service::sanitizer::check(&code)?;
+    sanitizer::check(&code, &state.sanitizer_config)?;
+
+    // Cache hits are served even when the service is overloaded.
+    let (key, cached) = {
+        let cache = state.cache.clone();
+        let code = code.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut cache = cache.lock().unwrap();
+            let key = cache.key(&code);
+            let response = cache.get(&key);
+            (key, response)
+        })
+        .await?
+    };
+    if let Some(response) = cached {
+        match serde_json::from_slice::<CompileResponse>(&response) {
+            Ok(response) => {
+                log::info!("Compile cache hit for {}", key);
+                return compile_response(response, json);
+            }
+            Err(e) => log::warn!("Failed to parse compile cache entry {}: {}", key, e),
+        }
+    }
+
     let start_time = std::time::Instant::now();
-    let result = tokio::runtime::Handle::current()
-        .spawn_blocking(move || compiler.lock().unwrap().compile(&code))
-        .await?;
+    let result = state.pool.compile(code).await?;
     let elapsed = std::time::Instant::now() - start_time;
     match result {
-        Ok(wasm) => {
+        Ok(response) => {
             log::info!("Compile succeeded in {:?}", elapsed);
-            Ok(Bytes::copy_from_slice(&wasm))
+            let cache = state.cache.clone();
+            let cached_response = serde_json::to_vec(&response)?;
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = cache.lock().unwrap().insert(&key, &cached_response) {
+                    log::warn!("Failed to insert into compile cache: {:?}", e);
+                }
+            });
+            compile_response(response, json)
         }
         Err(e) => {
             log::info!("Compile failed in {:?}", elapsed);
             log::debug!("Compile failed: {}", e);
-            Err(error(StatusCode::BAD_REQUEST, e.to_string()))
+            match e.downcast_ref::<CompileError>() {
+                Some(CompileError(errors)) => Err(json_error(StatusCode::BAD_REQUEST, errors)),
+                None => Err(error(StatusCode::BAD_REQUEST, e.to_string())),
+            }
         }
     }
 }
 
+async fn get_cache_stats(State(state): State<Arc<AppState>>) -> Json<CacheStats> {
+    Json(state.cache.lock().unwrap().stats())
+}
+
 async fn post_format(code: String) -> Result<String, Error> {
     let _guard = FORMAT_LOCK.lock().await;
     let mut tmpfile = NamedTempFile::new()?;
@@ -83,6 +140,26 @@ async fn main() {
     struct Arguments {
         #[clap(short, long)]
         prepare: bool,
+        /// Number of compilers running in parallel, each with its own build
+        /// directory.
+        #[clap(long, default_value_t = 3)]
+        workers: usize,
+        /// Number of requests that can wait for a compiler before the service
+        /// reports being overloaded.
+        #[clap(long, default_value_t = 16)]
+        queue_depth: usize,
+        /// Directory for cached compiler output.
+        #[clap(long, default_value = "/tmp/oort-cache")]
+        cache_dir: String,
+        /// Maximum total size of cached compiler output, in megabytes.
+        #[clap(long, default_value_t = 1024)]
+        cache_size_mb: u64,
+        /// Additional attribute allowed by the sanitizer. May be repeated.
+        #[clap(long)]
+        allow_attribute: Vec<String>,
+        /// Additional macro forbidden by the sanitizer. May be repeated.
+        #[clap(long)]
+        forbid_macro: Vec<String>,
     }
     let args = Arguments::parse();
 
@@ -99,33 +176,59 @@ async fn main() {
         Err(_e) => {}
     };
 
-    let dir = "/tmp/oort-ai";
-    std::fs::create_dir_all(dir).unwrap();
-    let mut compiler = Compiler::new_with_dir(std::path::Path::new(dir));
+    let dir = std::path::Path::new("/tmp/oort-ai");
 
     if args.prepare {
-        compiler.enable_online();
-        compiler
-            .compile(include_str!("../../../shared/builtin_ai/src/empty.rs"))
-            .unwrap();
+        for path in pool::worker_dirs(dir, args.workers) {
+            std::fs::create_dir_all(&path).unwrap();
+            let mut compiler = Compiler::new_with_dir(&path);
+            compiler.enable_online();
+            compiler.compile(pool::PREPARE_CODE).unwrap();
+        }
         return;
     }
 
     log::info!("Starting oort_compiler_service v1");
 
+    let toolchain = oort_compiler::rustc_version().unwrap();
+    log::info!("Toolchain: {}", toolchain);
+    let cache = CompileCache::new(
+        std::path::Path::new(&args.cache_dir),
+        args.cache_size_mb * 1024 * 1024,
+        &toolchain,
+    )
+    .unwrap();
+    log::info!("Compile cache: {:?}", cache.stats());
+
+    let pool = CompilerPool::with_dirs(dir, args.workers, args.queue_depth).unwrap();
+    log::info!(
+        "Started {} compiler workers with queue depth {}",
+        args.workers,
+        args.queue_depth
+    );
+
+    let mut sanitizer_config = sanitizer::Config::default();
+    sanitizer_config.attributes.extend(args.allow_attribute);
+    sanitizer_config.forbidden_macros.extend(args.forbid_macro);
+
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
         .allow_origin(Any)
         .allow_headers(Any);
 
     let router = {
-        use axum::routing::post;
+        use axum::routing::{get, post};
         Router::new()
             .route("/compile", post(post_compile))
             .route("/format", post(post_format))
+            .route("/cache/stats", get(get_cache_stats))
             .layer(cors)
             .layer(tower_http::trace::TraceLayer::new_for_http())
-            .with_state(Arc::new(Mutex::new(compiler)))
+            .with_state(Arc::new(AppState {
+                pool,
+                sanitizer_config,
+                cache: Arc::new(Mutex::new(cache)),
+            }))
     };
 
     axum::Server::bind(&format!("0.0.0.0:{port}").parse().unwrap())
@@ -0,0 +1,217 @@
+//! Pool of compilers, each with its own build directory, so that multiple
+//! requests can be compiled in parallel.
+//!
+//! Requests wait for an idle compiler. Once `queue_depth` requests are already
+//! waiting, new requests are rejected with `SERVICE_UNAVAILABLE`.
+use crate::{error, Error};
+use http::StatusCode;
+use oort_compiler::Compiler;
+use oort_proto::compiler::CompileResponse;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Compiled in each worker directory to build the AI's dependencies.
+pub const PREPARE_CODE: &str = include_str!("../../../shared/builtin_ai/src/empty.rs");
+
+pub type CompilerPool = Pool<Compiler>;
+
+/// Hands out workers to one request at a time. Generic so the admission
+/// logic can be tested without running the compiler.
+pub struct Pool<T> {
+    idle: Arc<Mutex<Vec<T>>>,
+    // One permit per idle worker.
+    workers: Arc<Semaphore>,
+    // One permit per request either running or waiting for a worker.
+    admission: Semaphore,
+}
+
+impl<T: Send + 'static> Pool<T> {
+    pub fn new(workers: Vec<T>, queue_depth: usize) -> Self {
+        let n = workers.len();
+        Self {
+            idle: Arc::new(Mutex::new(workers)),
+            workers: Arc::new(Semaphore::new(n)),
+            admission: Semaphore::new(n + queue_depth),
+        }
+    }
+
+    /// Runs `f` on the next idle worker in a blocking task.
+    ///
+    /// Fails if the pool is overloaded or `f` panics. A worker that panicked
+    /// is dropped from the pool.
+    pub async fn run<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut T) -> R + Send + 'static,
+    {
+        let Ok(_admission) = self.admission.try_acquire() else {
+            return Err(error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Service overloaded".to_string(),
+            ));
+        };
+
+        let start_time = std::time::Instant::now();
+        let worker = self.workers.clone().acquire_owned().await?;
+        let elapsed = std::time::Instant::now() - start_time;
+        log::debug!("Waited {:?} for a compiler", elapsed);
+
+        let Some(item) = self.idle.lock().unwrap().pop() else {
+            return Err(error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "No idle compiler".to_string(),
+            ));
+        };
+        let mut checkout = Checkout {
+            item: Some(item),
+            idle: self.idle.clone(),
+            worker: Some(worker),
+        };
+        // The checkout is owned by the blocking task, so the worker goes
+        // back to the pool even if this future is dropped first.
+        let handle = tokio::runtime::Handle::current()
+            .spawn_blocking(move || f(checkout.item.as_mut().unwrap()));
+        match handle.await {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                log::error!("Compiler worker lost: {:?}", e);
+                Err(e.into())
+            }
+        }
+    }
+}
+
+impl Pool<Compiler> {
+    /// Creates a compiler for each of `num_workers` directories under `dir`.
+    ///
+    /// Directories that `--prepare` didn't set up, for example because the
+    /// service runs with more workers than it was prepared with, have their
+    /// dependencies built here so that requests don't pay for it.
+    pub fn with_dirs(dir: &Path, num_workers: usize, queue_depth: usize) -> anyhow::Result<Self> {
+        let mut compilers = vec![];
+        for path in worker_dirs(dir, num_workers) {
+            std::fs::create_dir_all(&path)?;
+            let mut compiler = Compiler::new_with_dir(&path);
+            compiler.compile(PREPARE_CODE)?;
+            compilers.push(compiler);
+        }
+        Ok(Self::new(compilers, queue_depth))
+    }
+
+    /// Compiles the code on the next idle compiler.
+    ///
+    /// The outer result fails if the pool is overloaded, the inner one if
+    /// compilation fails.
+    pub async fn compile(&self, code: String) -> Result<anyhow::Result<CompileResponse>, Error> {
+        self.run(move |compiler| compiler.compile_with_warnings(&code))
+            .await
+    }
+}
+
+/// A worker taken from the pool, along with the permit for it. Both are
+/// returned when it's dropped.
+struct Checkout<T> {
+    item: Option<T>,
+    idle: Arc<Mutex<Vec<T>>>,
+    worker: Option<OwnedSemaphorePermit>,
+}
+
+impl<T> Drop for Checkout<T> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            // The worker panicked and may be in a bad state, like a compiler
+            // with a broken build directory. Shrink the pool rather than
+            // reuse it.
+            if let Some(worker) = self.worker.take() {
+                worker.forget();
+            }
+            return;
+        }
+        if let Some(item) = self.item.take() {
+            self.idle.lock().unwrap().push(item);
+        }
+    }
+}
+
+pub fn worker_dirs(dir: &Path, num_workers: usize) -> Vec<std::path::PathBuf> {
+    (0..num_workers)
+        .map(|i| dir.join(format!("worker{i}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    // Waits until `n` requests hold an admission permit.
+    async fn wait_for_admitted(pool: &Pool<u32>, n: usize, capacity: usize) {
+        while capacity - pool.admission.available_permits() < n {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let pool = Pool::new(vec![1, 2], 0);
+        assert_eq!(pool.run(|x| *x * 10).await.ok(), Some(20));
+        // The worker was returned to the pool.
+        assert_eq!(pool.idle.lock().unwrap().len(), 2);
+        assert_eq!(pool.workers.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_queue_full() {
+        let pool = Arc::new(Pool::new(vec![1], 1));
+        let (release, released) = mpsc::channel::<()>();
+
+        // Occupies the only worker until released.
+        let busy = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run(move |x| {
+                    released.recv().unwrap();
+                    *x
+                })
+                .await
+                .ok()
+            }
+        });
+        wait_for_admitted(&pool, 1, 2).await;
+
+        // Waits in the queue for the worker.
+        let queued = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.run(|x| *x + 1).await.ok() }
+        });
+        wait_for_admitted(&pool, 2, 2).await;
+
+        // The queue is full.
+        let err = pool.run(|x| *x).await.err().unwrap();
+        assert_eq!(err.status_code, StatusCode::SERVICE_UNAVAILABLE);
+
+        release.send(()).unwrap();
+        assert_eq!(busy.await.unwrap(), Some(1));
+        assert_eq!(queued.await.unwrap(), Some(2));
+
+        // Admission permits are released once requests finish.
+        assert_eq!(pool.admission.available_permits(), 2);
+        assert_eq!(pool.run(|x| *x).await.ok(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_panic() {
+        let pool = Pool::new(vec![1, 2], 0);
+        assert!(pool
+            .run(|_| -> u32 { panic!("worker failed") })
+            .await
+            .is_err());
+
+        // The worker that panicked isn't reused.
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+        assert_eq!(pool.workers.available_permits(), 1);
+        assert_eq!(pool.admission.available_permits(), 2);
+        assert_eq!(pool.run(|x| *x).await.ok(), Some(1));
+    }
+}
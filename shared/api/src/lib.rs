@@ -145,13 +145,163 @@ pub enum SystemState {
 
     Id,
 
+    RadarMaxContacts,
+    RadarContactCount,
+
+    RadarContact0Class,
+    RadarContact0PositionX,
+    RadarContact0PositionY,
+    RadarContact0VelocityX,
+    RadarContact0VelocityY,
+    RadarContact0Rssi,
+    RadarContact0Snr,
+
+    RadarContact1Class,
+    RadarContact1PositionX,
+    RadarContact1PositionY,
+    RadarContact1VelocityX,
+    RadarContact1VelocityY,
+    RadarContact1Rssi,
+    RadarContact1Snr,
+
+    RadarContact2Class,
+    RadarContact2PositionX,
+    RadarContact2PositionY,
+    RadarContact2VelocityX,
+    RadarContact2VelocityY,
+    RadarContact2Rssi,
+    RadarContact2Snr,
+
+    RadarContact3Class,
+    RadarContact3PositionX,
+    RadarContact3PositionY,
+    RadarContact3VelocityX,
+    RadarContact3VelocityY,
+    RadarContact3Rssi,
+    RadarContact3Snr,
+
+    RadarContact4Class,
+    RadarContact4PositionX,
+    RadarContact4PositionY,
+    RadarContact4VelocityX,
+    RadarContact4VelocityY,
+    RadarContact4Rssi,
+    RadarContact4Snr,
+
+    RadarContact5Class,
+    RadarContact5PositionX,
+    RadarContact5PositionY,
+    RadarContact5VelocityX,
+    RadarContact5VelocityY,
+    RadarContact5Rssi,
+    RadarContact5Snr,
+
+    RadarContact6Class,
+    RadarContact6PositionX,
+    RadarContact6PositionY,
+    RadarContact6VelocityX,
+    RadarContact6VelocityY,
+    RadarContact6Rssi,
+    RadarContact6Snr,
+
+    RadarContact7Class,
+    RadarContact7PositionX,
+    RadarContact7PositionY,
+    RadarContact7VelocityX,
+    RadarContact7VelocityY,
+    RadarContact7Rssi,
+    RadarContact7Snr,
+
+    Radio0Key,
+    Radio1Key,
+    Radio2Key,
+    Radio3Key,
+    Radio4Key,
+    Radio5Key,
+    Radio6Key,
+    Radio7Key,
+
+    Radio0SendLength,
+    Radio0ReceiveLength,
+    Radio0TransmitTicks,
+    Radio0Bandwidth,
+    Radio0MaxMessageSize,
+
+    Radio1SendLength,
+    Radio1ReceiveLength,
+    Radio1TransmitTicks,
+    Radio1Bandwidth,
+    Radio1MaxMessageSize,
+
+    Radio2SendLength,
+    Radio2ReceiveLength,
+    Radio2TransmitTicks,
+    Radio2Bandwidth,
+    Radio2MaxMessageSize,
+
+    Radio3SendLength,
+    Radio3ReceiveLength,
+    Radio3TransmitTicks,
+    Radio3Bandwidth,
+    Radio3MaxMessageSize,
+
+    Radio4SendLength,
+    Radio4ReceiveLength,
+    Radio4TransmitTicks,
+    Radio4Bandwidth,
+    Radio4MaxMessageSize,
+
+    Radio5SendLength,
+    Radio5ReceiveLength,
+    Radio5TransmitTicks,
+    Radio5Bandwidth,
+    Radio5MaxMessageSize,
+
+    Radio6SendLength,
+    Radio6ReceiveLength,
+    Radio6TransmitTicks,
+    Radio6Bandwidth,
+    Radio6MaxMessageSize,
+
+    Radio7SendLength,
+    Radio7ReceiveLength,
+    Radio7TransmitTicks,
+    Radio7Bandwidth,
+    Radio7MaxMessageSize,
+
+    GasBudget,
+    GasUsed,
+
+    SelectLoadout,
+
+    DisabledSubsystems,
+
+    Energy,
+    MaxEnergy,
+    Heat,
+    MaxHeat,
+
     Size,
-    MaxSize = 128,
+    MaxSize = 256,
 }
 
+/// Version of the interface between AIs and the simulator: the layout of
+/// `SystemState` and the buffers that AIs export.
+///
+/// Bump this when making an incompatible change. The simulator refuses AIs
+/// built for a version it doesn't know how to run.
+///
+/// Version 2 added `SystemState::SelectLoadout`. Version 3 added
+/// `SystemState::DisabledSubsystems`. Version 4 added energy and heat.
+pub const ABI_VERSION: u32 = 4;
+
 #[allow(missing_docs)]
 pub const MAX_ENVIRONMENT_SIZE: usize = 1024;
 
+/// Upper limit on the length of a radio message sent with `send_bytes`.
+/// Individual radios may support a smaller size.
+pub const MAX_RADIO_MESSAGE_SIZE: usize = 1024;
+
 /// Identifiers for each class of ship.
 #[allow(missing_docs)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -235,10 +385,43 @@ pub enum Ability {
     Decoy,
     /// Cruiser only. Deflects projectiles for 1s. Reloads in 5s.
     Shield,
+    /// Frigate only, in scenarios with radio interference. Jams radio reception
+    /// of enemy ships for 2s. Reloads in 10s.
+    RadioJam,
 }
 
 /// Array of all ability types.
-pub const ABILITIES: &[Ability] = &[Ability::Boost, Ability::Decoy, Ability::Shield];
+pub const ABILITIES: &[Ability] = &[
+    Ability::Boost,
+    Ability::Decoy,
+    Ability::Shield,
+    Ability::RadioJam,
+];
+
+/// Parts of a ship that can be disabled by damage.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Subsystem {
+    /// Disabled engines reduce the ship's maximum acceleration.
+    Engines,
+    /// A disabled radar doesn't detect anything.
+    Radar,
+    /// A gun or missile launcher, using the same index as `fire`. Disabled
+    /// weapons can't fire.
+    Weapon(usize),
+}
+
+impl Subsystem {
+    #[doc(hidden)]
+    pub fn bit(self) -> u64 {
+        match self {
+            Subsystem::Engines => 1,
+            Subsystem::Radar => 2,
+            Subsystem::Weapon(index) if index < 62 => 4 << index,
+            Subsystem::Weapon(_) => 0,
+        }
+    }
+}
 
 /// Electronic Counter Measures (ECM) modes.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -249,6 +432,14 @@ pub enum EcmMode {
     /// Affected enemy radars will have a lower signal-to-noise ratio, making
     /// it harder to detect and track targets.
     Noise,
+    /// Affected enemy radars that detect this ship will report it further
+    /// away than it really is. The error grows over a few seconds and then
+    /// resets, and is larger when the jammer is strong compared to the radar
+    /// return.
+    RangeGatePullOff,
+    /// Affected enemy radars will see several false contacts near this ship.
+    /// They move randomly from tick to tick.
+    FalseTargets,
 }
 
 impl From<f64> for EcmMode {
@@ -256,6 +447,8 @@ impl From<f64> for EcmMode {
         match x as u32 {
             0 => EcmMode::None,
             1 => EcmMode::Noise,
+            2 => EcmMode::RangeGatePullOff,
+            3 => EcmMode::FalseTargets,
             _ => EcmMode::None,
         }
     }
@@ -288,10 +481,16 @@ pub type Message = [f64; 4];
 // Public for fuzzer.
 #[doc(hidden)]
 pub mod sys {
-    use crate::MAX_ENVIRONMENT_SIZE;
+    use crate::api::radio_internal::MAX_RADIOS;
+    use crate::{MAX_ENVIRONMENT_SIZE, MAX_RADIO_MESSAGE_SIZE};
 
     use super::SystemState;
 
+    // Read by the simulator before running the AI. AIs built before this was
+    // added don't export it.
+    #[no_mangle]
+    pub static ABI_VERSION: u32 = crate::ABI_VERSION;
+
     // TODO crashes rust-analyzer
     #[no_mangle]
     pub static mut SYSTEM_STATE: [u64; SystemState::MaxSize as usize] =
@@ -315,9 +514,49 @@ pub mod sys {
         write_system_state_u64(index, value.to_bits())
     }
 
+    // Calls to this import are replaced with a read of the gas counter when
+    // the simulator loads the AI.
+    #[cfg(target_arch = "wasm32")]
+    #[link(wasm_import_module = "oort")]
+    extern "C" {
+        #[link_name = "gas_remaining"]
+        fn import_gas_remaining() -> u32;
+    }
+
+    pub fn gas_remaining() -> u32 {
+        #[cfg(target_arch = "wasm32")]
+        {
+            unsafe { import_gas_remaining() }
+        }
+        // Native AIs don't use gas.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            read_system_state(SystemState::GasBudget) as u32
+        }
+    }
+
     #[no_mangle]
     pub static mut ENVIRONMENT: [u8; MAX_ENVIRONMENT_SIZE] = [0; MAX_ENVIRONMENT_SIZE];
 
+    // Payloads for send_bytes and receive_bytes, MAX_RADIO_MESSAGE_SIZE bytes per radio.
+    #[no_mangle]
+    pub static mut RADIO_SEND_BUFFER: [u8; MAX_RADIOS * MAX_RADIO_MESSAGE_SIZE] =
+        [0; MAX_RADIOS * MAX_RADIO_MESSAGE_SIZE];
+
+    #[no_mangle]
+    pub static mut RADIO_RECEIVE_BUFFER: [u8; MAX_RADIOS * MAX_RADIO_MESSAGE_SIZE] =
+        [0; MAX_RADIOS * MAX_RADIO_MESSAGE_SIZE];
+
+    pub fn radio_send_buffer(sel: usize) -> &'static mut [u8] {
+        let buffer = unsafe { &mut RADIO_SEND_BUFFER };
+        &mut buffer[(sel * MAX_RADIO_MESSAGE_SIZE)..((sel + 1) * MAX_RADIO_MESSAGE_SIZE)]
+    }
+
+    pub fn radio_receive_buffer(sel: usize) -> &'static [u8] {
+        let buffer = unsafe { &RADIO_RECEIVE_BUFFER };
+        &buffer[(sel * MAX_RADIO_MESSAGE_SIZE)..((sel + 1) * MAX_RADIO_MESSAGE_SIZE)]
+    }
+
     pub fn read_environment() -> &'static str {
         // Format is key=value\nkey=value\n... ending with a null byte.
         let environment = unsafe { &ENVIRONMENT };
@@ -399,8 +638,10 @@ pub mod rng_state {
 
 mod api {
     use super::sys::{read_system_state, write_system_state};
-    use super::{Ability, Class, EcmMode, SystemState};
-    use crate::sys::{read_system_state_u64, write_system_state_u64};
+    use super::{Ability, Class, EcmMode, Subsystem, SystemState};
+    use crate::sys::{
+        radio_receive_buffer, radio_send_buffer, read_system_state_u64, write_system_state_u64,
+    };
     use crate::{vec::*, ActiveAbilities, Message};
 
     /// The time between each simulation tick.
@@ -495,6 +736,8 @@ mod api {
     ///
     /// `index` selects the weapon.
     /// `heading` is in radians.
+    ///
+    /// Laser turrets turn towards the heading at a limited rate.
     pub fn aim(index: usize, heading: f64) {
         let state_index = match index {
             0 => SystemState::Aim0,
@@ -508,7 +751,13 @@ mod api {
 
     /// Fires a weapon.
     ///
-    /// `index` selects the weapon.
+    /// `index` selects the weapon. Guns are numbered first, then lasers,
+    /// then missile launchers. For example, a frigate with the point defense
+    /// loadout has its gun at index 0 and its lasers at 1 and 2.
+    ///
+    /// Lasers fire a beam for one tick, hitting the first enemy ship in its
+    /// path, and must cool down after firing for too long. The beam passes
+    /// through friendly ships.
     pub fn fire(index: usize) {
         let state_index = match index {
             0 => SystemState::Fire0,
@@ -520,9 +769,32 @@ mod api {
         write_system_state(state_index, 1.0);
     }
 
+    /// Chooses one of the preset weapon loadouts for the ship.
+    ///
+    /// Only has an effect on the ship's first tick, and only in scenarios
+    /// that allow the loadout. Fighters can choose between:
+    ///
+    /// - 0: Standard. One gun and one missile launcher.
+    /// - 1: Gunship. Two guns.
+    /// - 2: Missile boat. Two missile launchers.
+    ///
+    /// Frigates can choose between:
+    ///
+    /// - 0: Standard. A main gun, two turreted guns, and a missile launcher.
+    /// - 1: Point defense. The turreted guns are replaced by lasers.
+    ///
+    /// Other classes always keep their standard weapons.
+    ///
+    /// Afterwards `fire` and `aim` indices refer to the chosen weapons: guns,
+    /// then lasers, then missile launchers.
+    pub fn select_loadout(index: usize) {
+        write_system_state(SystemState::SelectLoadout, (index + 1) as f64);
+    }
+
     /// Returns the number of ticks until a weapon is ready to fire.
     ///
-    /// `index` selects the weapon. Returns 0 if the weapon is ready.
+    /// `index` selects the weapon. Returns 0 if the weapon is ready. For an
+    /// overheated laser this is the time until it has cooled down.
     pub fn reload_ticks(index: usize) -> u32 {
         let state_index = match index {
             0 => SystemState::ReloadTicks0,
@@ -551,6 +823,35 @@ mod api {
         read_system_state(SystemState::Fuel)
     }
 
+    /// Returns the stored energy.
+    ///
+    /// Firing guns and lasers, running the radar or ECM, transmitting on the
+    /// radio and keeping abilities active use energy, and fail if there isn't enough.
+    /// Energy is only limited in some scenarios, otherwise this is infinite.
+    pub fn energy() -> f64 {
+        read_system_state(SystemState::Energy)
+    }
+
+    /// Returns the maximum stored energy.
+    pub fn max_energy() -> f64 {
+        read_system_state(SystemState::MaxEnergy)
+    }
+
+    /// Returns the current heat.
+    ///
+    /// Using energy generates heat, which dissipates over time. Guns, lasers
+    /// and ECM run hotter than the radar and radio. Above half of `max_heat`
+    /// the radar power, rate of fire and laser damage drop, down to nothing
+    /// at `max_heat`.
+    pub fn heat() -> f64 {
+        read_system_state(SystemState::Heat)
+    }
+
+    /// Returns the heat at which the radar and weapons stop working.
+    pub fn max_heat() -> f64 {
+        read_system_state(SystemState::MaxHeat)
+    }
+
     /// Returns the heading the radar is pointed at.
     pub fn radar_heading() -> f64 {
         read_system_state(SystemState::RadarHeading)
@@ -628,6 +929,9 @@ mod api {
     }
 
     /// Returns the radar contact with the highest signal strength.
+    ///
+    /// Contacts hidden behind asteroids, planets, or other ships are not
+    /// returned.
     pub fn scan() -> Option<ScanResult> {
         if read_system_state(SystemState::RadarContactFound) == 0.0 {
             return None;
@@ -647,25 +951,122 @@ mod api {
         })
     }
 
+    #[doc(hidden)]
+    pub mod radar_internal {
+        use super::SystemState;
+
+        pub const MAX_RADAR_CONTACTS: usize = 8;
+
+        pub struct RadarContactIndices {
+            pub class: SystemState,
+            pub position: [SystemState; 2],
+            pub velocity: [SystemState; 2],
+            pub rssi: SystemState,
+            pub snr: SystemState,
+        }
+
+        pub fn radar_contact_indices(sel: usize) -> RadarContactIndices {
+            assert!(sel < MAX_RADAR_CONTACTS);
+            let stride = 7;
+            let offset = stride * sel;
+            let add_offset = |x| unsafe {
+                ::std::mem::transmute::<u16, SystemState>((x as u16) + offset as u16)
+            };
+            RadarContactIndices {
+                class: add_offset(SystemState::RadarContact0Class),
+                position: [
+                    add_offset(SystemState::RadarContact0PositionX),
+                    add_offset(SystemState::RadarContact0PositionY),
+                ],
+                velocity: [
+                    add_offset(SystemState::RadarContact0VelocityX),
+                    add_offset(SystemState::RadarContact0VelocityY),
+                ],
+                rssi: add_offset(SystemState::RadarContact0Rssi),
+                snr: add_offset(SystemState::RadarContact0Snr),
+            }
+        }
+    }
+
+    /// Returns the maximum number of contacts returned by [`scan_all`].
+    pub fn radar_max_contacts() -> usize {
+        read_system_state(SystemState::RadarMaxContacts) as usize
+    }
+
+    /// Sets the maximum number of contacts returned by [`scan_all`], up to 8.
+    ///
+    /// Tracking more than one contact splits the radar's time between them,
+    /// which raises the noise floor by `10 * log10(n)` dB and so reduces the
+    /// detection range. This also applies to [`scan`].
+    ///
+    /// It takes effect next tick.
+    pub fn set_radar_max_contacts(n: usize) {
+        let n = n.clamp(1, radar_internal::MAX_RADAR_CONTACTS);
+        write_system_state(SystemState::RadarMaxContacts, n as f64);
+    }
+
+    /// Returns up to [`radar_max_contacts`] radar contacts, strongest first.
+    ///
+    /// The first contact is the same one returned by [`scan`].
+    pub fn scan_all() -> Vec<ScanResult> {
+        let n = (read_system_state(SystemState::RadarContactCount) as usize)
+            .min(radar_internal::MAX_RADAR_CONTACTS);
+        (0..n)
+            .map(|i| {
+                let idxs = radar_internal::radar_contact_indices(i);
+                ScanResult {
+                    class: Class::from_f64(read_system_state(idxs.class)),
+                    position: vec2(
+                        read_system_state(idxs.position[0]),
+                        read_system_state(idxs.position[1]),
+                    ),
+                    velocity: vec2(
+                        read_system_state(idxs.velocity[0]),
+                        read_system_state(idxs.velocity[1]),
+                    ),
+                    rssi: read_system_state(idxs.rssi),
+                    snr: read_system_state(idxs.snr),
+                }
+            })
+            .collect()
+    }
+
     #[doc(hidden)]
     pub mod radio_internal {
         use super::SystemState;
 
         pub const MAX_RADIOS: usize = 8;
 
+        /// Written to the send slot by `send`. The message is in the data slots.
+        pub const SEND_MESSAGE: f64 = 1.0;
+        /// Written to the send slot by `send_bytes`. The message is in the
+        /// send buffer and may be empty.
+        pub const SEND_BYTES: f64 = 2.0;
+
         pub struct RadioIndices {
             pub channel: SystemState,
             pub send: SystemState,
             pub receive: SystemState,
             pub data: [SystemState; 4],
+            pub key: SystemState,
+            pub send_length: SystemState,
+            pub receive_length: SystemState,
+            pub transmit_ticks: SystemState,
+            pub bandwidth: SystemState,
+            pub max_message_size: SystemState,
         }
 
         pub fn radio_indices(sel: usize) -> RadioIndices {
             assert!(sel < MAX_RADIOS);
             let stride = 7;
             let offset = stride * sel;
-            let add_offset =
-                |x| unsafe { ::std::mem::transmute::<u8, SystemState>((x as u8) + offset as u8) };
+            let add_offset = |x| unsafe {
+                ::std::mem::transmute::<u16, SystemState>((x as u16) + offset as u16)
+            };
+            let message_offset = 5 * sel;
+            let add_message_offset = |x| unsafe {
+                ::std::mem::transmute::<u16, SystemState>((x as u16) + message_offset as u16)
+            };
             RadioIndices {
                 channel: add_offset(SystemState::Radio0Channel),
                 send: add_offset(SystemState::Radio0Send),
@@ -676,6 +1077,16 @@ mod api {
                     add_offset(SystemState::Radio0Data2),
                     add_offset(SystemState::Radio0Data3),
                 ],
+                key: unsafe {
+                    ::std::mem::transmute::<u16, SystemState>(
+                        SystemState::Radio0Key as u16 + sel as u16,
+                    )
+                },
+                send_length: add_message_offset(SystemState::Radio0SendLength),
+                receive_length: add_message_offset(SystemState::Radio0ReceiveLength),
+                transmit_ticks: add_message_offset(SystemState::Radio0TransmitTicks),
+                bandwidth: add_message_offset(SystemState::Radio0Bandwidth),
+                max_message_size: add_message_offset(SystemState::Radio0MaxMessageSize),
             }
         }
     }
@@ -705,15 +1116,42 @@ mod api {
         ) as usize
     }
 
+    /// Sets the encryption key used to send and receive radio transmissions.
+    ///
+    /// Encrypted messages are received as random noise by radios using a
+    /// different key. The default key of zero means no encryption, and
+    /// unencrypted messages can be read by any radio.
+    ///
+    /// Takes effect next tick.
+    pub fn set_radio_key(key: u64) {
+        write_system_state_u64(
+            radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize)
+                .key,
+            key,
+        );
+    }
+
+    /// Gets the current radio encryption key.
+    pub fn get_radio_key() -> u64 {
+        read_system_state_u64(
+            radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize)
+                .key,
+        )
+    }
+
     /// Sends a radio message.
     ///
-    /// The message will be received on the next tick.
+    /// The message will be received on the next tick. If several radios
+    /// transmit on the same channel, the strongest signal is received. In
+    /// scenarios with radio interference, the message is instead lost if
+    /// another transmission has a similar signal strength or the receiver is
+    /// being jammed.
     ///
     /// If you want to send arbitrary data, consider using [`send_bytes`] instead.
     pub fn send(msg: Message) {
         let idxs =
             radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize);
-        write_system_state(idxs.send, 1.0);
+        write_system_state(idxs.send, radio_internal::SEND_MESSAGE);
         write_system_state(idxs.data[0], msg[0]);
         write_system_state(idxs.data[1], msg[1]);
         write_system_state(idxs.data[2], msg[2]);
@@ -721,6 +1159,9 @@ mod api {
     }
 
     /// Returns the received radio message.
+    ///
+    /// Messages longer than 32 bytes are truncated, use
+    /// [`receive_message_bytes`] to get the full message.
     pub fn receive() -> Option<Message> {
         let idxs =
             radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize);
@@ -736,35 +1177,33 @@ mod api {
         }
     }
 
-    /// Sends a radio message.
-    /// The message will be zero-filled or truncated to be 32 bytes long.
+    /// Sends a radio message of arbitrary bytes.
     ///
-    /// The message will be received on the next tick.
+    /// The message will be truncated to [`get_radio_max_message_size`] bytes.
+    /// Radios transmit [`get_radio_bandwidth`] bytes per tick, so longer
+    /// messages take multiple ticks to send and are received on the tick after
+    /// the last byte is transmitted. While a transmission is in progress (see
+    /// [`get_radio_transmit_ticks`]) further messages sent on the same radio
+    /// are dropped.
     ///
     /// If you only want to send [`f64`]s consider using [`send`] instead.
     pub fn send_bytes(msg: &[u8]) {
-        let mut bytes = [[0; 8]; 4];
-        bytes
-            .iter_mut()
-            .flatten()
-            .zip(msg)
-            .for_each(|(b, m)| *b = *m);
-
-        let idxs = radio_internal::radio_indices(
-            read_system_state_u64(SystemState::SelectedRadio) as usize
-        );
-        write_system_state(idxs.send, 1.0);
-        write_system_state_u64(idxs.data[0], u64::from_ne_bytes(bytes[0]));
-        write_system_state_u64(idxs.data[1], u64::from_ne_bytes(bytes[1]));
-        write_system_state_u64(idxs.data[2], u64::from_ne_bytes(bytes[2]));
-        write_system_state_u64(idxs.data[3], u64::from_ne_bytes(bytes[3]));
+        let sel = read_system_state(SystemState::SelectedRadio) as usize;
+        let idxs = radio_internal::radio_indices(sel);
+        let buffer = radio_send_buffer(sel);
+        let n = msg.len().min(buffer.len());
+        buffer[..n].copy_from_slice(&msg[..n]);
+        write_system_state(idxs.send, radio_internal::SEND_BYTES);
+        write_system_state(idxs.send_length, n as f64);
     }
 
     /// Returns the received radio message.
+    ///
+    /// The message will be zero-filled or truncated to be 32 bytes long, use
+    /// [`receive_message_bytes`] to get the full message.
     pub fn receive_bytes() -> Option<[u8; 32]> {
-        let idxs = radio_internal::radio_indices(
-            read_system_state_u64(SystemState::SelectedRadio) as usize
-        );
+        let idxs =
+            radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize);
         if read_system_state(idxs.receive) != 0.0 {
             let mut bytes = [0; 32];
             bytes[0..8].copy_from_slice(&read_system_state_u64(idxs.data[0]).to_ne_bytes());
@@ -777,6 +1216,47 @@ mod api {
         }
     }
 
+    /// Returns the full received radio message, including messages sent with
+    /// [`send`].
+    ///
+    /// The returned slice is overwritten by the next tick's message.
+    pub fn receive_message_bytes() -> Option<&'static [u8]> {
+        let sel = read_system_state(SystemState::SelectedRadio) as usize;
+        let idxs = radio_internal::radio_indices(sel);
+        if read_system_state(idxs.receive) != 0.0 {
+            let buffer = radio_receive_buffer(sel);
+            let n = (read_system_state(idxs.receive_length) as usize).min(buffer.len());
+            Some(&buffer[..n])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of bytes the radio can transmit per tick.
+    pub fn get_radio_bandwidth() -> usize {
+        read_system_state(
+            radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize)
+                .bandwidth,
+        ) as usize
+    }
+
+    /// Returns the maximum length of a message sent with [`send_bytes`].
+    pub fn get_radio_max_message_size() -> usize {
+        read_system_state(
+            radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize)
+                .max_message_size,
+        ) as usize
+    }
+
+    /// Returns the number of ticks until the radio finishes transmitting the
+    /// current message, or zero if it is idle.
+    pub fn get_radio_transmit_ticks() -> u32 {
+        read_system_state(
+            radio_internal::radio_indices(read_system_state(SystemState::SelectedRadio) as usize)
+                .transmit_ticks,
+        ) as u32
+    }
+
     /// Returns the maximum linear acceleration (in m/s²).
     #[deprecated]
     pub fn max_acceleration() -> Vec2 {
@@ -816,6 +1296,25 @@ mod api {
         read_system_state(SystemState::CurrentTick) * TICK_LENGTH
     }
 
+    /// Returns the amount of gas left for this tick.
+    ///
+    /// Gas is consumed roughly once per instruction. A ship that runs out
+    /// crashes.
+    pub fn gas_remaining() -> u32 {
+        crate::sys::gas_remaining()
+    }
+
+    /// Returns the amount of gas available at the start of each tick. The
+    /// budget depends on the scenario and the ship class.
+    pub fn gas_budget() -> u32 {
+        read_system_state(SystemState::GasBudget) as u32
+    }
+
+    /// Returns the amount of gas used during the previous tick.
+    pub fn gas_used() -> u32 {
+        read_system_state(SystemState::GasUsed) as u32
+    }
+
     /// Activates a special ability.
     pub fn activate_ability(ability: Ability) {
         let mut active_abilities =
@@ -837,6 +1336,15 @@ mod api {
         ActiveAbilities(read_system_state_u64(SystemState::ActivateAbility))
     }
 
+    /// Returns true if the subsystem has been disabled by damage.
+    ///
+    /// Hits close to a subsystem damage it as well as the hull. Builtin
+    /// frigates and cruisers only have subsystems in scenarios with component
+    /// damage, and builtin fighters never do.
+    pub fn is_disabled(subsystem: Subsystem) -> bool {
+        read_system_state_u64(SystemState::DisabledSubsystems) & subsystem.bit() != 0
+    }
+
     /// Returns the position of the target set by the scenario.
     /// Only used in tutorials.
     pub fn target() -> Vec2 {
@@ -1053,6 +1561,24 @@ pub mod dbg {
         }
     }
 
+    // Accessors for hosts that link the AI natively, where the pointers
+    // written by update() aren't meaningful.
+
+    #[doc(hidden)]
+    pub fn text_buffer() -> &'static str {
+        unsafe { &TEXT_BUFFER }
+    }
+
+    #[doc(hidden)]
+    pub fn line_buffer() -> &'static [Line] {
+        unsafe { &LINE_BUFFER }
+    }
+
+    #[doc(hidden)]
+    pub fn drawn_text_buffer() -> &'static [Text] {
+        unsafe { &DRAWN_TEXT_BUFFER }
+    }
+
     #[doc(hidden)]
     pub fn reset() {
         unsafe {
@@ -1120,7 +1646,7 @@ pub mod prelude {
     #[doc(inline)]
     pub use super::vec::*;
     #[doc(inline)]
-    pub use super::{Ability, Class, EcmMode, Message};
+    pub use super::{Ability, Class, EcmMode, Message, Subsystem};
     #[doc(inline)]
     pub use crate::{debug, draw_text};
 
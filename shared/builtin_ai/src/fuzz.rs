@@ -10,8 +10,8 @@ impl Ship {
 
     pub fn tick(&mut self) {
         draw_triangle(vec2(gen(), gen()), gen(), 0xffffff);
-        for i in 0..(SystemState::MaxSize as u8) {
-            if i == SystemState::Explode as u8 {
+        for i in 0..(SystemState::Size as u16) {
+            if i == SystemState::Explode as u16 {
                 continue;
             }
             oort_api::sys::write_system_state(unsafe { std::mem::transmute(i) }, gen());
@@ -0,0 +1,182 @@
+//! Parsing of rustc's `--error-format=json` output.
+use oort_proto::compiler::{CompileErrors, Diagnostic, Severity, Span, Suggestion};
+use serde::Deserialize;
+
+/// Returned (wrapped in `anyhow::Error`) when rustc rejects the code.
+#[derive(Debug, Clone)]
+pub struct CompileError(pub CompileErrors);
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rustc failed: {}", self.0.rendered)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[derive(Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    code: Option<RustcCode>,
+    level: String,
+    spans: Vec<RustcSpan>,
+    children: Vec<RustcDiagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    is_primary: bool,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+}
+
+/// Parses rustc's stderr. Lines that aren't JSON diagnostics (e.g. linker
+/// output) are only included in the rendered text.
+pub fn parse(stderr: &str) -> CompileErrors {
+    let mut errors = CompileErrors::default();
+    for line in stderr.lines() {
+        let Ok(diagnostic) = serde_json::from_str::<RustcDiagnostic>(line) else {
+            errors.rendered.push_str(line);
+            errors.rendered.push('\n');
+            continue;
+        };
+        if let Some(rendered) = diagnostic.rendered.as_ref() {
+            errors.rendered.push_str(rendered);
+        }
+        if let Some(diagnostic) = translate(diagnostic) {
+            errors.diagnostics.push(diagnostic);
+        }
+    }
+    errors
+}
+
+fn translate(diagnostic: RustcDiagnostic) -> Option<Diagnostic> {
+    let severity = translate_level(&diagnostic.level)?;
+    // Summaries like "aborting due to 2 previous errors".
+    if diagnostic.spans.is_empty() && diagnostic.children.is_empty() {
+        return None;
+    }
+
+    let mut notes = vec![];
+    let mut suggestions = vec![];
+    for child in diagnostic.children.iter() {
+        let replacements: Vec<_> = child
+            .spans
+            .iter()
+            .filter_map(|span| Some((span, span.suggested_replacement.as_ref()?)))
+            .collect();
+        if replacements.is_empty() {
+            notes.push(format!("{}: {}", child.level, child.message));
+        }
+        for (span, replacement) in replacements {
+            suggestions.push(Suggestion {
+                message: child.message.clone(),
+                span: translate_span(span),
+                replacement: replacement.clone(),
+                machine_applicable: span.suggestion_applicability.as_deref()
+                    == Some("MachineApplicable"),
+            });
+        }
+    }
+
+    Some(Diagnostic {
+        severity,
+        message: diagnostic.message,
+        code: diagnostic.code.map(|c| c.code),
+        spans: diagnostic.spans.iter().map(translate_span).collect(),
+        notes,
+        suggestions,
+        rendered: diagnostic.rendered.unwrap_or_default(),
+    })
+}
+
+fn translate_level(level: &str) -> Option<Severity> {
+    match level {
+        "error" | "error: internal compiler error" => Some(Severity::Error),
+        "warning" => Some(Severity::Warning),
+        "note" => Some(Severity::Note),
+        "help" => Some(Severity::Help),
+        _ => None,
+    }
+}
+
+fn translate_span(span: &RustcSpan) -> Span {
+    Span {
+        file_name: span.file_name.clone(),
+        start_line: span.line_start,
+        start_column: span.column_start,
+        end_line: span.line_end,
+        end_column: span.column_end,
+        is_primary: span.is_primary,
+        label: span.label.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = r#"{"$message_type":"diagnostic","message":"cannot find value `x` in this scope","code":{"code":"E0425","explanation":"..."},"level":"error","spans":[{"file_name":"/tmp/oort-ai/ai/src/user.rs","byte_start":40,"byte_end":41,"line_start":3,"line_end":3,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":"help: a local variable with a similar name exists: `y`","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"consider importing","code":null,"level":"help","spans":[{"file_name":"/tmp/oort-ai/ai/src/user.rs","byte_start":0,"byte_end":0,"line_start":1,"line_end":1,"column_start":1,"column_end":1,"is_primary":true,"text":[],"label":null,"suggested_replacement":"use crate::x;\n","suggestion_applicability":"MaybeIncorrect","expansion":null}],"children":[],"rendered":null}],"rendered":"error[E0425]: cannot find value `x` in this scope\n"}
+{"$message_type":"diagnostic","message":"unused variable: `y`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"/tmp/oort-ai/ai/src/user.rs","byte_start":30,"byte_end":31,"line_start":2,"line_end":2,"column_start":13,"column_end":14,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"`#[warn(unused_variables)]` on by default","code":null,"level":"note","spans":[],"children":[],"rendered":null},{"message":"if this is intentional, prefix it with an underscore","code":null,"level":"help","spans":[{"file_name":"/tmp/oort-ai/ai/src/user.rs","byte_start":30,"byte_end":31,"line_start":2,"line_end":2,"column_start":13,"column_end":14,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_y","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":"warning: unused variable: `y`\n"}
+{"$message_type":"diagnostic","message":"aborting due to previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting due to previous error\n"}
+linker output"#;
+
+    #[test]
+    fn test_parse() {
+        let errors = parse(STDERR);
+        assert_eq!(errors.diagnostics.len(), 2);
+        assert_eq!(errors.errors().count(), 1);
+        assert!(errors.rendered.contains("aborting due to previous error"));
+        assert!(errors.rendered.contains("linker output"));
+
+        let error = &errors.diagnostics[0];
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(error.code.as_deref(), Some("E0425"));
+        assert_eq!(error.spans.len(), 1);
+        assert_eq!(error.spans[0].start_line, 3);
+        assert_eq!(error.spans[0].start_column, 9);
+        assert_eq!(error.suggestions.len(), 1);
+        assert_eq!(error.suggestions[0].replacement, "use crate::x;\n");
+        assert!(!error.suggestions[0].machine_applicable);
+
+        let warning = &errors.diagnostics[1];
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(
+            warning.notes,
+            vec!["note: `#[warn(unused_variables)]` on by default"]
+        );
+        assert_eq!(warning.suggestions.len(), 1);
+        assert_eq!(warning.suggestions[0].replacement, "_y");
+        assert!(warning.suggestions[0].machine_applicable);
+    }
+
+    #[test]
+    fn test_parse_warnings_only() {
+        let stderr = STDERR
+            .lines()
+            .filter(|line| line.contains(r#""level":"warning""#))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let warnings = parse(&stderr);
+        assert_eq!(warnings.errors().count(), 0);
+        assert_eq!(warnings.diagnostics.len(), 1);
+        assert_eq!(warnings.diagnostics[0].severity, Severity::Warning);
+        assert_eq!(
+            warnings.diagnostics[0].code.as_deref(),
+            Some("unused_variables")
+        );
+        assert!(warnings.rendered.contains("unused variable: `y`"));
+    }
+}
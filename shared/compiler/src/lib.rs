@@ -1,6 +1,17 @@
+pub mod diagnostics;
+
 use anyhow::{bail, Result};
+use diagnostics::CompileError;
+use oort_proto::compiler::CompileResponse;
 use std::path::{Path, PathBuf};
 
+/// Codegen options passed to rustc with `-C` when compiling AIs.
+pub const CODEGEN_OPTIONS: &[&str] = &[
+    "opt-level=s",
+    "link-arg=-zstack-size=16384",
+    "llvm-args=-rng-seed=42",
+];
+
 pub struct Compiler {
     #[allow(dead_code)]
     tmp_dir: Option<tempdir::TempDir>,
@@ -36,10 +47,15 @@ impl Compiler {
     }
 
     pub fn compile(&mut self, code: &str) -> Result<Vec<u8> /* wasm */> {
+        Ok(self.compile_with_warnings(code)?.wasm)
+    }
+
+    /// Like `compile`, but also returns the warnings rustc emitted.
+    pub fn compile_with_warnings(&mut self, code: &str) -> Result<CompileResponse> {
         let tmp_path = &self.dir;
 
         if std::fs::metadata(tmp_path.join("Cargo.toml")).is_ok() {
-            return self.compile_fast(code);
+            return self.compile_fast_with_warnings(code);
         }
 
         std::fs::write(
@@ -96,17 +112,26 @@ impl Compiler {
             ])
             .env(
                 "RUSTFLAGS",
-                "-C opt-level=s -C link-arg=-zstack-size=16384 -C llvm-args=-rng-seed=42",
+                CODEGEN_OPTIONS
+                    .iter()
+                    .map(|option| format!("-C {option}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
             )
             .output()?;
         if !output.status.success() {
             bail!("cargo failed: {}", std::str::from_utf8(&output.stderr)?);
         }
 
-        self.compile_fast(code)
+        self.compile_fast_with_warnings(code)
     }
 
     pub fn compile_fast(&mut self, code: &str) -> Result<Vec<u8> /* wasm */> {
+        Ok(self.compile_fast_with_warnings(code)?.wasm)
+    }
+
+    /// Like `compile_fast`, but also returns the warnings rustc emitted.
+    pub fn compile_fast_with_warnings(&mut self, code: &str) -> Result<CompileResponse> {
         let tmp_path = &self.dir;
         std::fs::write(tmp_path.join("ai/src/user.rs"), code.as_bytes())?;
         let rustc_bin_dir = Path::new(&self.rustc).parent().unwrap();
@@ -123,6 +148,7 @@ impl Compiler {
                 tmp_path.join("ai/src/lib.rs").as_os_str().to_str().unwrap(),
                 "--crate-type",
                 "cdylib",
+                "--error-format=json",
                 "-o",
                 tmp_path
                     .join("target/wasm32-unknown-unknown/release/oort_ai.wasm")
@@ -150,24 +176,41 @@ impl Compiler {
                         .to_str()
                         .unwrap()
                 ),
-                "-C",
-                "opt-level=s",
-                "-C",
-                "link-arg=-zstack-size=16384",
-                "-C",
-                "llvm-args=-rng-seed=42",
                 "--remap-path-prefix",
                 &format!("{}=/tmp/oort-ai", tmp_path.display()),
             ])
+            .args(CODEGEN_OPTIONS.iter().flat_map(|option| ["-C", option]))
             .output()?;
+        let stderr = std::str::from_utf8(&output.stderr)?;
         if !output.status.success() {
-            bail!("rustc failed: {}", std::str::from_utf8(&output.stderr)?);
+            return Err(CompileError(diagnostics::parse(stderr)).into());
         }
 
-        Ok(std::fs::read(tmp_path.join(
-            "target/wasm32-unknown-unknown/release/oort_ai.wasm",
-        ))?)
+        Ok(CompileResponse {
+            wasm: std::fs::read(
+                tmp_path.join("target/wasm32-unknown-unknown/release/oort_ai.wasm"),
+            )?,
+            warnings: diagnostics::parse(stderr),
+        })
+    }
+}
+
+/// Returns the verbose version of the rustc used to compile AIs, which
+/// includes the commit hash and LLVM version.
+pub fn rustc_version() -> Result<String> {
+    let rustc = find_rustc();
+    let rustc_bin_dir = Path::new(&rustc).parent().unwrap_or(Path::new(""));
+    let output = std::process::Command::new(&rustc)
+        .env(
+            "LD_LIBRARY_PATH",
+            &format!("{}/../lib", rustc_bin_dir.display()),
+        )
+        .arg("-vV")
+        .output()?;
+    if !output.status.success() {
+        bail!("rustc failed: {}", std::str::from_utf8(&output.stderr)?);
     }
+    Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
 }
 
 fn find_rlib(tmp_path: &Path, crate_name: &str) -> PathBuf {
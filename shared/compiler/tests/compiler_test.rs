@@ -0,0 +1,36 @@
+use oort_compiler::Compiler;
+use oort_proto::compiler::Severity;
+
+#[test]
+fn test_warnings() {
+    let mut compiler = Compiler::new();
+    let response = compiler
+        .compile_with_warnings(
+            r#"
+use oort_api::prelude::*;
+
+pub struct Ship {}
+
+impl Ship {
+    pub fn new() -> Ship {
+        Ship {}
+    }
+
+    pub fn tick(&mut self) {
+        let unused = 1;
+    }
+}
+"#,
+        )
+        .unwrap();
+    assert!(!response.wasm.is_empty());
+    assert_eq!(response.warnings.errors().count(), 0);
+    let warning = response
+        .warnings
+        .diagnostics
+        .iter()
+        .find(|d| d.code.as_deref() == Some("unused_variables"))
+        .unwrap();
+    assert_eq!(warning.severity, Severity::Warning);
+    assert_eq!(warning.spans[0].start_line, 12);
+}
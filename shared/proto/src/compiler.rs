@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+/// Region of a source file, with 1-based lines and columns.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file_name: String,
+    pub start_line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+    pub is_primary: bool,
+    pub label: Option<String>,
+}
+
+/// Replacement text that fixes (part of) a diagnostic.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    /// Whether rustc thinks the suggestion can be applied without review.
+    pub machine_applicable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Error code like "E0425", if any.
+    pub code: Option<String>,
+    pub spans: Vec<Span>,
+    /// Notes and help messages without a suggested replacement.
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+    /// Human-readable rendering, as rustc would print it.
+    pub rendered: String,
+}
+
+/// Body of a failed compile response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompileErrors {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Full compiler output, including anything that couldn't be parsed into
+    /// diagnostics.
+    pub rendered: String,
+}
+
+impl CompileErrors {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Body of a successful compile response, returned when the request accepts
+/// JSON. Otherwise the body is just the wasm.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompileResponse {
+    #[serde(with = "base64_bytes")]
+    pub wasm: Vec<u8>,
+    /// Warnings and notes from compiling the code. Empty if there were none.
+    pub warnings: CompileErrors,
+}
+
+mod base64_bytes {
+    use base64::Engine as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(s)
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -31,6 +31,9 @@ pub struct BulletData {
     pub team: i32,
     pub ttl: f32,
     pub color: u32,
+    /// Set once the bullet can no longer add to `SHOTS_HIT`, so that a bullet
+    /// that passes through a destroyed ship counts as a single hit.
+    pub hit: bool,
 }
 
 pub fn body(sim: &Simulation, handle: BulletHandle) -> &RigidBody {
@@ -1,8 +1,9 @@
 use crate::bullet::{self, BulletHandle};
 use crate::index_set::HasIndex;
+use crate::scenario::{DAMAGE_DEALT, SHOTS_HIT};
 use crate::ship::{ShipClass, ShipHandle};
 use crate::simulation::{Particle, Simulation, PHYSICS_TICK_LENGTH};
-use nalgebra::{Rotation2, UnitComplex};
+use nalgebra::{Rotation2, UnitComplex, Vector2};
 use oort_api::Ability;
 use rand::Rng;
 use rapier2d_f64::prelude::*;
@@ -65,6 +66,12 @@ pub fn planet_interaction_groups() -> InteractionGroups {
     )
 }
 
+/// Used for scene queries that should hit ship hulls, asteroids, and planets
+/// but not bullets or walls.
+pub fn hull_query_groups() -> InteractionGroups {
+    InteractionGroups::new(Group::ALL, SHIP_COLLISION_GROUP | PLANET_COLLISION_GROUP)
+}
+
 pub fn handle_collisions(sim: &mut Simulation, events: &[CollisionEvent]) {
     for event in events {
         if let CollisionEvent::Started(h1, h2, _flags) = event {
@@ -86,7 +93,11 @@ pub fn handle_collisions(sim: &mut Simulation, events: &[CollisionEvent]) {
                             false,
                         );
                     }
-                    bullet::data_mut(sim, bullet).team = sim.ship(ship).data().team;
+                    let team = sim.ship(ship).data().team;
+                    let data = bullet::data_mut(sim, bullet);
+                    data.team = team;
+                    // The new team didn't fire it.
+                    data.hit = true;
                     return;
                 }
                 if bullet::data(sim, bullet).team == sim.ship(ship).data().team {
@@ -107,27 +118,14 @@ pub fn handle_collisions(sim: &mut Simulation, events: &[CollisionEvent]) {
                         lifetime: (PHYSICS_TICK_LENGTH * 30.0) as f32,
                     });
                 }
-                let ship_destroyed = {
-                    let ship_data = sim.ship_data.get_mut(ship.index()).unwrap();
-                    ship_data.health -= damage;
-                    ship_data.health <= 0.0
-                };
+                let attacker_team = bullet::data(sim, bullet).team;
+                let (damage_dealt, ship_destroyed) =
+                    damage_ship(sim, ship, bullet_position, damage, attacker_team);
+                if damage_dealt > 0.0 && !bullet::data(sim, bullet).hit {
+                    sim.add_score(attacker_team, SHOTS_HIT, 1.0);
+                    bullet::data_mut(sim, bullet).hit = true;
+                }
                 if ship_destroyed {
-                    for _ in 0..10 {
-                        let rot = Rotation2::new(sim.rng.gen_range(0.0..TAU));
-                        let v = rot.transform_vector(&vector![sim.rng.gen_range(0.0..200.0), 0.0]);
-                        let p = sim.ship(ship).body().position().translation.vector
-                            + v * sim.rng.gen_range(0.0..0.1);
-                        let lifetime = (sim.ship_data.get(ship.index()).unwrap().mass.log2()
-                            * PHYSICS_TICK_LENGTH) as f32;
-                        sim.events.particles.push(Particle {
-                            position: p,
-                            velocity: v,
-                            color: vector![1.0, 1.0, 1.0, sim.rng.gen_range(0.5..1.0)],
-                            lifetime,
-                        });
-                    }
-                    sim.ship_mut(ship).data_mut().destroyed = true;
                     bullet::data_mut(sim, bullet).mass *= 0.5;
                     let rotation = UnitComplex::new(sim.rng.gen_range(-0.1..0.1));
                     let new_bullet_velocity = rotation.transform_vector(&bullet_velocity);
@@ -179,6 +177,54 @@ pub fn handle_collisions(sim: &mut Simulation, events: &[CollisionEvent]) {
     }
 }
 
+/// Damages a ship hit at `position` in the world frame, returning the damage
+/// dealt and whether the ship was destroyed.
+pub fn damage_ship(
+    sim: &mut Simulation,
+    ship: ShipHandle,
+    position: Vector2<f64>,
+    damage: f64,
+    attacker_team: i32,
+) -> (f64, bool) {
+    let hit = {
+        let body = sim.ship(ship).body();
+        body.position()
+            .rotation
+            .inverse_transform_vector(&(position - body.translation()))
+    };
+    let (ship_destroyed, damage_dealt) = {
+        let ship_data = sim.ship_data.get_mut(ship.index()).unwrap();
+        let health = ship_data.health.max(0.0);
+        let damage = ship_data.damage(hit, damage);
+        let damage_dealt = match ship_data.class {
+            ShipClass::Asteroid { .. } | ShipClass::Planet => 0.0,
+            _ => damage.min(health),
+        };
+        (ship_data.health <= 0.0, damage_dealt)
+    };
+    if damage_dealt > 0.0 {
+        sim.add_score(attacker_team, DAMAGE_DEALT, damage_dealt);
+    }
+    if ship_destroyed {
+        for _ in 0..10 {
+            let rot = Rotation2::new(sim.rng.gen_range(0.0..TAU));
+            let v = rot.transform_vector(&vector![sim.rng.gen_range(0.0..200.0), 0.0]);
+            let p = sim.ship(ship).body().position().translation.vector
+                + v * sim.rng.gen_range(0.0..0.1);
+            let lifetime =
+                (sim.ship_data.get(ship.index()).unwrap().mass.log2() * PHYSICS_TICK_LENGTH) as f32;
+            sim.events.particles.push(Particle {
+                position: p,
+                velocity: v,
+                color: vector![1.0, 1.0, 1.0, sim.rng.gen_range(0.5..1.0)],
+                lifetime,
+            });
+        }
+        sim.ship_mut(ship).data_mut().destroyed = true;
+    }
+    (damage_dealt, ship_destroyed)
+}
+
 pub fn add_walls(sim: &mut Simulation) {
     let world_size = sim.world_size();
     let mut make_edge = |x: f64, y: f64, a: f64| {
@@ -3,7 +3,7 @@ use crate::simulation::Simulation;
 use nalgebra::{vector, Point2, UnitComplex, Vector4};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Line {
     pub a: Point2<f64>,
     pub b: Point2<f64>,
@@ -12,7 +12,7 @@ pub struct Line {
 
 pub fn emit_ship(sim: &mut Simulation, handle: ShipHandle) {
     let mut lines = vec![];
-    lines.reserve(2 + sim.ship(handle).data().guns.len());
+    lines.reserve(2 + sim.ship(handle).data().guns.len() + sim.ship(handle).data().lasers.len());
     let body = sim.ship(handle).body();
     let p = body.position().translation.vector.into();
     lines.push(Line {
@@ -40,5 +40,15 @@ pub fn emit_ship(sim: &mut Simulation, handle: ShipHandle) {
             color: vector![1.0, 0.0, 0.0, 1.0],
         });
     }
+    for laser in sim.ship(handle).data().lasers.iter() {
+        let turret_rot = body.rotation() * UnitComplex::new(laser.heading);
+        let p0 = p + body.rotation().transform_vector(&laser.offset);
+        let p1 = p0 + turret_rot.transform_vector(&vector![10.0, 0.0]);
+        lines.push(Line {
+            a: p0,
+            b: p1,
+            color: vector![1.0, 0.3, 0.3, 1.0],
+        });
+    }
     sim.emit_debug_lines(handle, lines);
 }
@@ -5,6 +5,7 @@ pub trait HasIndex {
     fn index(self) -> Index;
 }
 
+#[derive(Clone)]
 pub struct IndexSet<T: HasIndex> {
     indices: Vec<T>,
     positions: HashMap<T, usize>,
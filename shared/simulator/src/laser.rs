@@ -0,0 +1,223 @@
+use crate::collision;
+use crate::scenario::{SHOTS_FIRED, SHOTS_HIT};
+use crate::ship::{self, ShipHandle};
+use crate::simulation::{Line, Simulation, PHYSICS_TICK_LENGTH};
+use nalgebra::{vector, Point2, UnitComplex, Vector2};
+use oort_api::{Ability, Subsystem};
+use rapier2d_f64::prelude::*;
+use std::f64::consts::{PI, TAU};
+
+// Energy used per point of damage, for ships with limited power.
+const LASER_ENERGY_FACTOR: f64 = 0.05;
+// Ship heat generated per unit of energy used by the laser.
+const LASER_HEAT_FACTOR: f64 = 1.0;
+
+/// A turreted beam weapon. Each tick it fires, it damages the first enemy hull
+/// along the beam. Firing heats the laser, and once overheated it can't fire until
+/// it has cooled down.
+#[derive(Clone, Debug)]
+pub struct Laser {
+    pub offset: Vector2<f64>,
+    /// Current heading of the turret relative to the ship.
+    pub heading: f64,
+    /// Heading the turret is turning towards, in the world frame.
+    pub aim_heading: f64,
+    /// Radians per second.
+    pub traverse_rate: f64,
+    pub min_angle: f64,
+    pub max_angle: f64,
+    /// Damage per second out to `falloff_range`, decreasing linearly to zero
+    /// at `max_range`.
+    pub damage: f64,
+    pub falloff_range: f64,
+    pub max_range: f64,
+    pub heat: f64,
+    pub max_heat: f64,
+    /// Heat added per tick of firing.
+    pub heat_per_tick: f64,
+    /// Heat lost per second.
+    pub cooling_rate: f64,
+    pub overheated: bool,
+    /// Set by `fire` and cleared when the beam is resolved.
+    pub firing: bool,
+}
+
+impl Default for Laser {
+    fn default() -> Self {
+        Self {
+            offset: vector![0.0, 0.0],
+            heading: 0.0,
+            aim_heading: 0.0,
+            traverse_rate: TAU / 2.0,
+            min_angle: 0.0,
+            max_angle: TAU,
+            damage: 400.0,
+            falloff_range: 1000.0,
+            max_range: 2500.0,
+            heat: 0.0,
+            max_heat: 120.0,
+            heat_per_tick: 1.0,
+            cooling_rate: 30.0,
+            overheated: false,
+            firing: false,
+        }
+    }
+}
+
+impl Laser {
+    /// Returns the damage per second at the given distance.
+    pub fn damage_at(&self, distance: f64) -> f64 {
+        if distance <= self.falloff_range {
+            self.damage
+        } else if distance >= self.max_range {
+            0.0
+        } else {
+            self.damage * (self.max_range - distance) / (self.max_range - self.falloff_range)
+        }
+    }
+
+    /// Returns the number of ticks until an overheated laser can fire again.
+    /// Like a gun's reload ticks, 0 means it can fire this tick.
+    pub fn cooldown_ticks(&self) -> u32 {
+        if !self.overheated || self.cooling_rate <= 0.0 {
+            return 0;
+        }
+        let excess = self.heat - ship::OVERHEAT_RECOVERY_FRACTION * self.max_heat;
+        ((excess / (self.cooling_rate * PHYSICS_TICK_LENGTH)).ceil() - 1.0).max(0.0) as u32
+    }
+
+    /// Turns the turret towards the aim heading, limited by the traverse rate
+    /// and the turret's arc.
+    fn traverse(&mut self, ship_heading: f64) {
+        let target = (self.aim_heading - ship_heading)
+            .rem_euclid(TAU)
+            .clamp(self.min_angle, self.max_angle);
+        let max_step = self.traverse_rate * PHYSICS_TICK_LENGTH;
+        if self.max_angle - self.min_angle >= TAU {
+            let delta = (target - self.heading + PI).rem_euclid(TAU) - PI;
+            self.heading = (self.heading + delta.clamp(-max_step, max_step)).rem_euclid(TAU);
+        } else {
+            let delta = target - self.heading;
+            self.heading += delta.clamp(-max_step, max_step);
+        }
+    }
+
+    fn cool(&mut self) {
+        self.heat = (self.heat - self.cooling_rate * PHYSICS_TICK_LENGTH).max(0.0);
+        if self.overheated && self.heat <= ship::OVERHEAT_RECOVERY_FRACTION * self.max_heat {
+            self.overheated = false;
+        }
+    }
+}
+
+#[inline(never)]
+pub fn tick(sim: &mut Simulation) {
+    let handle_snapshot: Vec<ShipHandle> = sim.ships.iter().cloned().collect();
+    for handle in handle_snapshot {
+        for index in 0..sim.ship(handle).data().lasers.len() {
+            if let Some(beam) = update_laser(sim, handle, index) {
+                fire_beam(sim, handle, beam);
+            }
+        }
+    }
+}
+
+struct Beam {
+    origin: Point2<f64>,
+    direction: Vector2<f64>,
+    laser: Laser,
+}
+
+/// Turns and cools the laser, returning the beam if it fires this tick.
+fn update_laser(sim: &mut Simulation, handle: ShipHandle, index: usize) -> Option<Beam> {
+    let (origin, rotation) = {
+        let body = sim.ship(handle).body();
+        (body.position().translation.vector, body.position().rotation)
+    };
+    let mut ship = sim.ship_mut(handle);
+    let data = ship.data_mut();
+    let disabled = data.is_disabled(Subsystem::Weapon(data.guns.len() + index));
+    let laser = &mut data.lasers[index];
+    laser.traverse(rotation.angle());
+    laser.cool();
+    if !std::mem::take(&mut laser.firing) || laser.overheated || disabled {
+        return None;
+    }
+    let mut throttle = 1.0;
+    if let Some(power) = data.power.as_mut() {
+        throttle = power.throttle();
+        let energy = laser.damage * throttle * PHYSICS_TICK_LENGTH * LASER_ENERGY_FACTOR;
+        if throttle <= 0.0 || !power.use_energy(energy, energy * LASER_HEAT_FACTOR) {
+            return None;
+        }
+    }
+    laser.heat += laser.heat_per_tick;
+    if laser.heat >= laser.max_heat {
+        laser.overheated = true;
+    }
+    let direction =
+        (rotation * UnitComplex::new(laser.heading)).transform_vector(&vector![1.0, 0.0]);
+    let mut laser = laser.clone();
+    // A hot ship fires a weaker beam.
+    laser.damage *= throttle;
+    Some(Beam {
+        origin: (origin + rotation.transform_vector(&laser.offset)).into(),
+        direction,
+        laser,
+    })
+}
+
+/// Casts the beam against hulls and damages the first enemy ship it hits.
+/// Each tick of firing counts as one shot.
+fn fire_beam(sim: &mut Simulation, handle: ShipHandle, beam: Beam) {
+    let team = sim.ship(handle).data().team;
+    sim.add_score(team, SHOTS_FIRED, 1.0);
+    let ray = Ray::new(beam.origin, beam.direction);
+    // The beam passes through the firing ship and its teammates.
+    let predicate = |_, collider: &Collider| {
+        collider.parent().map_or(true, |body| {
+            let ship = ShipHandle(body.0);
+            !sim.ships.contains(ship) || sim.ship(ship).data().team != team
+        })
+    };
+    let filter = QueryFilter::new()
+        .groups(collision::hull_query_groups())
+        .predicate(&predicate);
+    let hit = sim.query_pipeline.cast_ray(
+        &sim.bodies,
+        &sim.colliders,
+        &ray,
+        beam.laser.max_range,
+        true,
+        filter,
+    );
+
+    let mut distance = beam.laser.max_range;
+    if let Some((collider, toi)) = hit {
+        distance = toi;
+        let target = sim
+            .colliders
+            .get(collider)
+            .and_then(|x| x.parent())
+            .map(|x| ShipHandle(x.0))
+            .filter(|&x| sim.ships.contains(x));
+        if let Some(target) = target {
+            if sim.ship(target).data().team != team
+                && !sim.ship(target).is_ability_active(Ability::Shield)
+            {
+                let damage = beam.laser.damage_at(toi) * PHYSICS_TICK_LENGTH;
+                let (damage_dealt, _) =
+                    collision::damage_ship(sim, target, ray.point_at(toi).coords, damage, team);
+                if damage_dealt > 0.0 {
+                    sim.add_score(team, SHOTS_HIT, 1.0);
+                }
+            }
+        }
+    }
+
+    sim.events.beams.push(Line {
+        a: beam.origin,
+        b: ray.point_at(distance),
+        color: vector![1.0, 0.3, 0.3, 1.0],
+    });
+}
@@ -3,12 +3,14 @@ pub mod collision;
 pub mod color;
 pub mod debug;
 pub mod index_set;
+pub mod laser;
 pub mod model;
 pub mod radar;
 pub mod radio;
 pub mod rng;
 pub mod scenario;
 pub mod ship;
+pub mod ship_definition;
 pub mod simulation;
 pub mod snapshot;
 pub mod vm;
@@ -1,4 +1,5 @@
 use crate::ship::ShipClass;
+use crate::ship_definition::ShipClasses;
 use nalgebra::{vector, Rotation2, Vector2};
 use oorandom::Rand32;
 
@@ -135,8 +136,12 @@ pub fn big_planet() -> Vec<Vector2<f32>> {
     scale(10000.0, &vertices)
 }
 
-pub fn load(class: ShipClass) -> Vec<Vector2<f32>> {
-    match class {
+/// Unregistered custom classes use the model of their base class.
+pub fn load(class: ShipClass, ship_classes: &ShipClasses) -> Vec<Vector2<f32>> {
+    if let Some(registered) = ship_classes.lookup(class) {
+        return registered.definition.hull();
+    }
+    match class.base() {
         ShipClass::Fighter => fighter(),
         ShipClass::Frigate => frigate(),
         ShipClass::Cruiser => cruiser(),
@@ -145,11 +150,12 @@ pub fn load(class: ShipClass) -> Vec<Vector2<f32>> {
         ShipClass::Missile => missile(),
         ShipClass::Torpedo => torpedo(),
         ShipClass::Planet => big_planet(),
+        ShipClass::Custom { .. } => unreachable!(),
     }
 }
 
-pub fn radius(class: ShipClass) -> f32 {
-    load(class)
+pub fn radius(class: ShipClass, ship_classes: &ShipClasses) -> f32 {
+    load(class, ship_classes)
         .iter()
         .map(|&v| v.norm())
         .max_by(|a, b| a.partial_cmp(b).unwrap())
@@ -1,9 +1,11 @@
+use crate::index_set::HasIndex;
 use crate::ship::{self, ShipClass, ShipHandle};
-use crate::simulation::{Line, Simulation};
-use crate::{model, rng, simulation};
+use crate::simulation::{Line, Simulation, PHYSICS_TICK_LENGTH};
+use crate::{collision, model, rng, simulation};
 use nalgebra::Rotation2;
 use nalgebra::{vector, Point2, Vector2};
-use oort_api::{Ability, EcmMode};
+use oort_api::prelude::radar_internal::MAX_RADAR_CONTACTS;
+use oort_api::{Ability, EcmMode, Subsystem};
 use rand::Rng;
 use rand_distr::StandardNormal;
 use rapier2d_f64::parry;
@@ -16,9 +18,18 @@ use wide::{f32x4, CmpGt, CmpLt};
 const DEBUG: bool = false;
 const BACKGROUND_NOISE: f64 = 1e-13; // -100 dBm
 const JAMMER_COEFF: f64 = 1e-9; // Account for frequency hopping and pulse length
+const DECEPTION_COEFF: f64 = 1e-7; // Repeaters are coherent with the radar's pulses
 const BEARING_NOISE_FACTOR: f64 = 1e1 * (TAU / 360.0);
 const DISTANCE_NOISE_FACTOR: f64 = 1e4;
 const VELOCITY_NOISE_FACTOR: f64 = 1e2;
+const RANGE_GATE_MAX_OFFSET: f64 = 2e3;
+const RANGE_GATE_PERIOD: u32 = 180; // 3 seconds
+const FALSE_TARGET_COUNT: usize = 3;
+const RADAR_ENERGY_FACTOR: f64 = 1e-4; // Energy per second per watt
+const RADAR_HEAT_FACTOR: f64 = 0.5; // Heat per unit of energy
+const ECM_ENERGY_MULTIPLIER: f64 = 2.0;
+const ECM_HEAT_FACTOR: f64 = 1.0; // Heat per unit of energy
+const FALSE_TARGET_SPREAD: f64 = 2e3;
 
 #[derive(Clone, Debug)]
 pub struct Radar {
@@ -33,7 +44,9 @@ pub struct Radar {
     pub reliable_rssi: f64,
     pub min_rssi: f64,
     pub ecm_mode: EcmMode,
+    pub max_contacts: usize,
     pub result: Option<ScanResult>,
+    pub contacts: Vec<ScanResult>,
 }
 
 impl Default for Radar {
@@ -50,7 +63,9 @@ impl Default for Radar {
             reliable_rssi: from_dbm(-90.0),
             min_rssi: from_dbm(-100.0),
             ecm_mode: EcmMode::None,
+            max_contacts: 1,
             result: None,
+            contacts: Vec::new(),
         }
     }
 }
@@ -92,9 +107,21 @@ impl Radar {
         self.ecm_mode = mode;
     }
 
+    pub fn get_max_contacts(&self) -> usize {
+        self.max_contacts
+    }
+
+    pub fn set_max_contacts(&mut self, n: f64) {
+        self.max_contacts = (n as usize).clamp(1, MAX_RADAR_CONTACTS);
+    }
+
     pub fn scan(&self) -> Option<ScanResult> {
         self.result
     }
+
+    pub fn scan_all(&self) -> &[ScanResult] {
+        &self.contacts
+    }
 }
 
 struct RadarEmitter {
@@ -117,6 +144,7 @@ struct RadarEmitter {
 
 #[derive(Clone)]
 struct RadarReflector {
+    handle: ShipHandle,
     position: Point2<f64>,
     velocity: Vector2<f64>,
     radar_cross_section: f64,
@@ -156,8 +184,37 @@ fn from_dbm(x: f64) -> f64 {
     10.0_f64.powf((x - 30.0) / 10.0)
 }
 
+/// Uses the energy to run the ship's radar this tick. Returns the fraction of
+/// its power the radar transmits with, which drops as the ship heats up and is
+/// 0 if the radar is disabled or can't be powered.
+fn power_radar(sim: &mut Simulation, handle: ShipHandle) -> f64 {
+    let mut ship = sim.ship_mut(handle);
+    let data = ship.data_mut();
+    if data.is_disabled(Subsystem::Radar) {
+        return 0.0;
+    }
+    let (Some(radar), Some(power)) = (data.radar.as_ref(), data.power.as_mut()) else {
+        return 1.0;
+    };
+    let throttle = power.throttle();
+    let mut energy = radar.power * throttle * RADAR_ENERGY_FACTOR * PHYSICS_TICK_LENGTH;
+    let mut heat = energy * RADAR_HEAT_FACTOR;
+    if radar.ecm_mode != EcmMode::None {
+        energy *= ECM_ENERGY_MULTIPLIER;
+        heat = energy * ECM_HEAT_FACTOR;
+    }
+    if power.use_energy(energy, heat) {
+        throttle
+    } else {
+        0.0
+    }
+}
+
 #[inline(never)]
-fn build_reflector_team(sim: &Simulation) -> Vec<ReflectorTeam> {
+fn build_reflector_team(
+    sim: &Simulation,
+    outputs: &HashMap<ShipHandle, f64>,
+) -> Vec<ReflectorTeam> {
     let mut reflectors_by_team: HashMap<i32, Vec<RadarReflector>> = HashMap::new();
 
     for handle in sim.ships.iter() {
@@ -173,22 +230,21 @@ fn build_reflector_team(sim: &Simulation) -> Vec<ReflectorTeam> {
         if class == ShipClass::Planet {
             continue;
         }
-        let jammer = ship_data
-            .radar
-            .as_ref()
-            .and_then(|radar| match radar.ecm_mode {
-                EcmMode::None => None,
-                _ => Some(RadarJammer {
-                    width: radar.width,
-                    bearing: radar.heading,
-                    power: radar.power,
-                    ecm_mode: radar.ecm_mode,
-                }),
-            });
+        let output = outputs[handle];
+        let jammer = match ship_data.radar.as_ref() {
+            Some(radar) if output > 0.0 && radar.ecm_mode != EcmMode::None => Some(RadarJammer {
+                width: radar.width,
+                bearing: radar.heading,
+                power: radar.power * output,
+                ecm_mode: radar.ecm_mode,
+            }),
+            _ => None,
+        };
         reflectors_by_team
             .entry(ship_data.team)
             .or_default()
             .push(RadarReflector {
+                handle: *handle,
                 position: ship.position().vector.into(),
                 velocity: ship.velocity(),
                 radar_cross_section,
@@ -240,7 +296,11 @@ fn build_reflector_team(sim: &Simulation) -> Vec<ReflectorTeam> {
 #[inline(never)]
 pub fn tick(sim: &mut Simulation) {
     let handle_snapshot: Vec<ShipHandle> = sim.ships.iter().cloned().collect();
-    let reflector_teams = build_reflector_team(sim);
+    let outputs: HashMap<ShipHandle, f64> = handle_snapshot
+        .iter()
+        .map(|&handle| (handle, power_radar(sim, handle)))
+        .collect();
+    let reflector_teams = build_reflector_team(sim, &outputs);
     let mut candidates: Vec<(i32, usize)> = Vec::new();
     let planets = sim
         .ships
@@ -254,15 +314,24 @@ pub fn tick(sim: &mut Simulation) {
         let ship_data = ship.data();
 
         if let Some(radar) = ship_data.radar.as_ref() {
+            let power = radar.power * outputs[&handle];
+            if power <= 0.0 {
+                let mut ship = sim.ship_mut(handle);
+                let radar = ship.data_mut().radar.as_mut().unwrap();
+                radar.result = None;
+                radar.contacts.clear();
+                continue;
+            }
             let h = radar.heading;
             let w = radar.width;
             assert!(w < TAU / 2.0);
-            let max_distance = compute_max_detection_range(radar, 40.0 /*cruiser*/)
-                .min(radar.max_distance)
-                .min(simulation::MAX_WORLD_SIZE);
-            let reliable_distance = compute_reliable_detection_range(radar, 10.0 /*fighter*/)
+            let max_distance = compute_max_detection_range(radar, power, 40.0 /*cruiser*/)
                 .min(radar.max_distance)
                 .min(simulation::MAX_WORLD_SIZE);
+            let reliable_distance =
+                compute_reliable_detection_range(radar, power, 10.0 /*fighter*/)
+                    .min(radar.max_distance)
+                    .min(simulation::MAX_WORLD_SIZE);
 
             let start_bearing = h - 0.5 * w;
             let end_bearing = h + 0.5 * w;
@@ -274,7 +343,7 @@ pub fn tick(sim: &mut Simulation) {
                 handle,
                 team: ship_data.team,
                 center: ship.position().vector.into(),
-                power: radar.power,
+                power,
                 reliable_rssi: radar.reliable_rssi,
                 min_rssi: radar.min_rssi,
                 rx_cross_section: radar.rx_cross_section,
@@ -294,6 +363,7 @@ pub fn tick(sim: &mut Simulation) {
                     let ship_data = ship.data_mut();
                     let radar = ship_data.radar.as_mut().unwrap();
                     radar.result = None;
+                    radar.contacts.clear();
                 }
                 draw_emitter(sim, &emitter, reliable_distance);
                 continue;
@@ -301,9 +371,12 @@ pub fn tick(sim: &mut Simulation) {
 
             let mut rng = rng::new_rng(sim.tick());
 
-            let mut best_rssi = emitter.min_rssi;
-            let mut best_reflector: Option<&RadarReflector> = None;
-            let mut received_noise = BACKGROUND_NOISE * 2.0f64.powf(rng.gen_range(-1.0..1.0));
+            let max_contacts = radar.max_contacts;
+            let mut false_targets: Vec<(f64, RadarReflector)> = Vec::new();
+            let mut hits: Vec<(f64, &RadarReflector)> = Vec::new();
+            // Splitting the dwell time between several contacts raises the noise floor.
+            let mut received_noise =
+                BACKGROUND_NOISE * 2.0f64.powf(rng.gen_range(-1.0..1.0)) * max_contacts as f64;
             candidates.clear();
 
             let planet_contact = check_planet_contact(sim, &emitter, &planets);
@@ -315,6 +388,28 @@ pub fn tick(sim: &mut Simulation) {
 
             find_candidates(&emitter, &reflector_teams, &mut candidates);
 
+            for (team, reflector_index) in candidates.iter() {
+                let reflector = &reflector_teams[*team as usize].reflectors[*reflector_index];
+                if let Some(jammer) = reflector.jammer.as_ref() {
+                    if jammer.ecm_mode == EcmMode::FalseTargets
+                        && check_inside_beam_raw(
+                            &reflector.position,
+                            jammer.bearing,
+                            jammer.width,
+                            &emitter.center,
+                        )
+                    {
+                        make_false_targets(
+                            &emitter,
+                            reflector,
+                            jammer,
+                            &mut rng,
+                            &mut false_targets,
+                        );
+                    }
+                }
+            }
+
             for (team, reflector_index) in candidates.iter() {
                 let reflector = &reflector_teams[*team as usize].reflectors[*reflector_index];
                 if let Some(jammer) = reflector.jammer.as_ref() {
@@ -336,6 +431,7 @@ pub fn tick(sim: &mut Simulation) {
                                         / (TAU * jammer.width * r_sq);
                             }
                         }
+                        EcmMode::RangeGatePullOff | EcmMode::FalseTargets => {}
                     }
                 }
 
@@ -348,13 +444,37 @@ pub fn tick(sim: &mut Simulation) {
                 {
                     let rssi =
                         compute_rssi(&emitter, reflector) * 1.2f64.powf(rng.gen_range(-1.0..1.0));
-                    if rssi > best_rssi {
-                        best_reflector = Some(reflector);
-                        best_rssi = rssi;
+                    if rssi > emitter.min_rssi {
+                        hits.push((rssi, reflector));
                     }
                 }
             }
 
+            for (rssi, reflector) in false_targets.iter() {
+                if *rssi > emitter.min_rssi
+                    && emitter
+                        .square_distance_range
+                        .contains(&nalgebra::distance_squared(
+                            &emitter.center,
+                            &reflector.position,
+                        ))
+                {
+                    hits.push((*rssi, reflector));
+                }
+            }
+
+            // Strongest first. The sort is stable so ties go to the first candidate.
+            hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let mut visible = hits
+                .iter()
+                .filter(|(_, reflector)| !check_occluded(sim, &emitter, reflector))
+                .take(max_contacts);
+            let (best_rssi, best_reflector) = match visible.next() {
+                Some(&(rssi, reflector)) => (rssi, Some(reflector)),
+                None => (emitter.min_rssi, None),
+            };
+            let others: Vec<(f64, &RadarReflector)> = visible.cloned().collect();
+
             let best_rssi_dbm = into_dbm(best_rssi);
             let received_noise_dbm = into_dbm(received_noise);
             let signal_db = best_rssi_dbm - received_noise_dbm;
@@ -382,25 +502,56 @@ pub fn tick(sim: &mut Simulation) {
                 None
             } else {
                 best_reflector.map(|reflector| {
-                    make_scan_result(
+                    let mut result = make_scan_result(
                         &emitter,
                         reflector,
                         best_rssi_dbm,
                         received_noise_dbm,
                         &mut rng,
-                    )
+                    );
+                    apply_range_gate_pull_off(
+                        &emitter,
+                        reflector,
+                        best_rssi,
+                        sim.tick(),
+                        &mut result,
+                    );
+                    result
                 })
             };
 
+            let mut contacts: Vec<ScanResult> = result.iter().cloned().collect();
+            if result.is_some() {
+                for (rssi, reflector) in others {
+                    let rssi_dbm = into_dbm(rssi);
+                    if rssi_dbm - received_noise_dbm < 3.0
+                        || (rssi < emitter.reliable_rssi
+                            && decide_unreliable_rssi(&mut rng, rssi, emitter.reliable_rssi))
+                    {
+                        continue;
+                    }
+                    let mut contact = make_scan_result(
+                        &emitter,
+                        reflector,
+                        rssi_dbm,
+                        received_noise_dbm,
+                        &mut rng,
+                    );
+                    apply_range_gate_pull_off(&emitter, reflector, rssi, sim.tick(), &mut contact);
+                    contacts.push(contact);
+                }
+            }
+
             {
                 let mut ship = sim.ship_mut(emitter.handle);
                 let ship_data = ship.data_mut();
                 let radar = ship_data.radar.as_mut().unwrap();
                 radar.result = result;
+                radar.contacts = contacts.clone();
             }
 
             draw_emitter(sim, &emitter, reliable_distance);
-            if let Some(contact) = &result {
+            for contact in contacts.iter() {
                 draw_contact(sim, emitter.handle, contact);
             }
         }
@@ -496,6 +647,81 @@ fn make_scan_result(
     }
 }
 
+/// Power received from a deception jammer pointed at the emitter.
+fn compute_deception_rssi(
+    emitter: &RadarEmitter,
+    position: &Point2<f64>,
+    jammer: &RadarJammer,
+) -> f64 {
+    let r_sq = nalgebra::distance_squared(&emitter.center, position);
+    DECEPTION_COEFF * jammer.power * emitter.rx_cross_section / (TAU * jammer.width * r_sq)
+}
+
+/// Adds ghost contacts along the line of sight to the jammer, splitting the
+/// jammer's power between them.
+fn make_false_targets(
+    emitter: &RadarEmitter,
+    reflector: &RadarReflector,
+    jammer: &RadarJammer,
+    rng: &mut impl Rng,
+    false_targets: &mut Vec<(f64, RadarReflector)>,
+) {
+    let rssi =
+        compute_deception_rssi(emitter, &reflector.position, jammer) / FALSE_TARGET_COUNT as f64;
+    let dir = (reflector.position - emitter.center).normalize();
+    for _ in 0..FALSE_TARGET_COUNT {
+        let offset = rng.gen_range(-FALSE_TARGET_SPREAD..FALSE_TARGET_SPREAD);
+        let velocity = reflector.velocity
+            + vector![rng.sample(StandardNormal), rng.sample(StandardNormal)]
+                * VELOCITY_NOISE_FACTOR;
+        false_targets.push((
+            rssi,
+            RadarReflector {
+                handle: reflector.handle,
+                position: reflector.position + dir * offset,
+                velocity,
+                radar_cross_section: 0.0,
+                class: reflector.class,
+                jammer: None,
+            },
+        ));
+    }
+}
+
+/// Moves the contact away from the emitter if the reflector is a jammer using
+/// range-gate pull-off. The offset ramps up over `RANGE_GATE_PERIOD` ticks and
+/// is reduced when the radar return is stronger than the jammer.
+fn apply_range_gate_pull_off(
+    emitter: &RadarEmitter,
+    reflector: &RadarReflector,
+    rssi: f64,
+    tick: u32,
+    result: &mut ScanResult,
+) {
+    let jammer = match reflector.jammer.as_ref() {
+        Some(jammer) if jammer.ecm_mode == EcmMode::RangeGatePullOff => jammer,
+        _ => return,
+    };
+    if !check_inside_beam_raw(
+        &reflector.position,
+        jammer.bearing,
+        jammer.width,
+        &emitter.center,
+    ) {
+        return;
+    }
+    let jammer_rssi = compute_deception_rssi(emitter, &reflector.position, jammer);
+    let strength = (jammer_rssi / rssi).min(1.0);
+    let phase = (tick % RANGE_GATE_PERIOD) as f64 / RANGE_GATE_PERIOD as f64;
+    let dir = match (result.position - emitter.center.coords).try_normalize(1e-6) {
+        Some(dir) => dir,
+        None => return,
+    };
+    let rate = RANGE_GATE_MAX_OFFSET / (RANGE_GATE_PERIOD as f64 * PHYSICS_TICK_LENGTH);
+    result.position += dir * (RANGE_GATE_MAX_OFFSET * phase * strength);
+    result.velocity += dir * (rate * strength);
+}
+
 fn decide_unreliable_rssi(rng: &mut impl Rng, rssi: f64, reliable_rssi: f64) -> bool {
     rng.gen_bool(1.0 / (2.0 * reliable_rssi / rssi).log2())
 }
@@ -527,14 +753,13 @@ fn compute_rssi(emitter: &RadarEmitter, reflector: &RadarReflector) -> f64 {
         / (TAU * emitter.width * r_sq * r_sq)
 }
 
-fn compute_max_detection_range(radar: &Radar, target_cross_section: f64) -> f64 {
-    (radar.power * target_cross_section * radar.rx_cross_section
-        / (TAU * radar.width * radar.min_rssi))
+fn compute_max_detection_range(radar: &Radar, power: f64, target_cross_section: f64) -> f64 {
+    (power * target_cross_section * radar.rx_cross_section / (TAU * radar.width * radar.min_rssi))
         .powf(0.25)
 }
 
-fn compute_reliable_detection_range(radar: &Radar, target_cross_section: f64) -> f64 {
-    (radar.power * target_cross_section * radar.rx_cross_section
+fn compute_reliable_detection_range(radar: &Radar, power: f64, target_cross_section: f64) -> f64 {
+    (power * target_cross_section * radar.rx_cross_section
         / (TAU * radar.width * radar.reliable_rssi))
         .powf(0.25)
 }
@@ -555,7 +780,7 @@ fn check_planet_contact(
     planets
         .iter()
         .filter_map(|handle| {
-            let radius = model::radius(sim.ship(*handle).data().class) as f64;
+            let radius = model::radius(sim.ship(*handle).data().class, sim.ship_classes()) as f64;
             let planet_shape = parry::shape::Ball::new(radius);
             let planet_isometry = *sim.ship(*handle).body().position();
 
@@ -573,6 +798,28 @@ fn check_planet_contact(
         .min_by_key(|p| (emitter.center - p.coords).coords.norm_squared() as i64)
 }
 
+/// Returns true if another hull, asteroid, or planet is between the emitter
+/// and the reflector.
+fn check_occluded(sim: &Simulation, emitter: &RadarEmitter, reflector: &RadarReflector) -> bool {
+    let dp = reflector.position - emitter.center;
+    let distance = dp.magnitude();
+    if distance < 1e-3 {
+        return false;
+    }
+    let ray = Ray::new(emitter.center, dp / distance);
+    let predicate = |_, collider: &Collider| {
+        collider.parent().map_or(true, |body| {
+            body.0 != emitter.handle.index() && body.0 != reflector.handle.index()
+        })
+    };
+    let filter = QueryFilter::new()
+        .groups(collision::hull_query_groups())
+        .predicate(&predicate);
+    sim.query_pipeline
+        .cast_ray(&sim.bodies, &sim.colliders, &ray, distance, true, filter)
+        .is_some()
+}
+
 fn draw_emitter(sim: &mut Simulation, emitter: &RadarEmitter, reliable_distance: f64) {
     let color = vector![0.2, 0.66, 0.97, 1.0];
     let mut lines = vec![];
@@ -854,6 +1101,184 @@ mod test {
         assert!(!check_detection(70e3));
     }
 
+    #[test]
+    fn test_range_gate_pull_off() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+        let range = 20e3;
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![range, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(1),
+        );
+        sim.ship_mut(ship0).radar_mut().unwrap().heading = 0.0;
+        sim.ship_mut(ship0).radar_mut().unwrap().width = TAU / 360.0;
+        sim.ship_mut(ship1).radar_mut().unwrap().heading = PI;
+        sim.ship_mut(ship1).radar_mut().unwrap().width = TAU / 360.0;
+        sim.ship_mut(ship1).radar_mut().unwrap().ecm_mode = EcmMode::RangeGatePullOff;
+
+        let reported_range = |sim: &Simulation| {
+            sim.ship(ship0)
+                .radar()
+                .unwrap()
+                .result
+                .unwrap()
+                .position
+                .magnitude()
+        };
+
+        sim.step();
+        assert!((reported_range(&sim) - range).abs() < 100.0);
+        while sim.tick() < 150 {
+            sim.step();
+        }
+        assert!(reported_range(&sim) > range + 1000.0);
+        while sim.tick() < 190 {
+            sim.step();
+        }
+        assert!((reported_range(&sim) - range).abs() < 500.0);
+    }
+
+    #[test]
+    fn test_false_targets() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+        let range = 20e3;
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![range, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(1),
+        );
+        sim.ship_mut(ship0).radar_mut().unwrap().heading = 0.0;
+        sim.ship_mut(ship0).radar_mut().unwrap().width = TAU / 360.0;
+        sim.ship_mut(ship0)
+            .radar_mut()
+            .unwrap()
+            .set_max_contacts(8.0);
+        sim.ship_mut(ship1).radar_mut().unwrap().heading = PI;
+        sim.ship_mut(ship1).radar_mut().unwrap().width = TAU / 360.0;
+        sim.ship_mut(ship1).radar_mut().unwrap().ecm_mode = EcmMode::FalseTargets;
+
+        let mut false_contacts = 0;
+        for _ in 0..10 {
+            sim.step();
+            let ship = sim.ship(ship0);
+            let contacts = ship.radar().unwrap().scan_all();
+            assert!(contacts.len() > 1);
+            false_contacts += contacts
+                .iter()
+                .filter(|c| (c.position - vector![range, 0.0]).magnitude() > 100.0)
+                .count();
+        }
+        assert!(false_contacts >= 10);
+    }
+
+    #[test]
+    fn test_occlusion() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        ship::create(
+            &mut sim,
+            vector![2000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::target(1),
+        );
+        let asteroid = ship::create(
+            &mut sim,
+            vector![1000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::asteroid(0),
+        );
+        sim.ship_mut(ship0).radar_mut().unwrap().heading = 0.0;
+        sim.ship_mut(ship0).radar_mut().unwrap().width = TAU / 360.0;
+
+        // The asteroid hides the target but is visible itself.
+        sim.step();
+        let result = sim.ship(ship0).radar().unwrap().result.unwrap();
+        assert!(matches!(result.class, ShipClass::Asteroid { .. }));
+
+        // Only the target is visible beyond the asteroid.
+        sim.ship_mut(ship0).radar_mut().unwrap().min_distance = 1500.0;
+        sim.step();
+        assert!(sim.ship(ship0).radar().unwrap().result.is_none());
+
+        // Moving the asteroid out of the way reveals the target.
+        sim.ship_mut(asteroid)
+            .body()
+            .set_translation(vector![1000.0, 1000.0], true);
+        sim.step();
+        let result = sim.ship(ship0).radar().unwrap().result.unwrap();
+        assert_eq!(result.class, ShipClass::Target);
+    }
+
+    #[test]
+    fn test_multiple_contacts() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        for y in [-100.0, 0.0, 100.0] {
+            ship::create(
+                &mut sim,
+                vector![1000.0, y],
+                vector![0.0, 0.0],
+                0.0,
+                ship::target(1),
+            );
+        }
+        sim.ship_mut(ship0).radar_mut().unwrap().heading = 0.0;
+
+        sim.step();
+        assert_eq!(sim.ship(ship0).radar().unwrap().scan_all().len(), 1);
+
+        sim.ship_mut(ship0)
+            .radar_mut()
+            .unwrap()
+            .set_max_contacts(2.0);
+        sim.step();
+        assert_eq!(sim.ship(ship0).radar().unwrap().scan_all().len(), 2);
+
+        sim.ship_mut(ship0)
+            .radar_mut()
+            .unwrap()
+            .set_max_contacts(100.0);
+        sim.step();
+        let ship = sim.ship(ship0);
+        let radar = ship.radar().unwrap();
+        let contacts = radar.scan_all();
+        assert_eq!(contacts.len(), 3);
+        assert_eq!(contacts[0].position, radar.scan().unwrap().position);
+        assert!(contacts.windows(2).all(|w| w[0].rssi >= w[1].rssi));
+    }
+
     #[test]
     fn test_random() {
         let mut rng = crate::rng::new_rng(1);
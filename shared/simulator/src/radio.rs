@@ -1,11 +1,21 @@
-use crate::ship::ShipHandle;
+use crate::rng;
+use crate::ship::{Power, ShipHandle};
 use crate::simulation::Simulation;
 use nalgebra::Point2;
-use oort_api::Message;
+use oort_api::{Ability, Message};
+use rand::Rng;
 use std::collections::BTreeMap;
 use std::f64::consts::TAU;
 
 const NUM_CHANNELS: usize = 10;
+// A message is lost unless it is this much stronger than all other
+// transmissions and jamming on the channel combined (3 dB).
+const CAPTURE_RATIO: f64 = 2.0;
+const JAMMER_POWER: f64 = 200e3;
+// Energy per byte transmitted per watt of radio power.
+const RADIO_ENERGY_FACTOR: f64 = 3e-6;
+// Heat per unit of energy used by the radio.
+const RADIO_HEAT_FACTOR: f64 = 0.5;
 
 #[derive(Clone, Debug)]
 pub struct Radio {
@@ -13,8 +23,21 @@ pub struct Radio {
     pub(crate) rx_cross_section: f64,
     pub(crate) min_rssi: f64,
     pub(crate) channel: usize,
+    pub(crate) key: u64,
+    /// Bytes transmitted per tick.
+    pub(crate) bandwidth: usize,
+    pub(crate) max_message_size: usize,
     pub(crate) sent: Option<Message>,
+    pub(crate) sent_bytes: Option<Vec<u8>>,
+    pub(crate) transmission: Option<Transmission>,
     pub(crate) received: Option<Message>,
+    pub(crate) received_bytes: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Transmission {
+    payload: Vec<u8>,
+    ticks_remaining: u32,
 }
 
 impl Radio {
@@ -26,27 +49,107 @@ impl Radio {
         self.channel = channel.clamp(0, NUM_CHANNELS - 1);
     }
 
+    pub fn get_key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn set_key(&mut self, key: u64) {
+        self.key = key;
+    }
+
     pub fn set_sent(&mut self, sent: Option<Message>) {
         self.sent = sent;
     }
 
+    pub fn set_sent_bytes(&mut self, mut sent: Vec<u8>) {
+        sent.truncate(self.max_message_size);
+        self.sent_bytes = Some(sent);
+    }
+
     pub fn get_received(&self) -> Option<Message> {
         self.received
     }
+
+    pub fn get_received_bytes(&self) -> Option<&[u8]> {
+        self.received_bytes.as_deref()
+    }
+
+    pub fn get_bandwidth(&self) -> usize {
+        self.bandwidth
+    }
+
+    pub fn get_max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Returns the number of ticks left in the current transmission.
+    pub fn get_transmit_ticks(&self) -> u32 {
+        self.transmission
+            .as_ref()
+            .map(|t| t.ticks_remaining)
+            .unwrap_or(0)
+    }
+
+    // Starts transmitting a newly sent message. Messages sent while the radio
+    // is busy or without enough energy are dropped.
+    fn start_transmission(&mut self, power: Option<&mut Power>) {
+        let payload = self
+            .sent_bytes
+            .take()
+            .or_else(|| self.sent.take().map(|msg| message_to_bytes(&msg)));
+        self.sent = None;
+        if self.transmission.is_some() {
+            return;
+        }
+        if let Some(payload) = payload {
+            let energy = RADIO_ENERGY_FACTOR * self.power * payload.len() as f64;
+            if let Some(power) = power {
+                if !power.use_energy(energy, energy * RADIO_HEAT_FACTOR) {
+                    return;
+                }
+            }
+            let ticks = (payload.len() + self.bandwidth - 1) / self.bandwidth;
+            self.transmission = Some(Transmission {
+                payload,
+                ticks_remaining: ticks.max(1) as u32,
+            });
+        }
+    }
+}
+
+pub fn message_to_bytes(msg: &Message) -> Vec<u8> {
+    msg.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// Converts the first 32 bytes of a message, zero-filled if shorter.
+pub fn bytes_to_message(bytes: &[u8]) -> Message {
+    let mut buf = [0; 32];
+    let n = bytes.len().min(buf.len());
+    buf[..n].copy_from_slice(&bytes[..n]);
+    [0, 1, 2, 3].map(|i| f64::from_le_bytes(buf[(i * 8)..(i * 8 + 8)].try_into().unwrap()))
 }
 
 struct RadioSender {
     position: Point2<f64>,
     power: f64,
-    msg: Message,
+    key: u64,
+    // Set on the final tick of a transmission.
+    payload: Option<Vec<u8>>,
 }
 
 struct RadioReceiver {
     handle: ShipHandle,
     radio_index: usize,
+    team: i32,
     position: Point2<f64>,
     rx_cross_section: f64,
     min_rssi: f64,
+    key: u64,
+}
+
+struct RadioJammer {
+    team: i32,
+    position: Point2<f64>,
 }
 
 #[inline(never)]
@@ -55,10 +158,26 @@ pub fn tick(sim: &mut Simulation) {
 
     let mut receivers: BTreeMap<usize, Vec<RadioReceiver>> = BTreeMap::new();
     let mut senders: BTreeMap<usize, Vec<RadioSender>> = BTreeMap::new();
+    let mut jammers: Vec<RadioJammer> = Vec::new();
+    let interference = sim.radio_interference();
+
+    for handle in handle_snapshot.iter().cloned() {
+        let mut ship = sim.ship_mut(handle);
+        let data = ship.data_mut();
+        for radio in data.radios.iter_mut() {
+            radio.start_transmission(data.power.as_mut());
+        }
+    }
 
     for handle in handle_snapshot.iter().cloned() {
         let ship = sim.ship(handle);
         let ship_data = ship.data();
+        if interference && ship.is_ability_active(Ability::RadioJam) {
+            jammers.push(RadioJammer {
+                team: ship_data.team,
+                position: ship.position().vector.into(),
+            });
+        }
         for (radio_index, radio) in ship_data.radios.iter().enumerate() {
             receivers
                 .entry(radio.channel)
@@ -66,60 +185,102 @@ pub fn tick(sim: &mut Simulation) {
                 .push(RadioReceiver {
                     handle,
                     radio_index,
+                    team: ship_data.team,
                     position: ship.position().vector.into(),
                     rx_cross_section: radio.rx_cross_section,
                     min_rssi: radio.min_rssi,
+                    key: radio.key,
                 });
 
-            if let Some(msg) = radio.sent {
+            if let Some(transmission) = radio.transmission.as_ref() {
                 senders.entry(radio.channel).or_default().push(RadioSender {
                     position: ship.position().vector.into(),
                     power: radio.power,
-                    msg,
+                    key: radio.key,
+                    payload: if transmission.ticks_remaining == 1 {
+                        Some(transmission.payload.clone())
+                    } else {
+                        None
+                    },
                 });
             }
         }
     }
 
+    let mut rng = rng::new_rng(sim.tick());
     for channel in 0..NUM_CHANNELS {
         for rx in receivers.get(&channel).unwrap_or(&Vec::new()) {
-            let mut best_msg = None;
+            let mut best_sender = None;
             let mut best_rssi = rx.min_rssi;
+            let mut total_rssi: f64 = jammers
+                .iter()
+                .filter(|jammer| jammer.team != rx.team)
+                .map(|jammer| compute_jammer_rssi(jammer, rx))
+                .sum();
             for tx in senders.get(&channel).unwrap_or(&Vec::new()) {
                 let rssi = compute_rssi(tx, rx);
+                total_rssi += rssi;
                 if rssi > best_rssi {
                     best_rssi = rssi;
-                    best_msg = Some(tx.msg);
+                    best_sender = Some(tx);
                 }
             }
-            sim.ship_mut(rx.handle)
-                .radio_mut(rx.radio_index)
-                .as_mut()
-                .unwrap()
-                .received = best_msg;
+            let received = best_sender
+                .filter(|_| !interference || best_rssi >= CAPTURE_RATIO * (total_rssi - best_rssi))
+                .and_then(|tx| tx.payload.as_ref().map(|payload| (tx.key, payload)))
+                .map(|(key, payload)| {
+                    if key == 0 || key == rx.key {
+                        payload.clone()
+                    } else {
+                        (0..payload.len()).map(|_| rng.gen()).collect()
+                    }
+                });
+            let radio = sim.ship_mut(rx.handle).radio_mut(rx.radio_index).unwrap();
+            radio.received = received.as_deref().map(bytes_to_message);
+            radio.received_bytes = received;
         }
     }
 
     for handle in handle_snapshot.iter().cloned() {
         for radio in sim.ship_mut(handle).data_mut().radios.iter_mut() {
-            radio.sent = None;
+            if let Some(transmission) = radio.transmission.as_mut() {
+                transmission.ticks_remaining -= 1;
+                if transmission.ticks_remaining == 0 {
+                    radio.transmission = None;
+                }
+            }
         }
     }
 }
 
 fn compute_rssi(sender: &RadioSender, receiver: &RadioReceiver) -> f64 {
-    let r_sq = nalgebra::distance_squared(&sender.position, &receiver.position);
+    let r_sq = nalgebra::distance_squared(&sender.position, &receiver.position).max(1.0);
     sender.power * receiver.rx_cross_section / (TAU * r_sq)
 }
 
+fn compute_jammer_rssi(jammer: &RadioJammer, receiver: &RadioReceiver) -> f64 {
+    let r_sq = nalgebra::distance_squared(&jammer.position, &receiver.position).max(1.0);
+    JAMMER_POWER * receiver.rx_cross_section / (TAU * r_sq)
+}
+
 #[cfg(test)]
 mod test {
-    use crate::ship;
+    use crate::scenario;
+    use crate::ship::{self, ShipData};
     use crate::simulation::Code;
     use crate::simulation::Simulation;
     use nalgebra::vector;
+    use oort_api::Ability;
     use test_log::test;
 
+    fn new_interference_sim() -> Box<Simulation> {
+        let scenario = scenario::load_definition(
+            "name = \"radio_interference\"\nvictory = \"none\"\nradio_interference = true",
+        )
+        .unwrap();
+        Simulation::new_with_scenario(scenario, 0, &[Code::None, Code::None])
+    }
+
     #[test]
     fn test_basic() {
         let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
@@ -197,6 +358,187 @@ mod test {
         assert_eq!(sim.ship(ship0).radio(0).unwrap().received, Some(msg));
     }
 
+    #[test]
+    fn test_encryption() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![1000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let enemy = ship::create(
+            &mut sim,
+            vector![0.0, 1000.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(1),
+        );
+
+        let msg = [42.0, 43.0, 44.0, 45.0];
+        sim.ship_mut(ship0).radio_mut(0).unwrap().set_key(1234);
+        sim.ship_mut(ship1).radio_mut(0).unwrap().set_key(1234);
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg);
+
+        sim.step();
+
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, Some(msg));
+        let garbled = sim.ship(enemy).radio(0).unwrap().received;
+        assert!(garbled.is_some());
+        assert_ne!(garbled, Some(msg));
+
+        // Unencrypted messages can be read by everyone.
+        sim.ship_mut(ship1).radio_mut(0).unwrap().set_key(0);
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg);
+
+        sim.step();
+
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, Some(msg));
+        assert_eq!(sim.ship(enemy).radio(0).unwrap().received, Some(msg));
+    }
+
+    #[test]
+    fn test_collision() {
+        let mut sim = new_interference_sim();
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![1000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship2 = ship::create(
+            &mut sim,
+            vector![-1100.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+
+        let msg1 = [1.0, 2.0, 3.0, 4.0];
+        let msg2 = [5.0, 6.0, 7.0, 8.0];
+
+        // Similar signal strength.
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg1);
+        sim.ship_mut(ship2).radio_mut(0).unwrap().sent = Some(msg2);
+        sim.step();
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, None);
+
+        // The closer sender captures the receiver.
+        sim.ship_mut(ship2)
+            .body()
+            .set_translation(vector![-5000.0, 0.0], true);
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg1);
+        sim.ship_mut(ship2).radio_mut(0).unwrap().sent = Some(msg2);
+        sim.step();
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, Some(msg1));
+    }
+
+    #[test]
+    fn test_no_interference() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![1000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship2 = ship::create(
+            &mut sim,
+            vector![-1100.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+
+        // The strongest signal is received even if others are nearly as strong.
+        let msg1 = [1.0, 2.0, 3.0, 4.0];
+        let msg2 = [5.0, 6.0, 7.0, 8.0];
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg1);
+        sim.ship_mut(ship2).radio_mut(0).unwrap().sent = Some(msg2);
+        sim.step();
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, Some(msg1));
+        assert!(ship::frigate(0).abilities.is_empty());
+    }
+
+    #[test]
+    fn test_jamming() {
+        let mut sim = new_interference_sim();
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![2000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let jammer = ship::create(
+            &mut sim,
+            vector![0.0, 3000.0],
+            vector![0.0, 0.0],
+            0.0,
+            ShipData {
+                abilities: vec![ship::radio_jam()],
+                ..ship::frigate(1)
+            },
+        );
+        let ally = ship::create(
+            &mut sim,
+            vector![0.0, 3500.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(1),
+        );
+
+        let msg = [42.0, 43.0, 44.0, 45.0];
+        sim.ship_mut(jammer).activate_ability(Ability::RadioJam);
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg);
+        sim.ship_mut(jammer).radio_mut(0).unwrap().sent = Some(msg);
+        sim.step();
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, None);
+        assert_eq!(sim.ship(ally).radio(0).unwrap().received, Some(msg));
+
+        for _ in 0..120 {
+            sim.step();
+        }
+        sim.ship_mut(ship1).radio_mut(0).unwrap().sent = Some(msg);
+        sim.step();
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().received, Some(msg));
+    }
+
     #[test]
     fn test_multi_radio() {
         let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
@@ -238,4 +580,91 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_long_message() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![1000.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+
+        let bandwidth = sim.ship(ship1).radio(0).unwrap().get_bandwidth();
+        let msg: Vec<u8> = (0..(bandwidth * 3 - 1)).map(|i| i as u8).collect();
+        sim.ship_mut(ship1)
+            .radio_mut(0)
+            .unwrap()
+            .set_sent_bytes(msg.clone());
+
+        for i in 0..2 {
+            sim.step();
+            assert_eq!(sim.ship(ship0).radio(0).unwrap().get_received_bytes(), None);
+            assert_eq!(
+                sim.ship(ship1).radio(0).unwrap().get_transmit_ticks(),
+                2 - i
+            );
+            // Dropped because the radio is busy.
+            sim.ship_mut(ship1)
+                .radio_mut(0)
+                .unwrap()
+                .set_sent(Some([1.0; 4]));
+        }
+
+        sim.step();
+        assert_eq!(
+            sim.ship(ship0).radio(0).unwrap().get_received_bytes(),
+            Some(msg.as_slice())
+        );
+        assert_eq!(
+            sim.ship(ship0).radio(0).unwrap().received,
+            Some(super::bytes_to_message(&msg))
+        );
+        assert_eq!(sim.ship(ship1).radio(0).unwrap().get_transmit_ticks(), 0);
+
+        sim.step();
+        assert_eq!(sim.ship(ship0).radio(0).unwrap().get_received_bytes(), None);
+    }
+
+    #[test]
+    fn test_max_message_size() {
+        let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            ship::fighter(0),
+        );
+
+        let max_message_size = sim.ship(ship0).radio(0).unwrap().get_max_message_size();
+        sim.ship_mut(ship0)
+            .radio_mut(0)
+            .unwrap()
+            .set_sent_bytes(vec![7; max_message_size * 2]);
+        while sim
+            .ship(ship0)
+            .radio(0)
+            .unwrap()
+            .get_received_bytes()
+            .is_none()
+        {
+            sim.step();
+        }
+        assert_eq!(
+            sim.ship(ship0).radio(0).unwrap().get_received_bytes(),
+            Some(vec![7; max_message_size].as_slice())
+        );
+    }
 }
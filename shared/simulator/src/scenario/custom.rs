@@ -0,0 +1,542 @@
+//! Scenarios defined in TOML or JSON instead of Rust.
+//!
+//! Example:
+//!
+//! ```toml
+//! name = "training_map"
+//! world_size = 20000
+//! max_ticks = 5000
+//! victory = "tournament"
+//! initial_code = ["empty", "reference"]
+//!
+//! crash_policy = { type = "restart_ship", delay_ticks = 60 }
+//! power = true
+//! component_damage = true
+//! radio_interference = true
+//!
+//! [gas_budgets]
+//! cruiser = 2000000
+//! missile = 250000
+//!
+//! [loadouts]
+//! fighter = ["standard", "gunship"]
+//!
+//! [[ships]]
+//! class = "fighter"
+//! team = 0
+//! position = [-5000, [-1000, 1000]]
+//!
+//! [[ships]]
+//! class = "heavy_fighter"
+//! team = 1
+//! position = [5000, [-1000, 1000]]
+//! heading = 3.14
+//!
+//! [[ship_classes]]
+//! name = "heavy_fighter"
+//! base = "fighter"
+//! health = 200
+//! mass = 20000
+//! max_forward_acceleration = 40
+//! max_backward_acceleration = 20
+//! max_lateral_acceleration = 20
+//! max_angular_acceleration = 3.14
+//! hull = [[-10, -10], [15, 0], [-10, 10]]
+//!
+//! [[ships]]
+//! class = "asteroid"
+//! count = 10
+//! position = [[-8000, 8000], [-8000, 8000]]
+//! velocity = [[-50, 50], [-50, 50]]
+//! heading = [0, 6.28]
+//! ```
+//!
+//! Any number can be given either as a fixed value or as a `[min, max]` range
+//! sampled uniformly using the simulation seed.
+//!
+//! See [`crate::ship_definition`] for the format of `ship_classes`.
+use super::prelude::*;
+use super::{check_victory_with_filter, MAX_TEAMS, MAX_TICKS};
+use crate::ship::{Loadout, ShipAccessor, ShipClass, ShipData};
+use crate::ship_definition::{ShipClasses, ShipDefinition};
+use crate::simulation::MAX_WORLD_SIZE;
+use crate::vm::{CrashPolicy, DEFAULT_GAS_PER_TICK};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MAX_SHIPS: u32 = 1000;
+const MAX_GAS_PER_TICK: u32 = 10 * DEFAULT_GAS_PER_TICK;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub human_name: Option<String>,
+    #[serde(default = "default_world_size")]
+    pub world_size: f64,
+    #[serde(default = "default_max_ticks")]
+    pub max_ticks: u32,
+    #[serde(default)]
+    pub victory: VictoryRule,
+    #[serde(default)]
+    pub tournament: bool,
+    /// Builtin AI names, indexed by team ID.
+    #[serde(default)]
+    pub initial_code: Vec<String>,
+    /// Per-tick gas budgets, indexed by ship class name. Classes not listed
+    /// get the default budget.
+    #[serde(default)]
+    pub gas_budgets: BTreeMap<String, u32>,
+    /// Preset loadouts ships may select, indexed by ship class name. Only
+    /// fighters and frigates have presets. By default ships keep their
+    /// standard loadout.
+    #[serde(default)]
+    pub loadouts: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub crash_policy: CrashPolicy,
+    /// Gives ships a limited energy and heat budget for guns, radar, radios,
+    /// and abilities.
+    #[serde(default)]
+    pub power: bool,
+    /// Gives builtin frigates and cruisers armor facings and subsystems that
+    /// can be disabled. Custom classes set these in their definitions.
+    #[serde(default)]
+    pub component_damage: bool,
+    /// Makes simultaneous radio transmissions and jamming interfere with
+    /// each other, and gives frigates the `RadioJam` ability.
+    #[serde(default)]
+    pub radio_interference: bool,
+    #[serde(default)]
+    pub ships: Vec<ShipSpawn>,
+    #[serde(default)]
+    pub lines: Vec<LineDefinition>,
+    /// Custom ship classes, which can be spawned by name. Missile launchers
+    /// can launch classes defined earlier in the list.
+    #[serde(default)]
+    pub ship_classes: Vec<ShipDefinition>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VictoryRule {
+    /// Last team with a fighter, frigate, or cruiser wins.
+    #[default]
+    Tournament,
+    /// Last team with a frigate or cruiser wins.
+    CapitalShips,
+    /// Team 0 must destroy everything else, otherwise the scenario fails.
+    Tutorial,
+    /// Teams with a fighter, frigate, or cruiser are ranked by how long they
+    /// survived.
+    FreeForAll,
+    /// Runs until max_ticks and ends in a draw.
+    None,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnClass {
+    Fighter,
+    Frigate,
+    Cruiser,
+    Asteroid,
+    Target,
+    Missile,
+    Torpedo,
+}
+
+const SHIP_CLASS_NAMES: &[&str] = &[
+    "fighter", "frigate", "cruiser", "asteroid", "target", "missile", "torpedo",
+];
+
+/// A builtin class, or the name of a class from `ship_classes`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SpawnClassName {
+    Builtin(SpawnClass),
+    Custom(String),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ShipSpawn {
+    pub class: SpawnClassName,
+    /// Defaults to team 0, or the neutral team for asteroids.
+    #[serde(default)]
+    pub team: Option<i32>,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    #[serde(default)]
+    pub position: [ValueRange; 2],
+    #[serde(default)]
+    pub velocity: [ValueRange; 2],
+    #[serde(default)]
+    pub heading: ValueRange,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ValueRange {
+    Fixed(f64),
+    Uniform([f64; 2]),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LineDefinition {
+    pub a: [f64; 2],
+    pub b: [f64; 2],
+    #[serde(default = "default_line_color")]
+    pub color: [f32; 4],
+}
+
+fn default_world_size() -> f64 {
+    40000.0
+}
+
+fn default_max_ticks() -> u32 {
+    MAX_TICKS
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_line_color() -> [f32; 4] {
+    [0.0, 1.0, 0.0, 1.0]
+}
+
+impl Default for ValueRange {
+    fn default() -> Self {
+        ValueRange::Fixed(0.0)
+    }
+}
+
+impl ValueRange {
+    fn sample(&self, rng: &mut SeededRng) -> f64 {
+        match *self {
+            ValueRange::Fixed(v) => v,
+            ValueRange::Uniform([min, max]) if min == max => min,
+            ValueRange::Uniform([min, max]) => rng.gen_range(min..max),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            ValueRange::Fixed(v) if v.is_finite() => Ok(()),
+            ValueRange::Uniform([min, max]) if min.is_finite() && max.is_finite() && min <= max => {
+                Ok(())
+            }
+            _ => Err(format!("Invalid value {self:?}")),
+        }
+    }
+}
+
+impl ScenarioDefinition {
+    pub fn from_toml(s: &str) -> Result<Self, String> {
+        let definition: Self = toml::from_str(s).map_err(|e| e.to_string())?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        let definition: Self = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Scenario name must not be empty".to_string());
+        }
+        if !(self.world_size > 0.0 && self.world_size <= MAX_WORLD_SIZE) {
+            return Err(format!(
+                "World size must be between 0 and {MAX_WORLD_SIZE}, got {}",
+                self.world_size
+            ));
+        }
+        if self.max_ticks == 0 || self.max_ticks > MAX_TICKS {
+            return Err(format!(
+                "Max ticks must be between 1 and {MAX_TICKS}, got {}",
+                self.max_ticks
+            ));
+        }
+        let mut class_names: Vec<&str> = SHIP_CLASS_NAMES.to_vec();
+        for class in self.ship_classes.iter() {
+            class.validate()?;
+            if class_names.contains(&class.name.as_str()) {
+                return Err(format!("Duplicate ship class {:?}", class.name));
+            }
+            for launcher in class.missile_launchers.iter() {
+                if !["missile", "torpedo"].contains(&launcher.class.as_str())
+                    && !class_names[SHIP_CLASS_NAMES.len()..].contains(&launcher.class.as_str())
+                {
+                    return Err(format!(
+                        "{}: unknown missile class {:?}",
+                        class.name, launcher.class
+                    ));
+                }
+            }
+            class_names.push(&class.name);
+        }
+        let mut num_ships = 0;
+        for spawn in self.ships.iter() {
+            if let SpawnClassName::Custom(name) = &spawn.class {
+                if !class_names.contains(&name.as_str()) {
+                    return Err(format!("Unknown ship class {name:?}"));
+                }
+            }
+            spawn.position[0].validate()?;
+            spawn.position[1].validate()?;
+            spawn.velocity[0].validate()?;
+            spawn.velocity[1].validate()?;
+            spawn.heading.validate()?;
+            if let Some(team) = spawn.team {
+                if team < 0 {
+                    return Err(format!("Invalid team {team}"));
+                }
+                if self.victory == VictoryRule::FreeForAll && team as usize >= MAX_TEAMS {
+                    return Err(format!(
+                        "Free-for-all scenarios support at most {MAX_TEAMS} teams, got team {team}"
+                    ));
+                }
+            }
+            num_ships = match num_ships.checked_add(spawn.count) {
+                Some(n) if n <= MAX_SHIPS => n,
+                _ => return Err(format!("Too many ships: more than {MAX_SHIPS}")),
+            };
+        }
+        for (class, &budget) in self.gas_budgets.iter() {
+            if !class_names.contains(&class.as_str()) {
+                return Err(format!("Unknown ship class {class:?} in gas budgets"));
+            }
+            if budget == 0 || budget > MAX_GAS_PER_TICK {
+                return Err(format!(
+                    "Gas budget for {class} must be between 1 and {MAX_GAS_PER_TICK}, got {budget}"
+                ));
+            }
+        }
+        for (class, loadouts) in self.loadouts.iter() {
+            if !class_names.contains(&class.as_str()) {
+                return Err(format!("Unknown ship class {class:?} in loadouts"));
+            }
+            let available = builtin_loadouts(class);
+            if available.is_empty() {
+                return Err(format!("{class} has no loadouts"));
+            }
+            for loadout in loadouts.iter() {
+                if !available.iter().any(|x| x.name == loadout) {
+                    return Err(format!("Unknown loadout {loadout:?} for {class}"));
+                }
+            }
+        }
+        for line in self.lines.iter() {
+            if !line.a.iter().chain(line.b.iter()).all(|v| v.is_finite()) {
+                return Err(format!("Invalid line {line:?}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct CustomScenario {
+    definition: ScenarioDefinition,
+    ship_classes: BTreeMap<String, ShipClass>,
+    /// Copied into the simulation on init.
+    registry: ShipClasses,
+}
+
+impl CustomScenario {
+    pub fn new(definition: ScenarioDefinition) -> Result<Self, String> {
+        let mut ship_classes = BTreeMap::new();
+        let mut registry = ShipClasses::new();
+        for class in definition.ship_classes.iter() {
+            let id = registry.register(class.clone(), &ship_classes)?;
+            ship_classes.insert(class.name.clone(), id);
+        }
+        Ok(Self {
+            definition,
+            ship_classes,
+            registry,
+        })
+    }
+
+    fn free_for_all(&self) -> VictoryConditions {
+        VictoryConditions::new(self.definition.max_ticks).with(Condition::FreeForAll {
+            filter: is_tournament_ship,
+        })
+    }
+}
+
+fn is_tournament_ship(ship: &ShipAccessor) -> bool {
+    let class = ship.data().class.base();
+    [ShipClass::Fighter, ShipClass::Frigate, ShipClass::Cruiser].contains(&class)
+}
+
+fn is_capital_ship(ship: &ShipAccessor) -> bool {
+    let class = ship.data().class.base();
+    [ShipClass::Frigate, ShipClass::Cruiser].contains(&class)
+}
+
+/// Returns the preset loadouts of a builtin class. Only fighters and frigates
+/// have any.
+fn builtin_loadouts(class: &str) -> Vec<Loadout> {
+    match class {
+        "fighter" => fighter(0).loadouts,
+        "frigate" => frigate(0).loadouts,
+        _ => vec![],
+    }
+}
+
+fn ship_data(
+    spawn: &ShipSpawn,
+    ship_classes: &BTreeMap<String, ShipClass>,
+    registry: &ShipClasses,
+    rng: &mut SeededRng,
+) -> ShipData {
+    let team = spawn.team.unwrap_or(0);
+    let class = match &spawn.class {
+        SpawnClassName::Builtin(class) => *class,
+        SpawnClassName::Custom(name) => {
+            return registry.ship_data(ship_classes[name], team).unwrap()
+        }
+    };
+    match class {
+        SpawnClass::Fighter => fighter(team),
+        SpawnClass::Frigate => frigate(team),
+        SpawnClass::Cruiser => cruiser(team),
+        SpawnClass::Asteroid => {
+            let mut data = asteroid(rng.gen_range(0..30));
+            if let Some(team) = spawn.team {
+                data.team = team;
+            }
+            data
+        }
+        SpawnClass::Target => target(team),
+        SpawnClass::Missile => missile(team),
+        SpawnClass::Torpedo => torpedo(team),
+    }
+}
+
+impl Scenario for CustomScenario {
+    fn name(&self) -> String {
+        self.definition.name.clone()
+    }
+
+    fn human_name(&self) -> String {
+        self.definition
+            .human_name
+            .clone()
+            .unwrap_or_else(|| self.name())
+    }
+
+    fn init(&mut self, sim: &mut Simulation, seed: u32) {
+        *sim.ship_classes_mut() = self.registry.clone();
+        let mut rng = new_rng(seed);
+        for spawn in self.definition.ships.iter() {
+            for _ in 0..spawn.count {
+                let position = vector![
+                    spawn.position[0].sample(&mut rng),
+                    spawn.position[1].sample(&mut rng)
+                ];
+                let velocity = vector![
+                    spawn.velocity[0].sample(&mut rng),
+                    spawn.velocity[1].sample(&mut rng)
+                ];
+                let heading = spawn.heading.sample(&mut rng);
+                let mut data = ship_data(spawn, &self.ship_classes, &self.registry, &mut rng);
+                if self.definition.power {
+                    data.power = self.registry.power(data.class);
+                }
+                if self.definition.component_damage {
+                    ship::add_component_damage(&mut data);
+                }
+                if self.definition.radio_interference && data.class.base() == ShipClass::Frigate {
+                    data.abilities.push(ship::radio_jam());
+                }
+                ship::create(sim, position, velocity, heading, data);
+            }
+        }
+    }
+
+    fn tick(&mut self, sim: &mut Simulation) {
+        if self.definition.victory == VictoryRule::FreeForAll {
+            self.free_for_all().tick(sim);
+        }
+    }
+
+    fn status(&self, sim: &Simulation) -> Status {
+        let max_ticks = self.definition.max_ticks;
+        match self.definition.victory {
+            VictoryRule::Tournament => {
+                check_victory_with_filter(sim, max_ticks, is_tournament_ship)
+            }
+            VictoryRule::CapitalShips => check_victory_with_filter(sim, max_ticks, is_capital_ship),
+            VictoryRule::Tutorial => check_tutorial_victory(sim, max_ticks),
+            VictoryRule::FreeForAll => self.free_for_all().status(sim),
+            VictoryRule::None => {
+                if sim.tick() >= max_ticks - 1 {
+                    Status::Draw
+                } else {
+                    Status::Running
+                }
+            }
+        }
+    }
+
+    fn initial_code(&self) -> Vec<Code> {
+        if self.definition.initial_code.is_empty() {
+            return vec![empty_ai()];
+        }
+        self.definition
+            .initial_code
+            .iter()
+            .map(|name| builtin(name))
+            .collect()
+    }
+
+    fn lines(&self) -> Vec<Line> {
+        self.definition
+            .lines
+            .iter()
+            .map(|line| Line {
+                a: point![line.a[0], line.a[1]],
+                b: point![line.b[0], line.b[1]],
+                color: line.color.into(),
+            })
+            .collect()
+    }
+
+    fn is_tournament(&self) -> bool {
+        self.definition.tournament
+    }
+
+    fn world_size(&self) -> f64 {
+        self.definition.world_size
+    }
+
+    fn crash_policy(&self) -> CrashPolicy {
+        self.definition.crash_policy
+    }
+
+    fn radio_interference(&self) -> bool {
+        self.definition.radio_interference
+    }
+
+    fn is_loadout_allowed(&self, class: ShipClass, loadout: &str) -> bool {
+        self.definition
+            .loadouts
+            .get(self.registry.name(class))
+            .map_or(false, |loadouts| loadouts.iter().any(|x| x == loadout))
+    }
+
+    fn gas_budget(&self, class: ShipClass) -> u32 {
+        self.definition
+            .gas_budgets
+            .get(self.registry.name(class))
+            .copied()
+            .unwrap_or(DEFAULT_GAS_PER_TICK)
+    }
+}
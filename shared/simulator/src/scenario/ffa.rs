@@ -0,0 +1,75 @@
+use super::prelude::*;
+use crate::ship::{ShipAccessor, ShipClass};
+use nalgebra::UnitComplex;
+
+const NUM_TEAMS: usize = 4;
+
+#[derive(Clone)]
+pub struct FighterFfa {
+    victory: VictoryConditions,
+}
+
+impl FighterFfa {
+    pub fn new() -> Self {
+        Self {
+            victory: VictoryConditions::new(TOURNAMENT_MAX_TICKS).with(Condition::FreeForAll {
+                filter: is_combat_ship,
+            }),
+        }
+    }
+}
+
+fn is_combat_ship(ship: &ShipAccessor) -> bool {
+    ship.data().class == ShipClass::Fighter && (ship.data().team as usize) < NUM_TEAMS
+}
+
+impl Scenario for FighterFfa {
+    fn name(&self) -> String {
+        "fighter_ffa".into()
+    }
+
+    fn human_name(&self) -> String {
+        "Fighter Free-for-all".into()
+    }
+
+    fn init(&mut self, sim: &mut Simulation, seed: u32) {
+        let mut rng = new_rng(seed);
+        let placements = place_teams_ffa(&mut rng, self.world_size(), NUM_TEAMS);
+        let offsets = [vector![0.0, 100.0], vector![0.0, -100.0]];
+
+        for (team, placement) in placements.into_iter().enumerate() {
+            let Placement { position, heading } = placement;
+            for offset in &offsets {
+                ship::create(
+                    sim,
+                    position + UnitComplex::new(heading).transform_vector(offset),
+                    vector![0.0, 0.0],
+                    heading,
+                    fighter(team as i32),
+                );
+            }
+        }
+    }
+
+    fn tick(&mut self, sim: &mut Simulation) {
+        self.victory.tick(sim);
+    }
+
+    fn status(&self, sim: &Simulation) -> Status {
+        self.victory.status(sim)
+    }
+
+    fn initial_code(&self) -> Vec<Code> {
+        let mut codes = vec![reference_ai(); NUM_TEAMS];
+        codes[0] = empty_ai();
+        codes
+    }
+
+    fn solution(&self) -> Code {
+        reference_ai()
+    }
+
+    fn is_tournament(&self) -> bool {
+        true
+    }
+}
@@ -2,6 +2,7 @@ use nalgebra::UnitComplex;
 
 use super::prelude::*;
 
+#[derive(Clone)]
 pub struct Fleet {}
 
 impl Fleet {
@@ -1,5 +1,6 @@
 use super::prelude::*;
 
+#[derive(Clone)]
 pub struct GunneryScenario {}
 
 impl Scenario for GunneryScenario {
@@ -16,6 +17,7 @@ impl Scenario for GunneryScenario {
         ship_data.guns.pop();
         ship_data.guns.pop();
         ship_data.missile_launchers.pop();
+        ship_data.loadouts.clear();
         ship_data.acceleration = vector![0.0, 0.0];
         ship_data.fuel = Some(0.0);
         ship::create(
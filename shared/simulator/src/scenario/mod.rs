@@ -1,6 +1,8 @@
 mod asteroid_duel;
 mod belt;
 mod cruiser_duel;
+mod custom;
+mod ffa;
 mod fighter_duel;
 mod fleet;
 mod frigate_duel;
@@ -26,14 +28,22 @@ mod tutorial_radio;
 mod tutorial_rotation;
 mod tutorial_search;
 mod tutorial_squadron;
+mod victory;
 mod welcome;
 
 use crate::ship::{asteroid, fighter, ShipAccessor, ShipClass, ShipData};
 use crate::simulation::{Code, Line, Simulation};
-use nalgebra::{vector, Vector2};
+use crate::vm;
+use nalgebra::{vector, Rotation2, Vector2};
 use rand::{seq::SliceRandom, Rng, RngCore};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::f64::consts::{PI, TAU};
+
+pub use custom::{CustomScenario, ScenarioDefinition};
+pub use victory::{
+    capture_time_counter, Condition, Scores, VictoryConditions, CAPTURE_TIME, DAMAGE_DEALT,
+    SHOTS_FIRED, SHOTS_HIT, SURVIVAL_TIME,
+};
 
 pub mod prelude {
     pub use super::Scenario;
@@ -43,7 +53,8 @@ pub mod prelude {
         check_capital_ship_tournament_victory, check_tournament_victory, check_tutorial_victory,
     };
     pub use super::{fighter_without_missiles, fighter_without_missiles_or_radar, target_asteroid};
-    pub use super::{place_teams, Placement};
+    pub use super::{place_teams, place_teams_ffa, Placement};
+    pub use super::{Condition, VictoryConditions};
     pub use super::{DEFAULT_TUTORIAL_MAX_TICKS, TOURNAMENT_MAX_TICKS};
     pub use crate::rng::{new_rng, SeededRng};
     pub use crate::ship::{
@@ -58,16 +69,87 @@ pub mod prelude {
 pub const DEFAULT_TUTORIAL_MAX_TICKS: u32 = 30 * 60;
 pub const TOURNAMENT_MAX_TICKS: u32 = 10000;
 pub const MAX_TICKS: u32 = 10000;
+// Tournament games that time out are won by the team that dealt this much more damage.
+pub const TOURNAMENT_DAMAGE_RATIO: f64 = 1.5;
+
+pub const MAX_TEAMS: usize = 10;
 
 #[derive(PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Copy, Clone)]
 pub enum Status {
     Running,
-    Victory { team: i32 },
+    Victory {
+        team: i32,
+    },
     Failed,
     Draw,
+    /// Result of a free-for-all game.
+    Ranked {
+        placements: Placements,
+    },
+}
+
+impl Status {
+    /// Returns the team that won outright, if any.
+    pub fn winner(&self) -> Option<i32> {
+        match self {
+            Status::Victory { team } => Some(*team),
+            Status::Ranked { placements } => placements.winner(),
+            _ => None,
+        }
+    }
+}
+
+/// Final placement of each team in a free-for-all game. Teams can share a
+/// placement, e.g. if they were destroyed on the same tick.
+#[derive(PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct Placements {
+    // Indexed by team. 1 is first place and 0 means the team didn't take part.
+    ranks: [u8; MAX_TEAMS],
 }
 
-pub trait Scenario {
+impl Placements {
+    /// Creates placements from (team, rank) pairs. Fails if a team is outside
+    /// `0..MAX_TEAMS` or a rank is outside `1..=MAX_TEAMS`.
+    pub fn new(ranks: &[(i32, u32)]) -> Result<Self, String> {
+        let mut placements = Placements::default();
+        for &(team, rank) in ranks {
+            if team < 0 || team as usize >= MAX_TEAMS {
+                return Err(format!("Invalid team {team}"));
+            }
+            if rank == 0 || rank as usize > MAX_TEAMS {
+                return Err(format!("Invalid rank {rank} for team {team}"));
+            }
+            placements.ranks[team as usize] = rank as u8;
+        }
+        Ok(placements)
+    }
+
+    pub fn rank(&self, team: i32) -> Option<u32> {
+        match self.ranks.get(team as usize) {
+            Some(&rank) if rank > 0 => Some(rank as u32),
+            _ => None,
+        }
+    }
+
+    /// Returns (team, rank) pairs from first to last place.
+    pub fn order(&self) -> Vec<(i32, u32)> {
+        let mut order: Vec<(i32, u32)> = (0..MAX_TEAMS as i32)
+            .filter_map(|team| self.rank(team).map(|rank| (team, rank)))
+            .collect();
+        order.sort_by_key(|&(team, rank)| (rank, team));
+        order
+    }
+
+    pub fn winner(&self) -> Option<i32> {
+        match self.order().as_slice() {
+            [(team, 1)] => Some(*team),
+            [(team, 1), (_, second), ..] if *second > 1 => Some(*team),
+            _ => None,
+        }
+    }
+}
+
+pub trait Scenario: ScenarioClone + Send + Sync {
     fn name(&self) -> String;
 
     fn human_name(&self) -> String {
@@ -120,6 +202,43 @@ pub trait Scenario {
     fn world_size(&self) -> f64 {
         40000.0
     }
+
+    /// Gas available to each ship's AI per tick.
+    fn gas_budget(&self, _class: ShipClass) -> u32 {
+        vm::DEFAULT_GAS_PER_TICK
+    }
+
+    /// Maximum size of each team's wasm linear memory, in bytes.
+    fn max_memory(&self) -> usize {
+        vm::DEFAULT_MAX_MEMORY
+    }
+
+    /// What happens to ships whose AI crashes.
+    fn crash_policy(&self) -> vm::CrashPolicy {
+        vm::CrashPolicy::StayCrashed
+    }
+
+    /// Whether radio transmissions interfere with each other and can be
+    /// jammed. Otherwise the strongest transmission is always received.
+    fn radio_interference(&self) -> bool {
+        false
+    }
+
+    /// Whether ships of the class may switch to the named loadout. Builtin
+    /// scenarios keep the standard loadout of each class.
+    fn is_loadout_allowed(&self, _class: ShipClass, _loadout: &str) -> bool {
+        false
+    }
+}
+
+pub trait ScenarioClone {
+    fn clone_box(&self) -> Box<dyn Scenario>;
+}
+
+impl<T: 'static + Scenario + Clone> ScenarioClone for T {
+    fn clone_box(&self) -> Box<dyn Scenario> {
+        Box::new(self.clone())
+    }
 }
 
 pub fn load_safe(name: &str) -> Option<Box<dyn Scenario>> {
@@ -154,6 +273,7 @@ pub fn load_safe(name: &str) -> Option<Box<dyn Scenario>> {
         "fleet" => Some(Box::new(fleet::Fleet::new())),
         "belt" => Some(Box::new(belt::Belt::new())),
         "orbit" => Some(Box::new(orbit::Orbit::new())),
+        "fighter_ffa" => Some(Box::new(ffa::FighterFfa::new())),
         // Challenge
         "gunnery" => Some(Box::new(gunnery::GunneryScenario {})),
         "planetary_defense" => Some(Box::new(planetary_defense::PlanetaryDefense::new())),
@@ -187,6 +307,17 @@ pub fn load(name: &str) -> Box<dyn Scenario> {
     }
 }
 
+/// Parses a scenario definition in TOML or JSON format. See the custom module
+/// for a description of the format.
+pub fn load_definition(contents: &str) -> Result<Box<dyn Scenario>, String> {
+    let definition = if contents.trim_start().starts_with('{') {
+        ScenarioDefinition::from_json(contents)?
+    } else {
+        ScenarioDefinition::from_toml(contents)?
+    };
+    Ok(Box::new(CustomScenario::new(definition)?))
+}
+
 pub fn list() -> Vec<(String, Vec<String>)> {
     vec![
         ("Introduction", vec!["welcome"]),
@@ -220,6 +351,7 @@ pub fn list() -> Vec<(String, Vec<String>)> {
                 "fleet",
                 "belt",
                 "orbit",
+                "fighter_ffa",
             ],
         ),
     ]
@@ -250,29 +382,16 @@ pub fn check_victory_with_filter(
     max_ticks: u32,
     ship_filter: fn(&ShipAccessor) -> bool,
 ) -> Status {
-    let mut team_health: HashMap<i32, u32> = HashMap::new();
-    for &handle in sim.ships.iter() {
-        let ship = sim.ship(handle);
-        if ship_filter(&ship) {
-            *team_health.entry(ship.data().team).or_insert(0) += ship.data().health as u32;
-        }
-    }
-    if team_health.is_empty() {
-        Status::Draw
-    } else if team_health.len() == 1 {
-        Status::Victory {
-            team: *team_health.iter().next().unwrap().0,
-        }
-    } else if sim.tick() >= max_ticks - 1 {
-        Status::Draw
-    } else {
-        Status::Running
-    }
+    VictoryConditions::new(max_ticks)
+        .with(Condition::Elimination {
+            filter: ship_filter,
+        })
+        .status(sim)
 }
 
 pub fn check_tutorial_victory(sim: &Simulation, max_ticks: u32) -> Status {
     match check_victory_with_filter(sim, max_ticks, |ship| {
-        ![ShipClass::Missile, ShipClass::Torpedo].contains(&ship.data().class)
+        ![ShipClass::Missile, ShipClass::Torpedo].contains(&ship.data().class.base())
     }) {
         x @ Status::Victory { team: 0 } => x,
         Status::Victory { .. } => Status::Failed,
@@ -281,28 +400,45 @@ pub fn check_tutorial_victory(sim: &Simulation, max_ticks: u32) -> Status {
 }
 
 pub fn check_tournament_victory(sim: &Simulation) -> Status {
-    check_victory_with_filter(sim, TOURNAMENT_MAX_TICKS, |ship| {
+    check_tournament_victory_with_filter(sim, |ship| {
         [ShipClass::Fighter, ShipClass::Frigate, ShipClass::Cruiser].contains(&ship.data().class)
             && ship.data().team < 2
     })
 }
 
 pub fn check_capital_ship_tournament_victory(sim: &Simulation) -> Status {
-    check_victory_with_filter(sim, TOURNAMENT_MAX_TICKS, |ship| {
+    check_tournament_victory_with_filter(sim, |ship| {
         [ShipClass::Frigate, ShipClass::Cruiser].contains(&ship.data().class)
             && ship.data().team < 2
     })
 }
 
+fn check_tournament_victory_with_filter(
+    sim: &Simulation,
+    ship_filter: fn(&ShipAccessor) -> bool,
+) -> Status {
+    VictoryConditions::new(TOURNAMENT_MAX_TICKS)
+        .with(Condition::Elimination {
+            filter: ship_filter,
+        })
+        .with(Condition::ScoreAtTimeout {
+            counter: DAMAGE_DEALT.to_string(),
+            min_ratio: TOURNAMENT_DAMAGE_RATIO,
+        })
+        .status(sim)
+}
+
 pub fn fighter_without_missiles(team: i32) -> ShipData {
     let mut data = fighter(team);
     data.missile_launchers.pop();
+    data.loadouts.clear();
     data
 }
 
 pub fn fighter_without_missiles_or_radar(team: i32) -> ShipData {
     let mut data = fighter(team);
     data.missile_launchers.pop();
+    data.loadouts.clear();
     data.radar = None;
     data
 }
@@ -334,3 +470,19 @@ pub fn place_teams(rng: &mut dyn RngCore, world_size: f64) -> Vec<Placement> {
     placements.shuffle(rng);
     placements
 }
+
+/// Spreads teams evenly around a circle, facing the center.
+pub fn place_teams_ffa(rng: &mut dyn RngCore, world_size: f64, num_teams: usize) -> Vec<Placement> {
+    assert!(num_teams <= MAX_TEAMS);
+    let radius = world_size * 0.4;
+    let offset = rng.gen_range(0.0..TAU);
+    (0..num_teams)
+        .map(|i| {
+            let angle = offset + TAU * i as f64 / num_teams as f64;
+            Placement {
+                position: Rotation2::new(angle).transform_vector(&vector![radius, 0.0]),
+                heading: (angle + PI) % TAU,
+            }
+        })
+        .collect()
+}
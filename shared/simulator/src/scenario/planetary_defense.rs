@@ -2,6 +2,7 @@ use super::prelude::*;
 use crate::ship::{ShipClass, ShipData};
 use crate::simulation::PHYSICS_TICK_LENGTH;
 
+#[derive(Clone)]
 pub struct PlanetaryDefense {
     rng: SeededRng,
 }
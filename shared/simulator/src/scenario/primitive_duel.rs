@@ -1,5 +1,6 @@
 use super::prelude::*;
 
+#[derive(Clone)]
 pub struct PrimitiveDuel {
     ship0: Option<ShipHandle>,
     ship1: Option<ShipHandle>,
@@ -1,6 +1,7 @@
 use super::prelude::*;
 use nalgebra::UnitComplex;
 
+#[derive(Clone)]
 pub struct Squadrons {}
 
 impl Squadrons {
@@ -2,6 +2,7 @@ use super::prelude::*;
 use crate::bullet::{self, BulletData};
 use crate::color;
 
+#[derive(Clone)]
 pub struct StressScenario {}
 
 impl Scenario for StressScenario {
@@ -40,6 +41,7 @@ impl Scenario for StressScenario {
     }
 }
 
+#[derive(Clone)]
 pub struct AsteroidStressScenario {}
 
 impl Scenario for AsteroidStressScenario {
@@ -68,6 +70,7 @@ impl Scenario for AsteroidStressScenario {
     }
 }
 
+#[derive(Clone)]
 pub struct BulletStressScenario {}
 
 impl Scenario for BulletStressScenario {
@@ -91,6 +94,7 @@ impl Scenario for BulletStressScenario {
                     team: 0,
                     color: color::to_u32(vector![1.00, 0.63, 0.00, 0.30]),
                     ttl: 100.0,
+                    hit: false,
                 },
             );
         }
@@ -105,6 +109,7 @@ impl Scenario for BulletStressScenario {
     }
 }
 
+#[derive(Clone)]
 pub struct MissileStressScenario {}
 
 impl Scenario for MissileStressScenario {
@@ -1,6 +1,7 @@
 use super::prelude::*;
 use crate::{bullet, simulation};
 
+#[derive(Clone)]
 pub struct TestScenario {}
 
 impl Scenario for TestScenario {
@@ -15,6 +16,7 @@ impl Scenario for TestScenario {
     }
 }
 
+#[derive(Clone)]
 pub struct BasicScenario {}
 
 impl Scenario for BasicScenario {
@@ -44,6 +46,7 @@ impl Scenario for BasicScenario {
     }
 }
 
+#[derive(Clone)]
 pub struct MissileTest {
     target: Option<ShipHandle>,
     rng: SeededRng,
@@ -145,6 +148,7 @@ impl Scenario for MissileTest {
     }
 }
 
+#[derive(Clone)]
 pub struct FrigateVsCruiser {}
 
 impl FrigateVsCruiser {
@@ -188,6 +192,7 @@ impl Scenario for FrigateVsCruiser {
     }
 }
 
+#[derive(Clone)]
 pub struct CruiserVsFrigate {}
 
 impl CruiserVsFrigate {
@@ -231,6 +236,7 @@ impl Scenario for CruiserVsFrigate {
     }
 }
 
+#[derive(Clone)]
 pub struct FrigatePointDefense {}
 
 impl Scenario for FrigatePointDefense {
@@ -243,6 +249,7 @@ impl Scenario for FrigatePointDefense {
 
         let mut data = frigate(0);
         data.missile_launchers.clear();
+        data.loadouts.clear();
         ship::create(sim, vector![0.0, 0.0], vector![0.0, 0.0], 0.0, data);
 
         for i in 1..10 {
@@ -265,6 +272,7 @@ impl Scenario for FrigatePointDefense {
     }
 }
 
+#[derive(Clone)]
 pub struct RadarTest {}
 
 impl Scenario for RadarTest {
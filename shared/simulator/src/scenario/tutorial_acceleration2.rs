@@ -1,5 +1,6 @@
 use super::prelude::*;
 
+#[derive(Clone)]
 pub struct TutorialAcceleration2 {
     hit_target: bool,
     target: Option<Point2<f64>>,
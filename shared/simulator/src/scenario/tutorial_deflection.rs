@@ -1,5 +1,6 @@
 use super::prelude::*;
 
+#[derive(Clone)]
 pub struct TutorialDeflection {
     ship_handle: Option<ShipHandle>,
     target_handle: Option<ShipHandle>,
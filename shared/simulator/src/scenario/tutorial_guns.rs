@@ -1,5 +1,6 @@
 use super::prelude::*;
 
+#[derive(Clone)]
 pub struct TutorialGuns {}
 
 impl Scenario for TutorialGuns {
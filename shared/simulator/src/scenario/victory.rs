@@ -0,0 +1,267 @@
+//! Composable victory conditions.
+//!
+//! Conditions are checked in order each tick and the first decisive one ends
+//! the game. At `max_ticks` the timeout conditions (e.g. `ScoreAtTimeout`) get
+//! a chance to pick a winner before the game is declared a draw.
+use super::{Placements, Status};
+use crate::ship::{ShipAccessor, ShipHandle};
+use crate::simulation::{Simulation, PHYSICS_TICK_LENGTH};
+use nalgebra::Vector2;
+use std::collections::{BTreeMap, HashMap};
+
+/// Score counter accumulating the damage each team deals to other teams' ships.
+pub const DAMAGE_DEALT: &str = "damage_dealt";
+
+/// Score counter of bullets fired by each team's guns, plus one for each tick
+/// a laser fires.
+pub const SHOTS_FIRED: &str = "shots_fired";
+
+/// Score counter of each team's bullets and laser ticks that damaged another
+/// team's ship.
+pub const SHOTS_HIT: &str = "shots_hit";
+
+/// Score counter tracking how long a team has continuously held the first
+/// capture zone, in seconds. See `capture_time_counter` for the others.
+pub const CAPTURE_TIME: &str = "capture_time";
+
+/// Score counter tracking how long each team has survived in a free-for-all
+/// game, in seconds.
+pub const SURVIVAL_TIME: &str = "survival_time";
+
+/// Per-team named score counters, exposed in the snapshot.
+pub type Scores = BTreeMap<i32, BTreeMap<String, f64>>;
+
+/// Returns the score counter for the capture zone with the given index,
+/// counting only `CaptureZone` conditions.
+pub fn capture_time_counter(zone: usize) -> String {
+    if zone == 0 {
+        CAPTURE_TIME.to_string()
+    } else {
+        format!("{CAPTURE_TIME}_{zone}")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Condition {
+    /// The last team with a ship passing the filter wins. If no team has one
+    /// the game is a draw.
+    Elimination { filter: fn(&ShipAccessor) -> bool },
+    /// Like `Elimination`, but teams are ranked by how long they survived,
+    /// breaking ties by damage dealt. Ends with `Status::Ranked`.
+    FreeForAll { filter: fn(&ShipAccessor) -> bool },
+    /// A team wins by being the only team with a ship passing the filter
+    /// inside the zone for `hold_time` seconds.
+    CaptureZone {
+        center: Vector2<f64>,
+        radius: f64,
+        hold_time: f64,
+        filter: fn(&ShipAccessor) -> bool,
+    },
+    /// The attacker wins if the VIP ship is destroyed.
+    Escort { vip: ShipHandle, attacker: i32 },
+    /// At timeout, the team with the highest value of the score counter wins
+    /// if it is more than `min_ratio` times that of every other team.
+    ScoreAtTimeout { counter: String, min_ratio: f64 },
+}
+
+#[derive(Clone, Debug)]
+pub struct VictoryConditions {
+    pub conditions: Vec<Condition>,
+    pub max_ticks: u32,
+}
+
+impl VictoryConditions {
+    pub fn new(max_ticks: u32) -> Self {
+        Self {
+            conditions: vec![],
+            max_ticks,
+        }
+    }
+
+    pub fn with(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Updates score counters. Must be called from `Scenario::tick` for
+    /// conditions that track progress over time.
+    pub fn tick(&self, sim: &mut Simulation) {
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if let Condition::FreeForAll { filter } = condition {
+                for team in surviving_teams(sim, *filter).into_keys() {
+                    sim.add_score(team, SURVIVAL_TIME, PHYSICS_TICK_LENGTH);
+                }
+            } else if let Condition::CaptureZone {
+                center,
+                radius,
+                filter,
+                ..
+            } = condition
+            {
+                let mut teams_in_zone: Vec<i32> = sim
+                    .ships
+                    .iter()
+                    .map(|&handle| sim.ship(handle))
+                    .filter(|ship| filter(ship))
+                    .filter(|ship| (ship.position().vector - center).magnitude() <= *radius)
+                    .map(|ship| ship.data().team)
+                    .collect();
+                teams_in_zone.sort();
+                teams_in_zone.dedup();
+                let holder = if teams_in_zone.len() == 1 {
+                    Some(teams_in_zone[0])
+                } else {
+                    None
+                };
+                let counter = capture_time_counter(self.zone_index(i));
+                let teams: Vec<i32> = sim.scores().keys().cloned().collect();
+                for team in teams {
+                    if Some(team) != holder {
+                        sim.set_score(team, &counter, 0.0);
+                    }
+                }
+                if let Some(team) = holder {
+                    sim.add_score(team, &counter, PHYSICS_TICK_LENGTH);
+                }
+            }
+        }
+    }
+
+    pub fn status(&self, sim: &Simulation) -> Status {
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if let Some(status) = check(condition, self.zone_index(i), sim) {
+                return status;
+            }
+        }
+        if sim.tick() >= self.max_ticks - 1 {
+            for condition in self.conditions.iter() {
+                if let Some(status) = check_timeout(condition, sim) {
+                    return status;
+                }
+            }
+            return Status::Draw;
+        }
+        Status::Running
+    }
+
+    // Number of capture zones before the condition at index `i`.
+    fn zone_index(&self, i: usize) -> usize {
+        self.conditions[..i]
+            .iter()
+            .filter(|condition| matches!(condition, Condition::CaptureZone { .. }))
+            .count()
+    }
+}
+
+fn check(condition: &Condition, zone: usize, sim: &Simulation) -> Option<Status> {
+    match condition {
+        Condition::Elimination { filter } => {
+            let team_health = surviving_teams(sim, *filter);
+            if team_health.is_empty() {
+                Some(Status::Draw)
+            } else if team_health.len() == 1 {
+                Some(Status::Victory {
+                    team: *team_health.iter().next().unwrap().0,
+                })
+            } else {
+                None
+            }
+        }
+        Condition::FreeForAll { filter } => {
+            if surviving_teams(sim, *filter).len() <= 1 {
+                Some(Status::Ranked {
+                    placements: rank_teams(sim, *filter),
+                })
+            } else {
+                None
+            }
+        }
+        Condition::CaptureZone { hold_time, .. } => {
+            let counter = capture_time_counter(zone);
+            sim.scores()
+                .iter()
+                .find(|(_, counters)| {
+                    counters.get(&counter).cloned().unwrap_or_default() >= *hold_time
+                })
+                .map(|(&team, _)| Status::Victory { team })
+        }
+        Condition::Escort { vip, attacker } => {
+            if !sim.ships.contains(*vip) || sim.ship(*vip).data().destroyed {
+                Some(Status::Victory { team: *attacker })
+            } else {
+                None
+            }
+        }
+        Condition::ScoreAtTimeout { .. } => None,
+    }
+}
+
+fn check_timeout(condition: &Condition, sim: &Simulation) -> Option<Status> {
+    match condition {
+        Condition::ScoreAtTimeout { counter, min_ratio } => {
+            let mut values: Vec<(i32, f64)> = sim
+                .scores()
+                .iter()
+                .map(|(&team, counters)| (team, counters.get(counter).cloned().unwrap_or_default()))
+                .collect();
+            values.sort_by(|a, b| b.1.total_cmp(&a.1));
+            match values.as_slice() {
+                [(team, best), rest @ ..]
+                    if *best > 0.0 && rest.iter().all(|(_, v)| *best > *v * min_ratio) =>
+                {
+                    Some(Status::Victory { team: *team })
+                }
+                _ => None,
+            }
+        }
+        Condition::FreeForAll { filter } => Some(Status::Ranked {
+            placements: rank_teams(sim, *filter),
+        }),
+        _ => None,
+    }
+}
+
+fn surviving_teams(sim: &Simulation, filter: fn(&ShipAccessor) -> bool) -> HashMap<i32, u32> {
+    let mut team_health: HashMap<i32, u32> = HashMap::new();
+    for &handle in sim.ships.iter() {
+        let ship = sim.ship(handle);
+        if filter(&ship) {
+            *team_health.entry(ship.data().team).or_insert(0) += ship.data().health as u32;
+        }
+    }
+    team_health
+}
+
+fn rank_teams(sim: &Simulation, filter: fn(&ShipAccessor) -> bool) -> Placements {
+    let surviving = surviving_teams(sim, filter);
+    let mut teams: Vec<i32> = sim
+        .scores()
+        .iter()
+        .filter(|(_, counters)| counters.contains_key(SURVIVAL_TIME))
+        .map(|(&team, _)| team)
+        .chain(surviving.keys().cloned())
+        .collect();
+    teams.sort();
+    teams.dedup();
+    let key = |team: i32| {
+        (
+            surviving.contains_key(&team),
+            sim.score(team, SURVIVAL_TIME),
+            sim.score(team, DAMAGE_DEALT),
+        )
+    };
+    let ranks: Vec<(i32, u32)> = teams
+        .iter()
+        .map(|&team| {
+            let better = teams
+                .iter()
+                .filter(|&&other| key(other) > key(team))
+                .count();
+            (team, better as u32 + 1)
+        })
+        .collect();
+    Placements::new(&ranks).unwrap_or_else(|e| {
+        log::error!("Failed to rank teams: {}", e);
+        Placements::default()
+    })
+}
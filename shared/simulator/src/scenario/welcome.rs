@@ -3,6 +3,7 @@ use crate::ship::ShipClass;
 use super::prelude::*;
 use rand::seq::SliceRandom;
 
+#[derive(Clone)]
 pub struct Welcome {
     rng: Option<SeededRng>,
 }
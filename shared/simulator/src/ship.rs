@@ -1,16 +1,20 @@
 use super::index_set::{HasIndex, Index};
 use super::rng::new_rng;
 use crate::color;
+use crate::laser::Laser;
 use crate::model;
 use crate::radar::Radar;
 use crate::radio::Radio;
 use crate::rng;
+use crate::scenario::SHOTS_FIRED;
+use crate::ship_definition::BaseClass;
 use crate::simulation::{self, PHYSICS_TICK_LENGTH};
 use crate::simulation::{Particle, Simulation};
+use crate::vm::GasUsage;
 use crate::{bullet, collision};
 use bullet::BulletData;
 use nalgebra::{vector, Rotation2, UnitComplex, Vector2};
-use oort_api::Ability;
+use oort_api::{Ability, Subsystem};
 use rand::Rng;
 use rapier2d_f64::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -32,6 +36,9 @@ impl From<ShipHandle> for u64 {
     }
 }
 
+/// Custom classes are defined in a simulation's
+/// [`crate::ship_definition::ShipClasses`]. Their base class is kept here so
+/// it can be used without looking up the definition.
 #[derive(Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize, Debug)]
 pub enum ShipClass {
     Fighter,
@@ -42,9 +49,12 @@ pub enum ShipClass {
     Missile,
     Torpedo,
     Planet,
+    Custom { id: u32, base: BaseClass },
 }
 
 impl ShipClass {
+    /// Custom classes are named by their definitions, see
+    /// [`crate::ship_definition::ShipClasses::name`].
     pub fn name(&self) -> &'static str {
         match self {
             ShipClass::Fighter => "fighter",
@@ -55,6 +65,15 @@ impl ShipClass {
             ShipClass::Missile => "missile",
             ShipClass::Torpedo => "torpedo",
             ShipClass::Planet => "planet",
+            ShipClass::Custom { .. } => "custom",
+        }
+    }
+
+    /// The builtin class this class behaves as.
+    pub fn base(&self) -> ShipClass {
+        match self {
+            ShipClass::Custom { base, .. } => base.class(),
+            _ => *self,
         }
     }
 }
@@ -88,6 +107,58 @@ pub struct MissileLauncher {
     pub angle: f64,
 }
 
+/// A weapon in a loadout.
+#[derive(Debug, Clone)]
+pub enum Weapon {
+    Gun(Gun),
+    Laser(Laser),
+    MissileLauncher(MissileLauncher),
+}
+
+/// A preset set of weapons a ship can choose when it spawns. The weapons
+/// replace the ship's guns, lasers, and missile launchers, and `fire` and
+/// `aim` indices refer to them in that order.
+#[derive(Debug, Clone)]
+pub struct Loadout {
+    pub name: &'static str,
+    pub weapons: Vec<Weapon>,
+}
+
+/// Fraction of damage blocked by each facing of the hull.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Armor {
+    pub front: f64,
+    pub side: f64,
+    pub rear: f64,
+}
+
+/// A part of the ship that stops working when damaged enough. Hits within
+/// `radius` of `offset` damage it as well as the hull.
+#[derive(Debug, Clone)]
+pub struct ShipSubsystem {
+    pub subsystem: Subsystem,
+    pub offset: Vector2<f64>,
+    pub radius: f64,
+    pub health: f64,
+    pub max_health: f64,
+}
+
+/// Energy and heat budget. Each system generates heat at its own rate as it
+/// uses energy, and a hot ship's radar and weapons are throttled until it has
+/// cooled down.
+#[derive(Debug, Clone)]
+pub struct Power {
+    pub energy: f64,
+    pub max_energy: f64,
+    /// Energy regained per second.
+    pub recharge_rate: f64,
+    pub heat: f64,
+    pub max_heat: f64,
+    /// Heat lost per second.
+    pub cooling_rate: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct ShipAbility {
     pub ability: Ability,
@@ -121,9 +192,11 @@ pub struct ShipData {
     pub max_angular_acceleration: f64,
     pub destroyed: bool,
     pub crash_message: Option<String>,
+    pub gas_usage: Option<GasUsage>,
     pub ttl: Option<u64>,
     pub fuel: Option<f64>,
     pub guns: Vec<Gun>,
+    pub lasers: Vec<Laser>,
     pub missile_launchers: Vec<MissileLauncher>,
     pub radar: Option<Radar>,
     pub radar_cross_section: f64,
@@ -131,6 +204,15 @@ pub struct ShipData {
     pub abilities: Vec<ShipAbility>,
     pub target: Option<Box<Target>>,
     pub warhead: Warhead,
+    /// Loadouts the ship can choose between, the first being the one it
+    /// spawns with. Only fighters and frigates have any.
+    pub loadouts: Vec<Loadout>,
+    /// Set after the ship's first tick, when it can no longer change loadout.
+    pub loadout_locked: bool,
+    pub armor: Option<Armor>,
+    pub subsystems: Vec<ShipSubsystem>,
+    /// Ships without power aren't limited by energy or heat.
+    pub power: Option<Power>,
 }
 
 #[derive(Debug, Clone)]
@@ -155,9 +237,11 @@ impl Default for ShipData {
             max_angular_acceleration: 0.0,
             destroyed: false,
             crash_message: None,
+            gas_usage: None,
             ttl: None,
             fuel: None,
             guns: vec![],
+            lasers: vec![],
             missile_launchers: vec![],
             radar: None,
             radar_cross_section: 10.0,
@@ -165,10 +249,163 @@ impl Default for ShipData {
             abilities: vec![],
             target: None,
             warhead: Default::default(),
+            loadouts: vec![],
+            loadout_locked: false,
+            armor: None,
+            subsystems: vec![],
+            power: None,
+        }
+    }
+}
+
+impl ShipData {
+    pub fn is_disabled(&self, subsystem: Subsystem) -> bool {
+        self.subsystems
+            .iter()
+            .any(|x| x.subsystem == subsystem && x.health <= 0.0)
+    }
+
+    /// Damages the hull and any subsystems near `hit`, which is relative to
+    /// the ship in its own frame. Returns the damage after armor.
+    pub fn damage(&mut self, hit: Vector2<f64>, damage: f64) -> f64 {
+        let damage = match self.armor {
+            Some(armor) => damage * (1.0 - armor.protection(hit)),
+            None => damage,
+        };
+        self.health -= damage;
+
+        let mut disabled = vec![];
+        for subsystem in self.subsystems.iter_mut() {
+            if subsystem.health > 0.0 && (hit - subsystem.offset).norm() < subsystem.radius {
+                subsystem.health -= damage;
+                if subsystem.health <= 0.0 {
+                    disabled.push(subsystem.subsystem);
+                }
+            }
+        }
+        if disabled.contains(&Subsystem::Engines) {
+            self.max_forward_acceleration *= DISABLED_ENGINES_ACCELERATION_FACTOR;
+            self.max_backward_acceleration *= DISABLED_ENGINES_ACCELERATION_FACTOR;
+            self.max_lateral_acceleration *= DISABLED_ENGINES_ACCELERATION_FACTOR;
+            self.max_angular_acceleration *= DISABLED_ENGINES_ACCELERATION_FACTOR;
+        }
+
+        damage
+    }
+}
+
+impl Power {
+    /// Creates a power budget with full energy and no heat.
+    pub fn new(max_energy: f64, recharge_rate: f64, max_heat: f64, cooling_rate: f64) -> Self {
+        Self {
+            energy: max_energy,
+            max_energy,
+            recharge_rate,
+            heat: 0.0,
+            max_heat,
+            cooling_rate,
+        }
+    }
+
+    /// Uses energy for an action that generates `heat`. Returns false, using
+    /// nothing, if there isn't enough energy.
+    pub fn use_energy(&mut self, energy: f64, heat: f64) -> bool {
+        if self.energy < energy {
+            return false;
+        }
+        self.energy -= energy;
+        self.heat = (self.heat + heat).min(self.max_heat);
+        true
+    }
+
+    /// Returns the fraction of full output the radar and weapons run at.
+    ///
+    /// This is 1 up to `THROTTLE_HEAT_FRACTION` of the maximum heat and
+    /// drops linearly to 0 at the maximum.
+    pub fn throttle(&self) -> f64 {
+        let start = THROTTLE_HEAT_FRACTION * self.max_heat;
+        if self.heat <= start {
+            1.0
+        } else {
+            ((self.max_heat - self.heat) / (self.max_heat - start)).clamp(0.0, 1.0)
+        }
+    }
+
+    fn tick(&mut self) {
+        self.energy = (self.energy + self.recharge_rate * PHYSICS_TICK_LENGTH).min(self.max_energy);
+        self.heat = (self.heat - self.cooling_rate * PHYSICS_TICK_LENGTH).max(0.0);
+    }
+}
+
+/// Returns the power budget each class gets in scenarios that limit energy.
+/// See [`crate::ship_definition::ShipClasses::power`] for custom classes.
+pub fn default_power(class: ShipClass) -> Option<Power> {
+    match class.base() {
+        ShipClass::Fighter => Some(Power::new(100.0, 25.0, 150.0, 20.0)),
+        ShipClass::Frigate => Some(Power::new(2000.0, 400.0, 4000.0, 300.0)),
+        ShipClass::Cruiser => Some(Power::new(1000.0, 150.0, 1500.0, 80.0)),
+        _ => None,
+    }
+}
+
+impl Armor {
+    /// Returns the fraction of damage blocked for a hit at `hit`, relative to
+    /// the ship in its own frame.
+    pub fn protection(&self, hit: Vector2<f64>) -> f64 {
+        let angle = hit.y.atan2(hit.x).abs();
+        if angle < TAU / 8.0 {
+            self.front
+        } else if angle > 3.0 * TAU / 8.0 {
+            self.rear
+        } else {
+            self.side
         }
     }
 }
 
+impl Gun {
+    /// Returns the energy used to fire one burst.
+    pub fn energy(&self) -> f64 {
+        0.5 * self.bullet_mass
+            * self.speed
+            * self.speed
+            * self.burst_size as f64
+            * GUN_ENERGY_FACTOR
+    }
+}
+
+impl Loadout {
+    pub fn guns(&self) -> Vec<Gun> {
+        self.weapons
+            .iter()
+            .filter_map(|weapon| match weapon {
+                Weapon::Gun(gun) => Some(gun.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn lasers(&self) -> Vec<Laser> {
+        self.weapons
+            .iter()
+            .filter_map(|weapon| match weapon {
+                Weapon::Laser(laser) => Some(laser.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn missile_launchers(&self) -> Vec<MissileLauncher> {
+        self.weapons
+            .iter()
+            .filter_map(|weapon| match weapon {
+                Weapon::MissileLauncher(missile_launcher) => Some(missile_launcher.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
 impl Default for Gun {
     fn default() -> Gun {
         Gun {
@@ -215,15 +452,114 @@ impl Default for Warhead {
     }
 }
 
-fn radio() -> Radio {
+const DISABLED_ENGINES_ACCELERATION_FACTOR: f64 = 0.25;
+/// Energy used per joule of bullet kinetic energy.
+const GUN_ENERGY_FACTOR: f64 = 1e-4;
+/// Heat generated per unit of energy used by guns.
+const GUN_HEAT_FACTOR: f64 = 1.5;
+/// Energy used per second while an ability is active.
+const ABILITY_ENERGY_RATE: f64 = 20.0;
+/// Heat generated per unit of energy used by abilities.
+const ABILITY_HEAT_FACTOR: f64 = 1.0;
+/// Fraction of the maximum heat above which the radar and weapons are
+/// throttled.
+const THROTTLE_HEAT_FRACTION: f64 = 0.5;
+/// An overheated laser recovers once its heat drops to this fraction of the
+/// maximum.
+pub(crate) const OVERHEAT_RECOVERY_FRACTION: f64 = 0.5;
+
+/// Adds subsystems for the engines at `engines_offset`, the radar, and each
+/// weapon. They all get the same radius and health.
+pub fn add_subsystems(data: &mut ShipData, engines_offset: Vector2<f64>, radius: f64, health: f64) {
+    let subsystem = |subsystem, offset| ShipSubsystem {
+        subsystem,
+        offset,
+        radius,
+        health,
+        max_health: health,
+    };
+    data.subsystems
+        .push(subsystem(Subsystem::Engines, engines_offset));
+    if data.radar.is_some() {
+        data.subsystems
+            .push(subsystem(Subsystem::Radar, vector![0.0, 0.0]));
+    }
+    add_weapon_subsystems(data, radius, health);
+}
+
+/// Gives builtin frigates and cruisers armor and subsystems, for scenarios
+/// with component damage. Other classes are unchanged.
+pub fn add_component_damage(data: &mut ShipData) {
+    let (armor, engines_offset, radius, health) = match data.class {
+        ShipClass::Frigate => (
+            Armor {
+                front: 0.5,
+                side: 0.25,
+                rear: 0.0,
+            },
+            vector![-40.0, 0.0],
+            18.0,
+            1500.0,
+        ),
+        ShipClass::Cruiser => (
+            Armor {
+                front: 0.6,
+                side: 0.3,
+                rear: 0.1,
+            },
+            vector![-80.0, 0.0],
+            35.0,
+            3000.0,
+        ),
+        _ => return,
+    };
+    data.armor = Some(armor);
+    add_subsystems(data, engines_offset, radius, health);
+}
+
+fn add_weapon_subsystems(data: &mut ShipData, radius: f64, health: f64) {
+    let weapon_offsets: Vec<_> = data
+        .guns
+        .iter()
+        .map(|gun| gun.offset)
+        .chain(data.lasers.iter().map(|x| x.offset))
+        .chain(data.missile_launchers.iter().map(|x| x.offset))
+        .collect();
+    for (i, offset) in weapon_offsets.into_iter().enumerate() {
+        data.subsystems.push(ShipSubsystem {
+            subsystem: Subsystem::Weapon(i),
+            offset,
+            radius,
+            health,
+            max_health: health,
+        });
+    }
+}
+
+pub(crate) fn radio() -> Radio {
     // TODO tune this
     Radio {
         power: 20e3,
         rx_cross_section: 5.0,
         min_rssi: 1e-5,
         channel: 0,
+        key: 0,
+        bandwidth: 32,
+        max_message_size: 256,
         sent: None,
+        sent_bytes: None,
+        transmission: None,
         received: None,
+        received_bytes: None,
+    }
+}
+
+// Higher bandwidth radio for coordinating groups of ships.
+fn command_radio() -> Radio {
+    Radio {
+        bandwidth: 64,
+        max_message_size: oort_api::MAX_RADIO_MESSAGE_SIZE,
+        ..radio()
     }
 }
 
@@ -239,7 +575,41 @@ pub fn vulcan_gun() -> Gun {
     }
 }
 
+fn fighter_loadouts() -> Vec<Loadout> {
+    let gun = |y| {
+        Weapon::Gun(Gun {
+            offset: vector![20.0, y],
+            ..vulcan_gun()
+        })
+    };
+    let missile_launcher = |y| {
+        Weapon::MissileLauncher(MissileLauncher {
+            class: ShipClass::Missile,
+            reload_ticks: 5 * 60,
+            reload_ticks_remaining: 0,
+            initial_speed: 100.0,
+            offset: vector![20.0, y],
+            angle: 0.0,
+        })
+    };
+    vec![
+        Loadout {
+            name: "standard",
+            weapons: vec![gun(0.0), missile_launcher(0.0)],
+        },
+        Loadout {
+            name: "gunship",
+            weapons: vec![gun(-3.0), gun(3.0)],
+        },
+        Loadout {
+            name: "missile_boat",
+            weapons: vec![missile_launcher(-3.0), missile_launcher(3.0)],
+        },
+    ]
+}
+
 pub fn fighter(team: i32) -> ShipData {
+    let loadouts = fighter_loadouts();
     ShipData {
         class: ShipClass::Fighter,
         team,
@@ -249,18 +619,9 @@ pub fn fighter(team: i32) -> ShipData {
         max_backward_acceleration: 30.0,
         max_lateral_acceleration: 30.0,
         max_angular_acceleration: TAU,
-        guns: vec![Gun {
-            offset: vector![20.0, 0.0],
-            ..vulcan_gun()
-        }],
-        missile_launchers: vec![MissileLauncher {
-            class: ShipClass::Missile,
-            reload_ticks: 5 * 60,
-            reload_ticks_remaining: 0,
-            initial_speed: 100.0,
-            offset: vector![20.0, 0.0],
-            angle: 0.0,
-        }],
+        guns: loadouts[0].guns(),
+        missile_launchers: loadouts[0].missile_launchers(),
+        loadouts,
         radar: Some(Radar {
             power: 20e3,
             rx_cross_section: 5.0,
@@ -279,7 +640,57 @@ pub fn fighter(team: i32) -> ShipData {
     }
 }
 
+fn frigate_loadouts() -> Vec<Loadout> {
+    let main_gun = Weapon::Gun(Gun {
+        magazine_size: 1,
+        magazine_reload_ticks: 0,
+        reload_ticks: 2 * 60,
+        speed: 4000.0,
+        offset: vector![40.0, 0.0],
+        bullet_mass: 1.0,
+        ttl: 60.0,
+        ..Default::default()
+    });
+    let turret = |y| {
+        Weapon::Gun(Gun {
+            offset: vector![0.0, y],
+            max_angle: TAU,
+            ..vulcan_gun()
+        })
+    };
+    let laser = |y| {
+        Weapon::Laser(Laser {
+            offset: vector![0.0, y],
+            ..Default::default()
+        })
+    };
+    let missile_launcher = Weapon::MissileLauncher(MissileLauncher {
+        class: ShipClass::Missile,
+        reload_ticks: 2 * 60,
+        reload_ticks_remaining: 0,
+        initial_speed: 100.0,
+        offset: vector![60.0, 0.0],
+        angle: 0.0,
+    });
+    vec![
+        Loadout {
+            name: "standard",
+            weapons: vec![
+                main_gun.clone(),
+                turret(15.0),
+                turret(-15.0),
+                missile_launcher.clone(),
+            ],
+        },
+        Loadout {
+            name: "point_defense",
+            weapons: vec![main_gun, laser(15.0), laser(-15.0), missile_launcher],
+        },
+    ]
+}
+
 pub fn frigate(team: i32) -> ShipData {
+    let loadouts = frigate_loadouts();
     ShipData {
         class: ShipClass::Frigate,
         team,
@@ -289,36 +700,9 @@ pub fn frigate(team: i32) -> ShipData {
         max_backward_acceleration: 5.0,
         max_lateral_acceleration: 5.0,
         max_angular_acceleration: TAU / 8.0,
-        guns: vec![
-            Gun {
-                magazine_size: 1,
-                magazine_reload_ticks: 0,
-                reload_ticks: 2 * 60,
-                speed: 4000.0,
-                offset: vector![40.0, 0.0],
-                bullet_mass: 1.0,
-                ttl: 60.0,
-                ..Default::default()
-            },
-            Gun {
-                offset: vector![0.0, 15.0],
-                max_angle: TAU,
-                ..vulcan_gun()
-            },
-            Gun {
-                offset: vector![0.0, -15.0],
-                max_angle: TAU,
-                ..vulcan_gun()
-            },
-        ],
-        missile_launchers: vec![MissileLauncher {
-            class: ShipClass::Missile,
-            reload_ticks: 2 * 60,
-            reload_ticks_remaining: 0,
-            initial_speed: 100.0,
-            offset: vector![60.0, 0.0],
-            angle: 0.0,
-        }],
+        guns: loadouts[0].guns(),
+        missile_launchers: loadouts[0].missile_launchers(),
+        loadouts,
         radar: Some(Radar {
             power: 100e3,
             rx_cross_section: 10.0,
@@ -330,6 +714,16 @@ pub fn frigate(team: i32) -> ShipData {
     }
 }
 
+/// The jamming ability frigates get in scenarios with radio interference.
+pub fn radio_jam() -> ShipAbility {
+    ShipAbility {
+        ability: Ability::RadioJam,
+        active_time: 2.0,
+        reload_time: 10.0,
+        ..Default::default()
+    }
+}
+
 pub const CRUISER_RADAR_CROSS_SECTION: f64 = 40.0;
 
 pub fn cruiser(team: i32) -> ShipData {
@@ -391,14 +785,14 @@ pub fn cruiser(team: i32) -> ShipData {
         }),
         radar_cross_section: CRUISER_RADAR_CROSS_SECTION,
         radios: vec![
-            radio(),
-            radio(),
-            radio(),
-            radio(),
-            radio(),
-            radio(),
-            radio(),
-            radio(),
+            command_radio(),
+            command_radio(),
+            command_radio(),
+            command_radio(),
+            command_radio(),
+            command_radio(),
+            command_radio(),
+            command_radio(),
         ],
         abilities: vec![ShipAbility {
             ability: Ability::Shield,
@@ -525,8 +919,8 @@ pub fn create(
     let body_handle = sim.bodies.insert(rigid_body);
     let handle = ShipHandle(body_handle.0);
     let team = data.team;
-    let model = model::load(data.class);
-    let restitution = match data.class {
+    let model = model::load(data.class, &sim.ship_classes);
+    let restitution = match data.class.base() {
         ShipClass::Missile => 0.0,
         _ => 0.1,
     };
@@ -623,12 +1017,14 @@ impl<'a> ShipAccessor<'a> {
     }
 
     pub fn get_reload_ticks(&self, idx: usize) -> u32 {
-        if let Some(gun) = self.data().guns.get(idx) {
+        let data = self.data();
+        if let Some(gun) = data.guns.get(idx) {
             gun.reload_ticks_remaining
-        } else if let Some(missile) = self
-            .data()
+        } else if let Some(laser) = data.lasers.get(idx - data.guns.len()) {
+            laser.cooldown_ticks()
+        } else if let Some(missile) = data
             .missile_launchers
-            .get(idx - self.data().guns.len())
+            .get(idx - data.guns.len() - data.lasers.len())
         {
             missile.reload_ticks_remaining
         } else {
@@ -699,8 +1095,11 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
 
     pub fn fire(&mut self, index: i64) {
         let num_guns = self.data().guns.len() as i64;
-        if index >= num_guns {
-            self.launch_missile(index - num_guns);
+        let num_lasers = self.data().lasers.len() as i64;
+        if index >= num_guns + num_lasers {
+            self.launch_missile(index - num_guns - num_lasers);
+        } else if index >= num_guns {
+            self.fire_laser(index - num_guns);
         } else {
             self.fire_gun(index);
         }
@@ -708,7 +1107,9 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
 
     pub fn fire_gun(&mut self, index: i64) {
         let ship_data = self.data_mut();
-        if index as usize >= ship_data.guns.len() {
+        if index as usize >= ship_data.guns.len()
+            || ship_data.is_disabled(Subsystem::Weapon(index as usize))
+        {
             return;
         }
         let team = ship_data.team;
@@ -717,7 +1118,16 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
             if gun.reload_ticks_remaining > 0 {
                 return;
             }
-            gun.reload_ticks_remaining = gun.reload_ticks;
+            let mut throttle = 1.0;
+            if let Some(power) = ship_data.power.as_mut() {
+                throttle = power.throttle();
+                let energy = gun.energy();
+                if throttle <= 0.0 || !power.use_energy(energy, energy * GUN_HEAT_FACTOR) {
+                    return;
+                }
+            }
+            // Overheating lowers the rate of fire.
+            gun.reload_ticks_remaining = (gun.reload_ticks as f64 / throttle).ceil() as u32;
             gun.magazine_remaining -= gun.burst_size;
             if gun.magazine_remaining <= 0 {
                 gun.magazine_remaining = gun.magazine_size;
@@ -725,6 +1135,8 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
             }
             gun.clone()
         };
+        self.simulation
+            .add_score(team, SHOTS_FIRED, gun.burst_size as f64);
 
         let mut rng =
             rng::new_rng(self.simulation.tick() ^ u64::from(self.handle) as u32 ^ index as u32);
@@ -763,15 +1175,27 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
                     team,
                     color,
                     ttl: gun.ttl + t as f32,
+                    hit: false,
                 },
             );
             t += dt;
         }
     }
 
+    /// Fires the laser for this tick. The beam is resolved in `laser::tick`.
+    pub fn fire_laser(&mut self, index: i64) {
+        if let Some(laser) = self.data_mut().lasers.get_mut(index as usize) {
+            laser.firing = true;
+        }
+    }
+
     pub fn launch_missile(&mut self, index: i64) {
         let missile_launcher = {
             let ship_data = self.data_mut();
+            let weapon_index = ship_data.guns.len() + ship_data.lasers.len() + index as usize;
+            if ship_data.is_disabled(Subsystem::Weapon(weapon_index)) {
+                return;
+            }
             if let Some(missile_launcher) =
                 ship_data.missile_launchers.get_mut(index as usize).as_mut()
             {
@@ -793,26 +1217,60 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
         let rot2 = rot * UnitComplex::new(missile_launcher.angle);
         let v = body.linvel() + rot2.transform_vector(&vector![speed, 0.0]);
         let team = self.data().team;
-        create(
-            self.simulation,
-            p,
-            v,
-            rot2.angle(),
-            match missile_launcher.class {
-                ShipClass::Missile => missile(team),
-                ShipClass::Torpedo => torpedo(team),
-                _ => unimplemented!(),
-            },
-        );
+        let data = match missile_launcher.class {
+            ShipClass::Missile => missile(team),
+            ShipClass::Torpedo => torpedo(team),
+            ShipClass::Custom { .. } => {
+                match self
+                    .simulation
+                    .ship_classes
+                    .ship_data(missile_launcher.class, team)
+                {
+                    Some(data) => data,
+                    None => return,
+                }
+            }
+            _ => unimplemented!(),
+        };
+        create(self.simulation, p, v, rot2.angle(), data);
+    }
+
+    /// Switches to one of the ship's loadouts. Only allowed before the ship's
+    /// first tick has finished and if the scenario permits the loadout.
+    pub fn select_loadout(&mut self, index: usize) {
+        let data = self.data();
+        if data.loadout_locked {
+            return;
+        }
+        let Some(loadout) = data.loadouts.get(index) else {
+            return;
+        };
+        if !self.simulation.is_loadout_allowed(data.class, loadout.name) {
+            return;
+        }
+        let guns = loadout.guns();
+        let lasers = loadout.lasers();
+        let missile_launchers = loadout.missile_launchers();
+        let data = self.data_mut();
+        data.guns = guns;
+        data.lasers = lasers;
+        data.missile_launchers = missile_launchers;
+        data.loadout_locked = true;
+        if let Some(template) = data.subsystems.first().cloned() {
+            data.subsystems
+                .retain(|x| !matches!(x.subsystem, Subsystem::Weapon(_)));
+            add_weapon_subsystems(data, template.radius, template.max_health);
+        }
     }
 
     pub fn aim(&mut self, index: i64, heading: f64) {
         let ship_data = self.data_mut();
-        if index as usize >= ship_data.guns.len() {
-            return;
+        let index = index as usize;
+        if let Some(gun) = ship_data.guns.get_mut(index) {
+            gun.heading = heading;
+        } else if let Some(laser) = ship_data.lasers.get_mut(index - ship_data.guns.len()) {
+            laser.aim_heading = heading;
         }
-        let gun = &mut ship_data.guns[index as usize];
-        gun.heading = heading;
     }
 
     pub fn explode(&mut self) {
@@ -842,6 +1300,7 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
                     team,
                     color: color::to_u32(color),
                     ttl: warhead.ttl,
+                    hit: false,
                 },
             );
             self.simulation.events.particles.push(Particle {
@@ -900,6 +1359,11 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
             }
         }
 
+        // Power.
+        if let Some(power) = self.data_mut().power.as_mut() {
+            power.tick();
+        }
+
         // Acceleration.
         {
             let mut acceleration = self.data().acceleration;
@@ -950,7 +1414,16 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
 
         // Special abilities.
         {
-            for ship_ability in self.data_mut().abilities.iter_mut() {
+            let ship_data = self.data_mut();
+            for ship_ability in ship_data.abilities.iter_mut() {
+                if ship_ability.active_time_remaining > 0.0 {
+                    if let Some(power) = ship_data.power.as_mut() {
+                        let energy = ABILITY_ENERGY_RATE * PHYSICS_TICK_LENGTH;
+                        if !power.use_energy(energy, energy * ABILITY_HEAT_FACTOR) {
+                            ship_ability.active_time_remaining = 0.0;
+                        }
+                    }
+                }
                 ship_ability.active_time_remaining =
                     (ship_ability.active_time_remaining - PHYSICS_TICK_LENGTH).max(0.0);
                 ship_ability.reload_time_remaining =
@@ -960,8 +1433,9 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
 
         // Destruction.
         if self.data().destroyed {
+            let gas_budget = self.simulation.gas_budget(self.handle);
             if let Some(team_ctrl) = self.simulation.get_team_controller(self.data().team) {
-                team_ctrl.borrow_mut().remove_ship(self.handle);
+                team_ctrl.borrow_mut().remove_ship(self.handle, gas_budget);
             }
             self.simulation.ships.remove(self.handle);
             self.simulation.bodies.remove(
@@ -979,7 +1453,8 @@ impl<'a: 'b, 'b> ShipAccessorMut<'a> {
     }
 
     pub fn handle_collision(&mut self) {
-        if self.data().class == ShipClass::Missile || self.data().class == ShipClass::Torpedo {
+        let class = self.data().class.base();
+        if class == ShipClass::Missile || class == ShipClass::Torpedo {
             self.explode();
         }
     }
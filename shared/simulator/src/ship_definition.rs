@@ -0,0 +1,683 @@
+//! Ship classes defined in TOML or JSON instead of Rust, so that variants and
+//! new hulls can be tried without changing the simulator.
+//!
+//! Example:
+//!
+//! ```toml
+//! name = "heavy_fighter"
+//! base = "fighter"
+//! health = 200
+//! mass = 20000
+//! max_forward_acceleration = 40
+//! max_backward_acceleration = 20
+//! max_lateral_acceleration = 20
+//! max_angular_acceleration = 3.14
+//! hull = [[-10, -10], [15, 0], [-10, 10]]
+//! armor = { front = 0.5, side = 0.25, rear = 0 }
+//! subsystem_health = 50
+//! power = { max_energy = 150, recharge_rate = 30, max_heat = 100, cooling_rate = 25 }
+//!
+//! [[guns]]
+//! offset = [15, 0]
+//! reload_ticks = 6
+//! magazine_size = 20
+//! bullet_mass = 0.2
+//!
+//! [[missile_launchers]]
+//! class = "missile"
+//! offset = [15, 0]
+//!
+//! [radar]
+//! power = 30000
+//!
+//! [[radios]]
+//!
+//! [[abilities]]
+//! ability = "Boost"
+//! active_time = 2
+//! reload_time = 10
+//! ```
+//!
+//! Each simulation has its own [`ShipClasses`] registry. Registering a
+//! definition returns a `ShipClass::Custom` pointing at it. The class ID is
+//! derived from the definition, so the same definition gets the same ID in
+//! every process. The first snapshot of a stream and each replay carry the
+//! definitions, and [`ShipClasses::load`] registers them in a process that
+//! didn't run the simulation, such as the renderer or a replay viewer.
+//!
+//! Missile launchers can launch "missile", "torpedo", or a custom class
+//! registered earlier and passed to [`ShipClasses::register`] by name.
+use crate::radar::Radar;
+use crate::ship::{self, Armor, Gun, MissileLauncher, Power, ShipAbility, ShipClass, ShipData};
+use nalgebra::{vector, Vector2};
+use oort_api::prelude::radio_internal::MAX_RADIOS;
+use oort_api::Ability;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const MAX_GUNS: usize = 16;
+const MAX_MISSILE_LAUNCHERS: usize = 16;
+const MAX_HULL_VERTICES: usize = 64;
+const MAX_HULL_RADIUS: f32 = 1000.0;
+/// Radius around each subsystem where hits damage it, relative to the hull.
+const SUBSYSTEM_RADIUS_FRACTION: f64 = 0.3;
+
+/// A definition along with the classes its missile launchers refer to.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Registered {
+    pub definition: ShipDefinition,
+    /// Resolved from the class names of the definition's missile launchers.
+    pub launcher_classes: Vec<ShipClass>,
+}
+
+impl Registered {
+    /// FNV-1a hash of the serialized definition. Unlike `DefaultHasher`, this
+    /// is stable across processes and Rust releases.
+    fn id(&self) -> u32 {
+        let bytes = serde_json::to_vec(self).expect("serializing ship definition");
+        bytes.iter().fold(0x811c9dc5, |hash: u32, &byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ShipDefinition {
+    pub name: String,
+    /// The builtin class reported to AIs and used by victory conditions.
+    pub base: BaseClass,
+    pub health: f64,
+    pub mass: f64,
+    pub max_forward_acceleration: f64,
+    pub max_backward_acceleration: f64,
+    pub max_lateral_acceleration: f64,
+    pub max_angular_acceleration: f64,
+    #[serde(default)]
+    pub guns: Vec<GunDefinition>,
+    #[serde(default)]
+    pub missile_launchers: Vec<MissileLauncherDefinition>,
+    #[serde(default)]
+    pub radar: Option<RadarDefinition>,
+    #[serde(default = "default_radar_cross_section")]
+    pub radar_cross_section: f64,
+    #[serde(default)]
+    pub radios: Vec<RadioDefinition>,
+    #[serde(default)]
+    pub abilities: Vec<AbilityDefinition>,
+    /// Remaining delta-v, for ships with limited fuel like missiles.
+    #[serde(default)]
+    pub fuel: Option<f64>,
+    /// Ticks until the ship is destroyed, for ships like missiles.
+    #[serde(default)]
+    pub ttl: Option<u64>,
+    #[serde(default)]
+    pub armor: Option<Armor>,
+    /// Health of each subsystem: the engines at the rear of the hull, the
+    /// radar at the center, and each weapon. Without it the ship only has
+    /// hull health.
+    #[serde(default)]
+    pub subsystem_health: Option<f64>,
+    /// Energy and heat budget in scenarios that limit energy. Defaults to
+    /// the budget of the base class.
+    #[serde(default)]
+    pub power: Option<PowerDefinition>,
+    /// Vertices of the hull in meters, with the ship facing +x. The collider
+    /// is their convex hull.
+    pub hull: Vec<[f32; 2]>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BaseClass {
+    Fighter,
+    Frigate,
+    Cruiser,
+    Target,
+    Missile,
+    Torpedo,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GunDefinition {
+    pub magazine_size: i32,
+    pub magazine_reload_ticks: u32,
+    pub reload_ticks: u32,
+    pub speed: f64,
+    pub speed_error: f64,
+    pub offset: [f64; 2],
+    pub min_angle: f64,
+    pub max_angle: f64,
+    pub inaccuracy: f64,
+    pub burst_size: i32,
+    pub ttl: f32,
+    pub bullet_mass: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MissileLauncherDefinition {
+    /// "missile", "torpedo", or the name of a custom class.
+    pub class: String,
+    #[serde(default = "default_launcher_reload_ticks")]
+    pub reload_ticks: u32,
+    #[serde(default = "default_launcher_initial_speed")]
+    pub initial_speed: f64,
+    #[serde(default)]
+    pub offset: [f64; 2],
+    #[serde(default)]
+    pub angle: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RadarDefinition {
+    pub power: f64,
+    pub rx_cross_section: f64,
+    pub min_width: f64,
+    pub max_width: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RadioDefinition {
+    pub power: f64,
+    pub rx_cross_section: f64,
+    /// Bytes transmitted per tick.
+    pub bandwidth: usize,
+    pub max_message_size: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PowerDefinition {
+    pub max_energy: f64,
+    /// Energy regained per second.
+    pub recharge_rate: f64,
+    pub max_heat: f64,
+    /// Heat lost per second.
+    pub cooling_rate: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AbilityDefinition {
+    pub ability: Ability,
+    /// Seconds the ability stays active.
+    pub active_time: f64,
+    /// Seconds before the ability can be activated again.
+    pub reload_time: f64,
+}
+
+fn default_radar_cross_section() -> f64 {
+    ShipData::default().radar_cross_section
+}
+
+fn default_launcher_reload_ticks() -> u32 {
+    5 * 60
+}
+
+fn default_launcher_initial_speed() -> f64 {
+    100.0
+}
+
+impl Default for GunDefinition {
+    fn default() -> Self {
+        let gun = Gun::default();
+        Self {
+            magazine_size: gun.magazine_size,
+            magazine_reload_ticks: gun.magazine_reload_ticks,
+            reload_ticks: gun.reload_ticks,
+            speed: gun.speed,
+            speed_error: gun.speed_error,
+            offset: [gun.offset.x, gun.offset.y],
+            min_angle: gun.min_angle,
+            max_angle: gun.max_angle,
+            inaccuracy: gun.inaccuracy,
+            burst_size: gun.burst_size,
+            ttl: gun.ttl,
+            bullet_mass: gun.bullet_mass,
+        }
+    }
+}
+
+impl Default for RadarDefinition {
+    fn default() -> Self {
+        let radar = Radar::default();
+        Self {
+            power: radar.power,
+            rx_cross_section: radar.rx_cross_section,
+            min_width: radar.min_width,
+            max_width: radar.max_width,
+        }
+    }
+}
+
+impl Default for RadioDefinition {
+    fn default() -> Self {
+        let radio = ship::radio();
+        Self {
+            power: radio.power,
+            rx_cross_section: radio.rx_cross_section,
+            bandwidth: radio.bandwidth,
+            max_message_size: radio.max_message_size,
+        }
+    }
+}
+
+impl BaseClass {
+    pub fn class(self) -> ShipClass {
+        match self {
+            BaseClass::Fighter => ShipClass::Fighter,
+            BaseClass::Frigate => ShipClass::Frigate,
+            BaseClass::Cruiser => ShipClass::Cruiser,
+            BaseClass::Target => ShipClass::Target,
+            BaseClass::Missile => ShipClass::Missile,
+            BaseClass::Torpedo => ShipClass::Torpedo,
+        }
+    }
+}
+
+impl ShipDefinition {
+    pub fn from_toml(s: &str) -> Result<Self, String> {
+        let definition: Self = toml::from_str(s).map_err(|e| e.to_string())?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        let definition: Self = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    /// Checks everything except that launcher classes exist, which is done
+    /// by [`ShipClasses::register`].
+    pub fn validate(&self) -> Result<(), String> {
+        let name = &self.name;
+        if name.is_empty() {
+            return Err("Ship class name must not be empty".to_string());
+        }
+        let positive = [
+            ("health", self.health),
+            ("mass", self.mass),
+            ("radar_cross_section", self.radar_cross_section),
+        ];
+        for (field, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{name}: {field} must be positive, got {value}"));
+            }
+        }
+        let non_negative = [
+            ("max_forward_acceleration", self.max_forward_acceleration),
+            ("max_backward_acceleration", self.max_backward_acceleration),
+            ("max_lateral_acceleration", self.max_lateral_acceleration),
+            ("max_angular_acceleration", self.max_angular_acceleration),
+            ("fuel", self.fuel.unwrap_or_default()),
+        ];
+        for (field, value) in non_negative {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("{name}: {field} must not be negative, got {value}"));
+            }
+        }
+
+        if self.guns.len() > MAX_GUNS {
+            return Err(format!("{name}: at most {MAX_GUNS} guns are supported"));
+        }
+        for gun in self.guns.iter() {
+            let finite = [
+                gun.speed,
+                gun.speed_error,
+                gun.offset[0],
+                gun.offset[1],
+                gun.min_angle,
+                gun.max_angle,
+                gun.inaccuracy,
+                gun.bullet_mass,
+                gun.ttl as f64,
+            ]
+            .iter()
+            .all(|v| v.is_finite());
+            if !finite
+                || gun.magazine_size <= 0
+                || gun.burst_size <= 0
+                || gun.speed_error < 0.0
+                || gun.inaccuracy < 0.0
+                || gun.bullet_mass <= 0.0
+                || gun.min_angle > gun.max_angle
+            {
+                return Err(format!("{name}: invalid gun {gun:?}"));
+            }
+        }
+
+        if self.missile_launchers.len() > MAX_MISSILE_LAUNCHERS {
+            return Err(format!(
+                "{name}: at most {MAX_MISSILE_LAUNCHERS} missile launchers are supported"
+            ));
+        }
+        for launcher in self.missile_launchers.iter() {
+            if ![
+                launcher.initial_speed,
+                launcher.offset[0],
+                launcher.offset[1],
+                launcher.angle,
+            ]
+            .iter()
+            .all(|v| v.is_finite())
+            {
+                return Err(format!("{name}: invalid missile launcher {launcher:?}"));
+            }
+        }
+
+        if let Some(radar) = self.radar.as_ref() {
+            if ![radar.power, radar.rx_cross_section]
+                .iter()
+                .all(|v| v.is_finite() && *v > 0.0)
+                || !(radar.min_width > 0.0 && radar.min_width <= radar.max_width)
+            {
+                return Err(format!("{name}: invalid radar {radar:?}"));
+            }
+        }
+
+        if self.radios.len() > MAX_RADIOS {
+            return Err(format!("{name}: at most {MAX_RADIOS} radios are supported"));
+        }
+        for radio in self.radios.iter() {
+            if ![radio.power, radio.rx_cross_section]
+                .iter()
+                .all(|v| v.is_finite() && *v > 0.0)
+                || radio.bandwidth == 0
+                || radio.max_message_size == 0
+                || radio.max_message_size > oort_api::MAX_RADIO_MESSAGE_SIZE
+            {
+                return Err(format!("{name}: invalid radio {radio:?}"));
+            }
+        }
+
+        if let Some(armor) = self.armor.as_ref() {
+            if ![armor.front, armor.side, armor.rear]
+                .iter()
+                .all(|v| (0.0..=1.0).contains(v))
+            {
+                return Err(format!(
+                    "{name}: armor must be between 0 and 1, got {armor:?}"
+                ));
+            }
+        }
+
+        if let Some(power) = self.power.as_ref() {
+            if ![
+                power.max_energy,
+                power.recharge_rate,
+                power.max_heat,
+                power.cooling_rate,
+            ]
+            .iter()
+            .all(|v| v.is_finite() && *v >= 0.0)
+            {
+                return Err(format!("{name}: invalid power {power:?}"));
+            }
+        }
+
+        if let Some(health) = self.subsystem_health {
+            if !(health.is_finite() && health > 0.0) {
+                return Err(format!(
+                    "{name}: subsystem_health must be positive, got {health}"
+                ));
+            }
+        }
+
+        for ability in self.abilities.iter() {
+            if !(ability.active_time.is_finite()
+                && ability.active_time >= 0.0
+                && ability.reload_time.is_finite()
+                && ability.reload_time >= 0.0)
+            {
+                return Err(format!("{name}: invalid ability {ability:?}"));
+            }
+        }
+
+        if self.hull.len() < 3 || self.hull.len() > MAX_HULL_VERTICES {
+            return Err(format!(
+                "{name}: hull must have between 3 and {MAX_HULL_VERTICES} vertices"
+            ));
+        }
+        if !self
+            .hull
+            .iter()
+            .flatten()
+            .all(|v| v.is_finite() && v.abs() <= MAX_HULL_RADIUS)
+        {
+            return Err(format!(
+                "{name}: hull vertices must be within {MAX_HULL_RADIUS} m of the center"
+            ));
+        }
+        // Shoelace formula. Zero if the vertices are collinear.
+        let area = self
+            .hull
+            .iter()
+            .zip(self.hull.iter().cycle().skip(1))
+            .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+            .sum::<f32>()
+            / 2.0;
+        if area.abs() < 1e-3 {
+            return Err(format!("{name}: hull must have a non-zero area"));
+        }
+
+        Ok(())
+    }
+
+    pub fn hull(&self) -> Vec<Vector2<f32>> {
+        self.hull.iter().map(|v| vector![v[0], v[1]]).collect()
+    }
+
+    fn ship_data(&self, class: ShipClass, launcher_classes: &[ShipClass], team: i32) -> ShipData {
+        let mut data = ShipData {
+            class,
+            team,
+            health: self.health,
+            mass: self.mass,
+            max_forward_acceleration: self.max_forward_acceleration,
+            max_backward_acceleration: self.max_backward_acceleration,
+            max_lateral_acceleration: self.max_lateral_acceleration,
+            max_angular_acceleration: self.max_angular_acceleration,
+            guns: self
+                .guns
+                .iter()
+                .map(|gun| Gun {
+                    magazine_size: gun.magazine_size,
+                    magazine_reload_ticks: gun.magazine_reload_ticks,
+                    reload_ticks: gun.reload_ticks,
+                    speed: gun.speed,
+                    speed_error: gun.speed_error,
+                    offset: vector![gun.offset[0], gun.offset[1]],
+                    min_angle: gun.min_angle,
+                    max_angle: gun.max_angle,
+                    inaccuracy: gun.inaccuracy,
+                    burst_size: gun.burst_size,
+                    ttl: gun.ttl,
+                    bullet_mass: gun.bullet_mass,
+                    ..Default::default()
+                })
+                .collect(),
+            missile_launchers: self
+                .missile_launchers
+                .iter()
+                .zip(launcher_classes.iter())
+                .map(|(launcher, &class)| MissileLauncher {
+                    class,
+                    reload_ticks: launcher.reload_ticks,
+                    reload_ticks_remaining: 0,
+                    initial_speed: launcher.initial_speed,
+                    offset: vector![launcher.offset[0], launcher.offset[1]],
+                    angle: launcher.angle,
+                })
+                .collect(),
+            radar: self.radar.as_ref().map(|radar| Radar {
+                power: radar.power,
+                rx_cross_section: radar.rx_cross_section,
+                min_width: radar.min_width,
+                max_width: radar.max_width,
+                ..Default::default()
+            }),
+            radar_cross_section: self.radar_cross_section,
+            radios: self
+                .radios
+                .iter()
+                .map(|radio| {
+                    let mut r = ship::radio();
+                    r.power = radio.power;
+                    r.rx_cross_section = radio.rx_cross_section;
+                    r.bandwidth = radio.bandwidth;
+                    r.max_message_size = radio.max_message_size;
+                    r
+                })
+                .collect(),
+            abilities: self
+                .abilities
+                .iter()
+                .map(|ability| ShipAbility {
+                    ability: ability.ability,
+                    active_time: ability.active_time,
+                    reload_time: ability.reload_time,
+                    ..Default::default()
+                })
+                .collect(),
+            fuel: self.fuel,
+            ttl: self.ttl,
+            armor: self.armor,
+            ..Default::default()
+        };
+        if let Some(health) = self.subsystem_health {
+            let hull = self.hull();
+            let radius = hull.iter().map(|v| v.norm()).fold(0.0, f32::max) as f64;
+            let rear = hull.iter().map(|v| v.x).fold(0.0, f32::min) as f64;
+            ship::add_subsystems(
+                &mut data,
+                vector![0.75 * rear, 0.0],
+                SUBSYSTEM_RADIUS_FRACTION * radius,
+                health,
+            );
+        }
+        data
+    }
+}
+
+/// Custom ship classes by ID.
+#[derive(Clone, Debug, Default)]
+pub struct ShipClasses {
+    classes: BTreeMap<u32, Arc<Registered>>,
+}
+
+impl ShipClasses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates and registers a definition, returning its class. `classes`
+    /// maps names to custom classes that missile launchers can refer to.
+    pub fn register(
+        &mut self,
+        definition: ShipDefinition,
+        classes: &BTreeMap<String, ShipClass>,
+    ) -> Result<ShipClass, String> {
+        definition.validate()?;
+        let launcher_classes = definition
+            .missile_launchers
+            .iter()
+            .map(|launcher| match launcher.class.as_str() {
+                "missile" => Ok(ShipClass::Missile),
+                "torpedo" => Ok(ShipClass::Torpedo),
+                name => classes
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| format!("{}: unknown missile class {name:?}", definition.name)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.insert(Registered {
+            definition,
+            launcher_classes,
+        })
+    }
+
+    fn insert(&mut self, registered: Registered) -> Result<ShipClass, String> {
+        let id = registered.id();
+        let base = registered.definition.base;
+        match self.classes.get(&id) {
+            Some(existing) if **existing != registered => {
+                return Err(format!(
+                    "{}: ship class ID {id} is already used by {}",
+                    registered.definition.name, existing.definition.name
+                ));
+            }
+            Some(_) => {}
+            None => {
+                self.classes.insert(id, Arc::new(registered));
+            }
+        }
+        Ok(ShipClass::Custom { id, base })
+    }
+
+    /// Registers definitions taken from a snapshot or replay, keeping their
+    /// class IDs.
+    pub fn load(&mut self, classes: &[Registered]) -> Result<(), String> {
+        for registered in classes.iter() {
+            self.insert(registered.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Returns None for builtin classes and classes that weren't registered.
+    pub fn lookup(&self, class: ShipClass) -> Option<&Registered> {
+        let ShipClass::Custom { id, .. } = class else {
+            return None;
+        };
+        self.classes.get(&id).map(Arc::as_ref)
+    }
+
+    /// All registered definitions, to be sent along with snapshots.
+    pub fn definitions(&self) -> Vec<Registered> {
+        self.classes
+            .values()
+            .map(|registered| (**registered).clone())
+            .collect()
+    }
+
+    /// Unregistered custom classes are named after their base class.
+    pub fn name(&self, class: ShipClass) -> &str {
+        match self.lookup(class) {
+            Some(registered) => &registered.definition.name,
+            None => class.base().name(),
+        }
+    }
+
+    /// Returns the power budget of a class in scenarios that limit energy,
+    /// from its definition or else its base class.
+    pub fn power(&self, class: ShipClass) -> Option<Power> {
+        match self
+            .lookup(class)
+            .and_then(|registered| registered.definition.power.as_ref())
+        {
+            Some(power) => Some(Power::new(
+                power.max_energy,
+                power.recharge_rate,
+                power.max_heat,
+                power.cooling_rate,
+            )),
+            None => ship::default_power(class),
+        }
+    }
+
+    /// Creates a ship of a registered class. Returns None for builtin
+    /// classes.
+    pub fn ship_data(&self, class: ShipClass, team: i32) -> Option<ShipData> {
+        let registered = self.lookup(class)?;
+        Some(
+            registered
+                .definition
+                .ship_data(class, &registered.launcher_classes, team),
+        )
+    }
+}
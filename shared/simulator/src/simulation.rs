@@ -3,14 +3,16 @@ use crate::collision;
 use crate::debug;
 pub use crate::debug::Line;
 use crate::index_set::{HasIndex, IndexSet};
+use crate::laser;
 use crate::radar;
 use crate::radio;
 use crate::scenario;
-use crate::scenario::Scenario;
-use crate::ship::{ShipAccessor, ShipAccessorMut, ShipData, ShipHandle, Target};
+use crate::scenario::{Scenario, Scores};
+use crate::ship::{ShipAccessor, ShipAccessorMut, ShipClass, ShipData, ShipHandle, Target};
+use crate::ship_definition::ShipClasses;
 use crate::snapshot::*;
 use crate::vm;
-use crate::vm::TeamController;
+use crate::vm::{TeamController, TeamControllerCheckpoint};
 use crossbeam::channel::Sender;
 use instant::Instant;
 use nalgebra::{Vector2, Vector4};
@@ -22,6 +24,7 @@ use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
+use std::sync::Arc;
 
 pub const MAX_WORLD_SIZE: f64 = 200000.0;
 pub const PHYSICS_TICK_LENGTH: f64 = 1.0 / 60.0;
@@ -34,6 +37,9 @@ pub enum Code {
     Builtin(String),
     #[cfg(feature = "precompile")]
     Precompiled(bytes::Bytes),
+    #[cfg(feature = "native")]
+    #[serde(skip)]
+    Native(crate::vm::native::NativeCode),
 }
 
 pub struct Simulation {
@@ -50,6 +56,7 @@ pub struct Simulation {
     pub(crate) colliders: ColliderSet,
     integration_parameters: IntegrationParameters,
     physics_pipeline: PhysicsPipeline,
+    pub(crate) query_pipeline: QueryPipeline,
     pub(crate) island_manager: IslandManager,
     broad_phase: BroadPhase,
     narrow_phase: NarrowPhase,
@@ -63,12 +70,61 @@ pub struct Simulation {
     timing: Timing,
     pub(crate) rng: ChaCha8Rng,
     world_size: f64,
+    max_memory: usize,
+    scores: Scores,
+    // Hashed when a replay starts, since hashing a builtin AI decompresses it.
+    codes: Arc<Vec<Code>>,
+    pub(crate) ship_classes: ShipClasses,
+    replay_recorder: Option<ReplayRecorder>,
+}
+
+/// A saved copy of the complete simulation state, from which any number of
+/// simulations can be restored.
+pub struct Checkpoint {
+    scenario: Box<dyn Scenario>,
+    ships: IndexSet<ShipHandle>,
+    ship_data: Coarena<ShipData>,
+    team_controllers: HashMap<i32, TeamControllerCheckpoint>,
+    new_ships: Vec<(i32, ShipHandle)>,
+    bullets: IndexSet<BulletHandle>,
+    bullet_data: Coarena<BulletData>,
+    bodies: RigidBodySet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    colliders: ColliderSet,
+    integration_parameters: IntegrationParameters,
+    query_pipeline: QueryPipeline,
+    island_manager: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    ccd_solver: CCDSolver,
+    tick: u32,
+    cheats: bool,
+    seed: u32,
+    rng: ChaCha8Rng,
+    world_size: f64,
+    max_memory: usize,
+    scores: Scores,
+    codes: Arc<Vec<Code>>,
+    ship_classes: ShipClasses,
+}
+
+impl Checkpoint {
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
 }
 
 impl Simulation {
     pub fn new(scenario_name: &str, seed: u32, codes: &[Code]) -> Box<Simulation> {
-        let mut scenario = scenario::load(scenario_name);
+        Simulation::new_with_scenario(scenario::load(scenario_name), seed, codes)
+    }
 
+    pub fn new_with_scenario(
+        mut scenario: Box<dyn Scenario>,
+        seed: u32,
+        codes: &[Code],
+    ) -> Box<Simulation> {
         log::debug!("seed {seed}");
         let (contact_send, contact_recv) = crossbeam::channel::unbounded();
         let mut sim = Box::new(Simulation {
@@ -89,6 +145,7 @@ impl Simulation {
                 ..Default::default()
             },
             physics_pipeline: PhysicsPipeline::new(),
+            query_pipeline: QueryPipeline::new(),
             island_manager: IslandManager::new(),
             broad_phase: BroadPhase::new(),
             narrow_phase: NarrowPhase::new(),
@@ -102,6 +159,11 @@ impl Simulation {
             timing: Default::default(),
             rng: crate::rng::new_rng(seed),
             world_size: scenario.world_size(),
+            max_memory: scenario.max_memory(),
+            scores: Scores::new(),
+            codes: Arc::new(codes.to_vec()),
+            ship_classes: ShipClasses::new(),
+            replay_recorder: None,
         });
 
         for (team, code) in codes.iter().enumerate() {
@@ -142,10 +204,79 @@ impl Simulation {
         self.world_size
     }
 
+    /// Returns the gas available to the ship's AI each tick.
+    pub fn gas_budget(&self, handle: ShipHandle) -> u32 {
+        let class = self.ship(handle).data().class;
+        self.scenario
+            .as_ref()
+            .map(|scenario| scenario.gas_budget(class))
+            .unwrap_or(vm::DEFAULT_GAS_PER_TICK)
+    }
+
+    /// Returns the gas available to a team's AI outside of a ship's tick,
+    /// such as when it's initialized. This is the largest budget of the
+    /// team's ships.
+    pub fn team_gas_budget(&self, team: i32) -> u32 {
+        self.ships
+            .iter()
+            .filter(|&&handle| self.ship(handle).data().team == team)
+            .map(|&handle| self.gas_budget(handle))
+            .max()
+            .unwrap_or(vm::DEFAULT_GAS_PER_TICK)
+    }
+
+    pub fn is_loadout_allowed(&self, class: ShipClass, loadout: &str) -> bool {
+        self.scenario
+            .as_ref()
+            .map(|scenario| scenario.is_loadout_allowed(class, loadout))
+            .unwrap_or(false)
+    }
+
+    pub fn crash_policy(&self) -> vm::CrashPolicy {
+        self.scenario
+            .as_ref()
+            .map(|scenario| scenario.crash_policy())
+            .unwrap_or_default()
+    }
+
+    pub fn radio_interference(&self) -> bool {
+        self.scenario
+            .as_ref()
+            .map_or(false, |scenario| scenario.radio_interference())
+    }
+
     pub fn status(&self) -> scenario::Status {
         self.scenario.as_ref().unwrap().status(self)
     }
 
+    pub fn scores(&self) -> &Scores {
+        &self.scores
+    }
+
+    pub fn score(&self, team: i32, name: &str) -> f64 {
+        self.scores
+            .get(&team)
+            .and_then(|counters| counters.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_score(&mut self, team: i32, name: &str, value: f64) {
+        self.scores
+            .entry(team)
+            .or_default()
+            .insert(name.to_string(), value);
+    }
+
+    pub fn add_score(&mut self, team: i32, name: &str, delta: f64) {
+        *self
+            .scores
+            .entry(team)
+            .or_default()
+            .entry(name.to_string())
+            .or_default() += delta;
+    }
+
     pub fn ship(self: &Simulation, handle: ShipHandle) -> ShipAccessor {
         ShipAccessor {
             simulation: self,
@@ -189,7 +320,7 @@ impl Simulation {
             &mut self.impulse_joints,
             &mut self.multibody_joints,
             &mut self.ccd_solver,
-            None,
+            Some(&mut self.query_pipeline),
             &physics_hooks,
             &self.event_collector,
         );
@@ -227,6 +358,7 @@ impl Simulation {
             debug::emit_ship(self, handle);
             self.ship_mut(handle).tick();
         }
+        laser::tick(self);
         self.timing.ship += ship_timer.elapsed();
 
         let bullet_timer = Timer::new();
@@ -240,10 +372,149 @@ impl Simulation {
         self.timing.scenario += scenario_timer.elapsed();
 
         self.tick += 1;
+
+        if self.replay_recorder.is_some() {
+            let snapshot = self.snapshot(0);
+            self.replay_recorder.as_mut().unwrap().record(snapshot);
+        }
+    }
+
+    /// Starts recording a replay, beginning with the current state.
+    pub fn start_recording(&mut self) {
+        let mut recorder = ReplayRecorder::new(
+            &self.scenario.as_ref().unwrap().name(),
+            self.seed,
+            self.tick,
+            self.code_hashes(),
+            self.ship_classes.definitions(),
+        );
+        recorder.record(self.snapshot(0));
+        self.replay_recorder = Some(recorder);
+    }
+
+    pub fn finish_recording(&mut self) -> Option<Replay> {
+        self.replay_recorder
+            .take()
+            .map(|recorder| recorder.finish())
+    }
+
+    /// Hex SHA-256 of each team's code. Stable across processes and Rust
+    /// releases, so it can identify the code that produced a replay.
+    pub fn code_hashes(&self) -> Vec<String> {
+        self.codes.iter().map(hash_code).collect()
+    }
+
+    /// Saves the current state. Must be called between steps.
+    pub fn checkpoint(&self) -> Result<Checkpoint, vm::Error> {
+        let mut team_controllers = HashMap::new();
+        for (team, team_ctrl) in self.team_controllers.iter() {
+            team_controllers.insert(*team, team_ctrl.borrow().checkpoint()?);
+        }
+
+        Ok(Checkpoint {
+            scenario: self.scenario.as_ref().unwrap().clone_box(),
+            ships: self.ships.clone(),
+            ship_data: self.ship_data.clone(),
+            team_controllers,
+            new_ships: self.new_ships.clone(),
+            bullets: self.bullets.clone(),
+            bullet_data: self.bullet_data.clone(),
+            bodies: self.bodies.clone(),
+            impulse_joints: self.impulse_joints.clone(),
+            multibody_joints: self.multibody_joints.clone(),
+            colliders: self.colliders.clone(),
+            integration_parameters: self.integration_parameters,
+            query_pipeline: self.query_pipeline.clone(),
+            island_manager: self.island_manager.clone(),
+            broad_phase: self.broad_phase.clone(),
+            narrow_phase: self.narrow_phase.clone(),
+            ccd_solver: self.ccd_solver.clone(),
+            tick: self.tick,
+            cheats: self.cheats,
+            seed: self.seed,
+            rng: self.rng.clone(),
+            world_size: self.world_size,
+            max_memory: self.max_memory,
+            scores: self.scores.clone(),
+            codes: self.codes.clone(),
+            ship_classes: self.ship_classes.clone(),
+        })
+    }
+
+    /// Creates a simulation from a checkpoint. Stepping it produces the same
+    /// results as stepping the original simulation would have.
+    pub fn restore(checkpoint: &Checkpoint) -> Result<Box<Simulation>, vm::Error> {
+        let mut team_controllers = HashMap::new();
+        for (team, team_ctrl) in checkpoint.team_controllers.iter() {
+            team_controllers.insert(
+                *team,
+                Rc::new(RefCell::new(TeamController::restore(team_ctrl)?)),
+            );
+        }
+
+        let (contact_send, contact_recv) = crossbeam::channel::unbounded();
+        Ok(Box::new(Simulation {
+            scenario: Some(checkpoint.scenario.clone_box()),
+            ships: checkpoint.ships.clone(),
+            ship_data: checkpoint.ship_data.clone(),
+            team_controllers,
+            new_ships: checkpoint.new_ships.clone(),
+            bullets: checkpoint.bullets.clone(),
+            bullet_data: checkpoint.bullet_data.clone(),
+            bodies: checkpoint.bodies.clone(),
+            impulse_joints: checkpoint.impulse_joints.clone(),
+            multibody_joints: checkpoint.multibody_joints.clone(),
+            colliders: checkpoint.colliders.clone(),
+            integration_parameters: checkpoint.integration_parameters,
+            physics_pipeline: PhysicsPipeline::new(),
+            query_pipeline: checkpoint.query_pipeline.clone(),
+            island_manager: checkpoint.island_manager.clone(),
+            broad_phase: checkpoint.broad_phase.clone(),
+            narrow_phase: checkpoint.narrow_phase.clone(),
+            ccd_solver: checkpoint.ccd_solver.clone(),
+            event_collector: CollisionEventHandler::new(contact_send),
+            contact_recv,
+            events: SimEvents::new(),
+            tick: checkpoint.tick,
+            cheats: checkpoint.cheats,
+            seed: checkpoint.seed,
+            timing: Default::default(),
+            rng: checkpoint.rng.clone(),
+            world_size: checkpoint.world_size,
+            max_memory: checkpoint.max_memory,
+            scores: checkpoint.scores.clone(),
+            codes: checkpoint.codes.clone(),
+            ship_classes: checkpoint.ship_classes.clone(),
+            replay_recorder: None,
+        }))
+    }
+
+    /// Replaces a team's AI. Its existing ships start over with fresh state
+    /// on the next tick.
+    pub fn replace_code(&mut self, team: i32, code: &Code) {
+        self.team_controllers.remove(&team);
+        if let Some(slot) = Arc::make_mut(&mut self.codes).get_mut(team as usize) {
+            *slot = code.clone();
+        }
+        if matches!(code, Code::None) {
+            return;
+        }
+        self.upload_code(team, code);
+        let handles: Vec<ShipHandle> = self
+            .ships
+            .iter()
+            .cloned()
+            .filter(|&handle| {
+                self.ship(handle).data().team == team && !self.new_ships.contains(&(team, handle))
+            })
+            .collect();
+        self.new_ships
+            .extend(handles.into_iter().map(|handle| (team, handle)));
+        self.update_environment(team, BTreeMap::new());
     }
 
     pub fn upload_code(&mut self, team: i32, code: &Code) {
-        match vm::new_team_controller(code) {
+        match vm::new_team_controller(code, self.max_memory, self.team_gas_budget(team)) {
             Ok(team_ctrl) => {
                 self.team_controllers
                     .insert(team, Rc::new(RefCell::new(team_ctrl)));
@@ -255,6 +526,17 @@ impl Simulation {
         }
     }
 
+    /// Custom ship classes registered by the scenario. [`Self::snapshot`]
+    /// leaves out their definitions, which only need to be sent with the
+    /// first snapshot of a stream.
+    pub fn ship_classes(&self) -> &ShipClasses {
+        &self.ship_classes
+    }
+
+    pub fn ship_classes_mut(&mut self) -> &mut ShipClasses {
+        &mut self.ship_classes
+    }
+
     pub fn events(&self) -> &SimEvents {
         &self.events
     }
@@ -332,10 +614,14 @@ impl Simulation {
             debug_text: self.events.debug_text.clone(),
             drawn_text: self.events.drawn_text.clone(),
             particles: self.events.particles.clone(),
+            beams: self.events.beams.clone(),
             errors: self.events.errors.clone(),
             cheats: self.cheats,
             timing: self.timing.clone(),
             world_size: self.world_size,
+            scores: self.scores.clone(),
+            gas_usage: BTreeMap::new(),
+            ship_classes: vec![],
         };
 
         for &handle in self.ships.iter() {
@@ -360,6 +646,9 @@ impl Simulation {
                 fuel,
                 active_abilities: ship.active_abilities(),
             });
+            if let Some(gas_usage) = ship.data().gas_usage.as_ref() {
+                snapshot.gas_usage.insert(id, gas_usage.clone());
+            }
         }
 
         for &handle in self.bullets.iter() {
@@ -395,6 +684,35 @@ impl Simulation {
     }
 }
 
+fn hash_code(code: &Code) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    let mut update = |kind: &str, data: &[u8]| {
+        hasher.update(kind);
+        hasher.update([0]);
+        hasher.update(data);
+    };
+    match code {
+        Code::None => update("none", &[]),
+        Code::Rust(src) => update("rust", src.as_bytes()),
+        Code::Wasm(wasm) => update("wasm", wasm),
+        // Hash the wasm so that rebuilding the builtin AIs changes the hash.
+        Code::Builtin(name) => match vm::builtin::load_compiled(name) {
+            Ok(Code::Wasm(wasm)) => update("wasm", &wasm),
+            _ => update("builtin", name.as_bytes()),
+        },
+        #[cfg(feature = "precompile")]
+        Code::Precompiled(bytes) => update("precompiled", bytes),
+        #[cfg(feature = "native")]
+        Code::Native(native) => update("native", native.name().as_bytes()),
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
 pub struct CollisionEventHandler {
     collision_event_sender: Sender<CollisionEvent>,
 }
@@ -438,9 +756,22 @@ pub struct Particle {
     pub lifetime: f32,
 }
 
+/// A ship's AI crashed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CrashEvent {
+    pub ship: u64,
+    pub team: i32,
+    pub tick: u32,
+    pub error: vm::Error,
+    /// Wasm functions on the stack at the time of the crash, innermost first.
+    pub backtrace: Vec<String>,
+}
+
 pub struct SimEvents {
     pub errors: Vec<vm::Error>,
+    pub crashes: Vec<CrashEvent>,
     pub particles: Vec<Particle>,
+    pub beams: Vec<Line>,
     pub debug_lines: Vec<(u64, Vec<Line>)>,
     pub debug_text: BTreeMap<u64, String>,
     pub drawn_text: BTreeMap<Option<u64>, Vec<Text>>,
@@ -450,7 +781,9 @@ impl SimEvents {
     pub fn new() -> Self {
         Self {
             errors: vec![],
+            crashes: vec![],
             particles: vec![],
+            beams: vec![],
             debug_lines: Vec::new(),
             debug_text: BTreeMap::new(),
             drawn_text: BTreeMap::new(),
@@ -459,7 +792,9 @@ impl SimEvents {
 
     pub fn clear(&mut self) {
         self.errors.clear();
+        self.crashes.clear();
         self.particles.clear();
+        self.beams.clear();
         self.debug_lines.clear();
         self.debug_text.clear();
         self.drawn_text.clear();
@@ -1,11 +1,15 @@
-use crate::scenario::Status;
+use crate::scenario::{Scores, Status};
 use crate::ship::ShipClass;
+use crate::ship_definition;
 use crate::simulation::{Line, Particle};
 use crate::vm;
 use nalgebra::{Point2, Vector2};
 use oort_api::{Ability, Text};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+
+pub const REPLAY_KEYFRAME_INTERVAL: usize = 60;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Snapshot {
@@ -17,6 +21,8 @@ pub struct Snapshot {
     pub bullets: Vec<BulletSnapshot>,
     pub scenario_lines: Vec<Line>,
     pub particles: Vec<Particle>,
+    /// Laser beams fired this tick.
+    pub beams: Vec<Line>,
     pub errors: Vec<vm::Error>,
     pub cheats: bool,
     pub debug_lines: Vec<(u64, Vec<Line>)>,
@@ -24,9 +30,16 @@ pub struct Snapshot {
     pub drawn_text: BTreeMap<Option<u64>, Vec<Text>>,
     pub timing: Timing,
     pub world_size: f64,
+    pub scores: Scores,
+    /// Indexed by ship ID. Only includes ships controlled by an AI.
+    pub gas_usage: BTreeMap<u64, vm::GasUsage>,
+    /// Definitions of the simulation's custom classes. Only set on the first
+    /// snapshot of a stream. Pass them to `ShipClasses::load` before looking
+    /// up those classes in another process.
+    pub ship_classes: Vec<ship_definition::Registered>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ShipSnapshot {
     pub id: u64,
     pub position: Point2<f64>,
@@ -126,3 +139,200 @@ pub fn interpolate(snapshot: &mut Snapshot, dt: f64) {
         bullet.position += bullet.velocity * dt;
     }
 }
+
+/// A recorded match that can be played back without running the AIs.
+///
+/// Frame N holds the state N ticks after `start_tick`, the tick recording
+/// started on. Every `REPLAY_KEYFRAME_INTERVAL` frames a full snapshot is
+/// stored, and the frames in between only record what changed since the
+/// previous frame. Debug output, timing, and gas usage are not recorded.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub scenario_name: String,
+    pub seed: u32,
+    pub start_tick: u32,
+    /// SHA-256 of each team's code, as returned by `Simulation::code_hashes`.
+    pub code_hashes: Vec<String>,
+    /// Definitions of the simulation's custom classes, which the frames
+    /// don't repeat.
+    pub ship_classes: Vec<ship_definition::Registered>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ReplayFrame {
+    Keyframe(Box<Snapshot>),
+    Delta(SnapshotDelta),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotDelta {
+    pub time: f64,
+    pub score_time: f64,
+    pub status: Status,
+    pub ships: Vec<ShipSnapshot>,
+    pub removed_ships: Vec<u64>,
+    pub bullets: Vec<BulletSnapshot>,
+    pub scenario_lines: Option<Vec<Line>>,
+    pub particles: Vec<Particle>,
+    pub beams: Vec<Line>,
+    pub errors: Vec<vm::Error>,
+    pub scores: Option<Scores>,
+}
+
+impl SnapshotDelta {
+    pub fn new(prev: &Snapshot, next: &Snapshot) -> Self {
+        let prev_ships: HashMap<u64, &ShipSnapshot> =
+            prev.ships.iter().map(|ship| (ship.id, ship)).collect();
+        let next_ids: HashSet<u64> = next.ships.iter().map(|ship| ship.id).collect();
+        Self {
+            time: next.time,
+            score_time: next.score_time,
+            status: next.status,
+            ships: next
+                .ships
+                .iter()
+                .filter(|ship| prev_ships.get(&ship.id) != Some(ship))
+                .cloned()
+                .collect(),
+            removed_ships: prev
+                .ships
+                .iter()
+                .map(|ship| ship.id)
+                .filter(|id| !next_ids.contains(id))
+                .collect(),
+            bullets: next.bullets.clone(),
+            scenario_lines: if prev.scenario_lines != next.scenario_lines {
+                Some(next.scenario_lines.clone())
+            } else {
+                None
+            },
+            particles: next.particles.clone(),
+            beams: next.beams.clone(),
+            errors: next.errors.clone(),
+            scores: if prev.scores != next.scores {
+                Some(next.scores.clone())
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn apply(&self, snapshot: &mut Snapshot) {
+        snapshot.time = self.time;
+        snapshot.score_time = self.score_time;
+        snapshot.status = self.status;
+        snapshot
+            .ships
+            .retain(|ship| !self.removed_ships.contains(&ship.id));
+        for ship in self.ships.iter() {
+            match snapshot.ships.iter_mut().find(|x| x.id == ship.id) {
+                Some(existing) => *existing = ship.clone(),
+                None => snapshot.ships.push(ship.clone()),
+            }
+        }
+        snapshot.bullets = self.bullets.clone();
+        if let Some(lines) = self.scenario_lines.as_ref() {
+            snapshot.scenario_lines = lines.clone();
+        }
+        snapshot.particles = self.particles.clone();
+        snapshot.beams = self.beams.clone();
+        snapshot.errors = self.errors.clone();
+        if let Some(scores) = self.scores.as_ref() {
+            snapshot.scores = scores.clone();
+        }
+    }
+}
+
+impl Replay {
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Rebuilds the snapshot at the given simulation tick.
+    pub fn snapshot(&self, tick: u32) -> Option<Snapshot> {
+        let index = tick.checked_sub(self.start_tick)? as usize;
+        if index >= self.frames.len() {
+            return None;
+        }
+        let keyframe_index = (0..=index)
+            .rev()
+            .find(|&i| matches!(self.frames[i], ReplayFrame::Keyframe(_)))?;
+        let mut snapshot = match &self.frames[keyframe_index] {
+            ReplayFrame::Keyframe(snapshot) => (**snapshot).clone(),
+            ReplayFrame::Delta(_) => unreachable!(),
+        };
+        for frame in &self.frames[(keyframe_index + 1)..=index] {
+            if let ReplayFrame::Delta(delta) = frame {
+                delta.apply(&mut snapshot);
+            }
+        }
+        Some(snapshot)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let serialized = bincode::serialize(self).map_err(|e| e.to_string())?;
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new()).map_err(|e| e.to_string())?;
+        encoder.write_all(&serialized).map_err(|e| e.to_string())?;
+        encoder.finish().into_result().map_err(|e| e.to_string())
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Replay, String> {
+        let mut decoder = libflate::gzip::Decoder::new(data).map_err(|e| e.to_string())?;
+        let mut serialized = Vec::new();
+        decoder
+            .read_to_end(&mut serialized)
+            .map_err(|e| e.to_string())?;
+        bincode::deserialize(&serialized).map_err(|e| e.to_string())
+    }
+}
+
+pub struct ReplayRecorder {
+    replay: Replay,
+    last_snapshot: Option<Snapshot>,
+}
+
+impl ReplayRecorder {
+    pub fn new(
+        scenario_name: &str,
+        seed: u32,
+        start_tick: u32,
+        code_hashes: Vec<String>,
+        ship_classes: Vec<ship_definition::Registered>,
+    ) -> Self {
+        Self {
+            replay: Replay {
+                scenario_name: scenario_name.to_string(),
+                seed,
+                start_tick,
+                code_hashes,
+                ship_classes,
+                frames: vec![],
+            },
+            last_snapshot: None,
+        }
+    }
+
+    pub fn record(&mut self, mut snapshot: Snapshot) {
+        snapshot.nonce = 0;
+        snapshot.debug_lines.clear();
+        snapshot.debug_text.clear();
+        snapshot.drawn_text.clear();
+        snapshot.timing = Default::default();
+        snapshot.gas_usage.clear();
+        snapshot.ship_classes.clear();
+
+        let frame = match self.last_snapshot.as_ref() {
+            Some(prev) if self.replay.frames.len() % REPLAY_KEYFRAME_INTERVAL != 0 => {
+                ReplayFrame::Delta(SnapshotDelta::new(prev, &snapshot))
+            }
+            _ => ReplayFrame::Keyframe(Box::new(snapshot.clone())),
+        };
+        self.replay.frames.push(frame);
+        self.last_snapshot = Some(snapshot);
+    }
+
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
@@ -1,30 +1,182 @@
 // Based on https://github.com/scrtlabs/SecretNetwork/blob/621d3899babc4741ef1ba596152c097677d246db/cosmwasm/enclaves/shared/contract-engine/src/wasm3/gas.rs
-use walrus::{ir::*, FunctionBuilder, GlobalId, InitExpr, LocalFunction, ValType};
+use std::collections::HashMap;
+use walrus::{
+    ir::*, FunctionBuilder, FunctionId, FunctionKind, GlobalId, ImportKind, InitExpr,
+    LocalFunction, MemoryId, ModuleTypes, ValType,
+};
 
-pub fn rewrite(wasm: &[u8]) -> Result<Vec<u8>, super::Error> {
+/// Prefix for the exports added for each mutable global, used to checkpoint the VM.
+pub const CHECKPOINT_GLOBAL_PREFIX: &str = "oort_checkpoint_global_";
+
+/// Maximum number of nested calls. Enforced here rather than left to the
+/// engine so that stack overflows happen at the same depth on every backend.
+/// Deep enough for the recursion AIs did before the limit existed, and
+/// shallow enough that the engines' native stacks don't overflow first.
+pub const MAX_CALL_DEPTH: i32 = 10000;
+
+/// Returned by the injected `get_trap_reason()` function after a trap.
+pub const TRAP_REASON_NONE: i32 = 0;
+pub const TRAP_REASON_OUT_OF_MEMORY: i32 = 1;
+pub const TRAP_REASON_STACK_OVERFLOW: i32 = 2;
+pub const TRAP_REASON_OUT_OF_GAS: i32 = 3;
+
+/// Function import that AIs call to read the gas left in the current tick.
+/// Calls to it are replaced with a read of the gas counter.
+pub const GAS_REMAINING_IMPORT: (&str, &str) = ("oort", "gas_remaining");
+
+/// Exported mutable global holding the number of pages memory can grow to.
+/// Set when the module is instantiated so that precompiled modules follow
+/// the scenario's limit rather than the one they were compiled with.
+pub const MEMORY_LIMIT_EXPORT: &str = "oort_memory_limit";
+
+struct Globals {
+    gas: GlobalId,
+    call_depth: GlobalId,
+    trap_reason: GlobalId,
+}
+
+pub fn rewrite(wasm: &[u8], max_memory_pages: u32) -> Result<Vec<u8>, super::Error> {
     let mut module = match walrus::Module::from_buffer(wasm) {
         Ok(m) => m,
-        Err(e) => {
-            return Err(super::Error {
-                msg: format!("{e:?}"),
-            })
-        }
+        Err(e) => return Err(super::Error::Internal(format!("{e:?}"))),
     };
 
-    let gas_global = module
+    // Export mutable globals (e.g. the stack pointer) so they can be checkpointed.
+    let mutable_globals: Vec<GlobalId> = module
         .globals
-        .add_local(ValType::I32, true, InitExpr::Value(Value::I32(0)));
+        .iter()
+        .filter(|global| global.mutable)
+        .map(|global| global.id())
+        .collect();
+    for (i, global) in mutable_globals.into_iter().enumerate() {
+        module
+            .exports
+            .add(&format!("{CHECKPOINT_GLOBAL_PREFIX}{i}"), global);
+    }
 
-    // Rewrite each block to check and decrement gas.
-    for (_, func) in module.funcs.iter_local_mut() {
-        rewrite_function(func, gas_global);
+    // Cap the size of linear memory. Growing past the cap fails, which the
+    // allocator turns into a trap. The memory limit global can lower the cap
+    // further at runtime.
+    for memory in module.memories.iter_mut() {
+        if memory.initial > max_memory_pages {
+            return Err(super::Error::OutOfMemory);
+        }
+        memory.maximum = Some(
+            memory
+                .maximum
+                .map_or(max_memory_pages, |max| max.min(max_memory_pages)),
+        );
     }
 
-    // Create a reset_gas(amount) function.
+    let mut add_global = || {
+        module
+            .globals
+            .add_local(ValType::I32, true, InitExpr::Value(Value::I32(0)))
+    };
+    let globals = Globals {
+        gas: add_global(),
+        call_depth: add_global(),
+        trap_reason: add_global(),
+    };
+
+    // Functions from the original module, as opposed to the ones added below.
+    let func_ids: Vec<FunctionId> = module.funcs.iter_local().map(|(id, _)| id).collect();
+
+    let gas_remaining_import = module
+        .imports
+        .iter()
+        .find(|import| (import.module.as_str(), import.name.as_str()) == GAS_REMAINING_IMPORT)
+        .and_then(|import| match import.kind {
+            ImportKind::Function(func) => Some((import.id(), func)),
+            _ => None,
+        });
+
+    // Create a wrapper around memory.grow for each memory that fails when
+    // growing past the memory limit and records failures, so that the
+    // resulting trap can be reported as out of memory.
+    let memory_ids: Vec<MemoryId> = module.memories.iter().map(|memory| memory.id()).collect();
+    let mut grow_funcs = HashMap::new();
+    if !memory_ids.is_empty() {
+        let memory_limit = module.globals.add_local(
+            ValType::I32,
+            true,
+            InitExpr::Value(Value::I32(max_memory_pages as i32)),
+        );
+        module.exports.add(MEMORY_LIMIT_EXPORT, memory_limit);
+        for memory in memory_ids {
+            let mut func =
+                FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+            let pages = module.locals.add(ValType::I32);
+            let result = module.locals.add(ValType::I32);
+            func.func_body()
+                // if size + pages > limit { -1 } else { memory.grow(pages) }
+                .local_get(pages)
+                .unop(UnaryOp::I64ExtendUI32)
+                .memory_size(memory)
+                .unop(UnaryOp::I64ExtendUI32)
+                .binop(BinaryOp::I64Add)
+                .global_get(memory_limit)
+                .unop(UnaryOp::I64ExtendUI32)
+                .binop(BinaryOp::I64GtU)
+                .if_else(
+                    Some(ValType::I32),
+                    |then| {
+                        then.i32_const(-1);
+                    },
+                    |else_| {
+                        else_.local_get(pages).memory_grow(memory);
+                    },
+                )
+                .local_tee(result)
+                .i32_const(-1)
+                .binop(BinaryOp::I32Eq)
+                .if_else(
+                    None,
+                    |then| {
+                        then.i32_const(TRAP_REASON_OUT_OF_MEMORY)
+                            .global_set(globals.trap_reason);
+                    },
+                    |_else| {},
+                )
+                .local_get(result);
+            grow_funcs.insert(memory, func.finish(vec![pages], &mut module.funcs));
+        }
+    }
+
+    for id in func_ids {
+        let results = module
+            .types
+            .get(module.funcs.get(id).ty())
+            .results()
+            .to_vec();
+        if let FunctionKind::Local(func) = &mut module.funcs.get_mut(id).kind {
+            // Rewrite each block to check and decrement gas.
+            rewrite_function(func, &globals);
+            replace_memory_grow(func, &grow_funcs);
+            if let Some((_, import_func)) = gas_remaining_import {
+                replace_gas_remaining(func, import_func, &globals);
+            }
+            limit_call_depth(func, &mut module.types, &results, &globals);
+        }
+    }
+
+    if let Some((import, func)) = gas_remaining_import {
+        module.imports.delete(import);
+        module.funcs.delete(func);
+    }
+
+    // Create a reset_gas(amount) function. It also clears the state left
+    // behind by a trap.
     {
         let mut func = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[]);
         let amount = module.locals.add(ValType::I32);
-        func.func_body().local_get(amount).global_set(gas_global);
+        func.func_body()
+            .local_get(amount)
+            .global_set(globals.gas)
+            .i32_const(0)
+            .global_set(globals.call_depth)
+            .i32_const(TRAP_REASON_NONE)
+            .global_set(globals.trap_reason);
         let reset_gas = func.finish(vec![amount], &mut module.funcs);
         module.exports.add("reset_gas", reset_gas);
     }
@@ -32,25 +184,34 @@ pub fn rewrite(wasm: &[u8]) -> Result<Vec<u8>, super::Error> {
     // Create a get_gas() function.
     {
         let mut func = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
-        func.func_body().global_get(gas_global);
+        func.func_body().global_get(globals.gas);
         let get_gas = func.finish(vec![], &mut module.funcs);
         module.exports.add("get_gas", get_gas);
     }
 
+    // Create a get_trap_reason() function.
+    {
+        let mut func = FunctionBuilder::new(&mut module.types, &[], &[ValType::I32]);
+        func.func_body().global_get(globals.trap_reason);
+        let get_trap_reason = func.finish(vec![], &mut module.funcs);
+        module.exports.add("get_trap_reason", get_trap_reason);
+    }
+
     Ok(module.emit_wasm())
 }
 
-fn rewrite_function(func: &mut LocalFunction, gas_global: GlobalId) {
+fn rewrite_function(func: &mut LocalFunction, globals: &Globals) {
     let block_ids: Vec<_> = func.blocks().map(|(block_id, _block)| block_id).collect();
     for block_id in block_ids {
-        rewrite_block(func, block_id, gas_global);
+        rewrite_block(func, block_id, globals);
     }
 }
 
 /// Number of injected metering instructions (needed to calculate final instruction size).
 const METERING_INSTRUCTION_COUNT: usize = 8;
 
-fn rewrite_block(func: &mut LocalFunction, block_id: InstrSeqId, gas_global: GlobalId) {
+fn rewrite_block(func: &mut LocalFunction, block_id: InstrSeqId, globals: &Globals) {
+    let gas_global = globals.gas;
     let block = func.block_mut(block_id);
     let block_instrs = &mut block.instrs;
     let block_len = block_instrs.len();
@@ -66,7 +227,9 @@ fn rewrite_block(func: &mut LocalFunction, block_id: InstrSeqId, gas_global: Glo
         .if_else(
             None,
             |then| {
-                then.unreachable();
+                then.i32_const(TRAP_REASON_OUT_OF_GAS)
+                    .global_set(globals.trap_reason)
+                    .unreachable();
             },
             |_else| {},
         )
@@ -84,6 +247,94 @@ fn rewrite_block(func: &mut LocalFunction, block_id: InstrSeqId, gas_global: Glo
     block.instrs = new_instrs;
 }
 
+fn replace_memory_grow(func: &mut LocalFunction, grow_funcs: &HashMap<MemoryId, FunctionId>) {
+    let block_ids: Vec<_> = func.blocks().map(|(block_id, _block)| block_id).collect();
+    for block_id in block_ids {
+        for (instr, _) in func.block_mut(block_id).instrs.iter_mut() {
+            if let Instr::MemoryGrow(MemoryGrow { memory }) = instr {
+                *instr = Instr::Call(Call {
+                    func: grow_funcs[memory],
+                });
+            }
+        }
+    }
+}
+
+fn replace_gas_remaining(func: &mut LocalFunction, import_func: FunctionId, globals: &Globals) {
+    let block_ids: Vec<_> = func.blocks().map(|(block_id, _block)| block_id).collect();
+    for block_id in block_ids {
+        for (instr, _) in func.block_mut(block_id).instrs.iter_mut() {
+            if matches!(instr, Instr::Call(Call { func }) if *func == import_func) {
+                *instr = Instr::GlobalGet(GlobalGet {
+                    global: globals.gas,
+                });
+            }
+        }
+    }
+}
+
+/// Moves the function body into a block surrounded by code that maintains
+/// the call depth. Returns become branches out of that block so that the
+/// depth is always decremented.
+fn limit_call_depth(
+    func: &mut LocalFunction,
+    types: &mut ModuleTypes,
+    results: &[ValType],
+    globals: &Globals,
+) {
+    let entry = func.entry_block();
+    let ty = InstrSeqType::new(types, &[], results);
+    let body = func.builder_mut().dangling_instr_seq(ty).id();
+
+    let block_ids: Vec<_> = func.blocks().map(|(block_id, _block)| block_id).collect();
+    let retarget = |block: &mut InstrSeqId| {
+        if *block == entry {
+            *block = body;
+        }
+    };
+    for block_id in block_ids {
+        for (instr, _) in func.block_mut(block_id).instrs.iter_mut() {
+            match instr {
+                Instr::Return(_) => *instr = Instr::Br(Br { block: body }),
+                Instr::Br(Br { block }) | Instr::BrIf(BrIf { block }) => retarget(block),
+                Instr::BrTable(BrTable { blocks, default }) => {
+                    blocks.iter_mut().for_each(retarget);
+                    retarget(default);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let instrs = std::mem::take(&mut func.block_mut(entry).instrs);
+    func.block_mut(body).instrs = instrs;
+
+    func.builder_mut()
+        .func_body()
+        // if globals[call_depth] >= MAX_CALL_DEPTH { throw(); }
+        .global_get(globals.call_depth)
+        .i32_const(MAX_CALL_DEPTH)
+        .binop(BinaryOp::I32GeS)
+        .if_else(
+            None,
+            |then| {
+                then.i32_const(TRAP_REASON_STACK_OVERFLOW)
+                    .global_set(globals.trap_reason)
+                    .unreachable();
+            },
+            |_else| {},
+        )
+        .global_get(globals.call_depth)
+        .i32_const(1)
+        .binop(BinaryOp::I32Add)
+        .global_set(globals.call_depth)
+        .instr(Block { seq: body })
+        .global_get(globals.call_depth)
+        .i32_const(1)
+        .binop(BinaryOp::I32Sub)
+        .global_set(globals.call_depth);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,7 +383,7 @@ mod tests {
 )
 ",
         );
-        let new_wasm = rewrite(&wasm).unwrap();
+        let new_wasm = rewrite(&wasm, 16).unwrap();
         check_wat(
             &new_wasm,
             "
@@ -140,39 +391,94 @@ mod tests {
   (type (;0;) (func (result i32)))
   (type (;1;) (func (param i32)))
   (func (;0;) (type 0) (result i32)
-    global.get 0
-    i32.const 2
-    i32.lt_u
+    global.get 1
+    i32.const 10000
+    i32.ge_s
     if  ;; label = @1
+      i32.const 2
+      global.set 2
       unreachable
     end
-    global.get 0
-    i32.const 2
-    i32.sub
-    global.set 0
-    loop  ;; label = @1
+    global.get 1
+    i32.const 1
+    i32.add
+    global.set 1
+    block (result i32)  ;; label = @1
       global.get 0
-      i32.const 1
+      i32.const 2
       i32.lt_u
       if  ;; label = @2
+        i32.const 3
+        global.set 2
         unreachable
       end
       global.get 0
-      i32.const 1
+      i32.const 2
       i32.sub
       global.set 0
-      br 0 (;@1;)
+      loop  ;; label = @2
+        global.get 0
+        i32.const 1
+        i32.lt_u
+        if  ;; label = @3
+          i32.const 3
+          global.set 2
+          unreachable
+        end
+        global.get 0
+        i32.const 1
+        i32.sub
+        global.set 0
+        br 0 (;@2;)
+      end
+      i32.const 1
     end
-    i32.const 1)
+    global.get 1
+    i32.const 1
+    i32.sub
+    global.set 1)
   (func (;1;) (type 1) (param i32)
     local.get 0
-    global.set 0)
+    global.set 0
+    i32.const 0
+    global.set 1
+    i32.const 0
+    global.set 2)
   (func (;2;) (type 0) (result i32)
     global.get 0)
+  (func (;3;) (type 0) (result i32)
+    global.get 2)
   (global (;0;) (mut i32) (i32.const 0))
+  (global (;1;) (mut i32) (i32.const 0))
+  (global (;2;) (mut i32) (i32.const 0))
   (export \"reset_gas\" (func 1))
-  (export \"get_gas\" (func 2)))
+  (export \"get_gas\" (func 2))
+  (export \"get_trap_reason\" (func 3)))
 ",
         );
     }
+
+    #[test]
+    fn test_memory_limit() {
+        let max_memory = |wat: &str| {
+            let wasm = rewrite(&wat2wasm(wat), 16).unwrap();
+            let module = walrus::Module::from_buffer(&wasm).unwrap();
+            let memory = module.memories.iter().next().unwrap();
+            memory.maximum
+        };
+        assert_eq!(max_memory("(module (memory 1))"), Some(16));
+        assert_eq!(max_memory("(module (memory 1 8))"), Some(8));
+        assert_eq!(max_memory("(module (memory 1 100))"), Some(16));
+        assert!(matches!(
+            rewrite(&wat2wasm("(module (memory 17))"), 16),
+            Err(crate::vm::Error::OutOfMemory)
+        ));
+
+        let wasm = rewrite(&wat2wasm("(module (memory 1))"), 16).unwrap();
+        let module = walrus::Module::from_buffer(&wasm).unwrap();
+        assert!(module
+            .exports
+            .iter()
+            .any(|export| export.name == MEMORY_LIMIT_EXPORT));
+    }
 }
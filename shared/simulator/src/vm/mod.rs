@@ -1,12 +1,15 @@
 pub mod builtin;
 mod limiter;
+#[cfg(feature = "native")]
+pub mod native;
 
 use crate::color;
 use crate::debug;
 use crate::rng::new_rng;
 use crate::ship::{ShipClass, ShipHandle};
-use crate::simulation::{Code, Particle, Simulation, PHYSICS_TICK_LENGTH};
+use crate::simulation::{Code, CrashEvent, Particle, Simulation, PHYSICS_TICK_LENGTH};
 use nalgebra::{point, vector, Rotation2};
+use oort_api::prelude::radio_internal;
 use oort_api::{ActiveAbilities, Class, EcmMode, Line, SystemState, Text};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -21,111 +24,340 @@ use wasmer::{imports, Instance, MemoryView, Module, Store, WasmPtr};
 pub type Vec2 = nalgebra::Vector2<f64>;
 pub type Environment = BTreeMap<String, String>;
 
-const GAS_PER_TICK: i32 = 1_000_000;
+pub const DEFAULT_GAS_PER_TICK: u32 = 1_000_000;
+pub const GAS_HISTOGRAM_BUCKETS: usize = 10;
+pub const DEFAULT_MAX_MEMORY: usize = 64 << 20;
+/// ABI version of AIs built before `oort_api::ABI_VERSION` existed. Their
+/// `SYSTEM_STATE` array only has room for 128 slots.
+const LEGACY_ABI_VERSION: u32 = 0;
+/// Oldest ABI version that can still be run. Old leaderboard submissions and
+/// builtin AIs depend on this, so when the ABI changes add the old layout to
+/// `system_state_size` rather than raising it.
+const MIN_ABI_VERSION: u32 = LEGACY_ABI_VERSION;
 const MAX_DEBUG_LINES: u32 = 1024;
 const MAX_DRAWN_TEXT: u32 = 128;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Error {
-    pub msg: String,
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The AI used more than its gas budget for the tick.
+    OutOfGas,
+    /// The AI needed more linear memory than the limit allows.
+    OutOfMemory,
+    /// The AI exceeded the maximum call depth.
+    StackOverflow,
+    DivisionByZero,
+    /// An `unreachable` instruction was executed without a panic message.
+    Unreachable,
+    /// The AI panicked with the given message.
+    Panic(String),
+    /// Any other trap, such as an out-of-bounds memory access.
+    Trap(String),
+    /// The AI was built for an ABI version this simulator can't run.
+    IncompatibleAbi(u32),
+    /// Failed to load the AI or to exchange data with it.
+    Internal(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::OutOfGas => write!(f, "Ship exceeded maximum number of instructions"),
+            Error::OutOfMemory => write!(f, "Ship exceeded maximum memory"),
+            Error::StackOverflow => write!(f, "Ship exceeded maximum call depth"),
+            Error::DivisionByZero => write!(f, "Ship divided an integer by zero"),
+            Error::Unreachable => write!(f, "Ship executed unreachable code"),
+            Error::Panic(msg) => write!(f, "{msg}"),
+            Error::Trap(msg) => write!(f, "Ship runtime error: {msg}"),
+            Error::IncompatibleAbi(version) => write!(
+                f,
+                "Ship uses ABI version {version}, supported versions are {MIN_ABI_VERSION} to {}",
+                oort_api::ABI_VERSION
+            ),
+            Error::Internal(msg) => write!(f, "{msg}"),
+        }
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
 impl From<wasm_bindgen::JsValue> for Error {
     fn from(err: wasm_bindgen::JsValue) -> Self {
+        Error::Internal(format!("JS error: {:?}", err))
+    }
+}
+
+/// Gas used by a ship's AI since it was created.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GasUsage {
+    pub budget: u32,
+    pub last: u32,
+    /// Number of ticks that used 0-10%, 10-20%, ..., 90-100% of the budget.
+    /// Ticks that ran out of gas are counted in the last bucket.
+    pub histogram: [u32; GAS_HISTOGRAM_BUCKETS],
+}
+
+impl GasUsage {
+    pub fn record(&mut self, budget: u32, used: u32) {
+        let used = used.min(budget);
+        let bucket = if budget == 0 {
+            GAS_HISTOGRAM_BUCKETS - 1
+        } else {
+            (used as u64 * GAS_HISTOGRAM_BUCKETS as u64 / budget as u64) as usize
+        };
+        self.budget = budget;
+        self.last = used;
+        self.histogram[bucket.min(GAS_HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+}
+
+/// What happens to a ship after its AI crashes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CrashPolicy {
+    /// The ship's AI stays disabled for the rest of the match.
+    #[default]
+    StayCrashed,
+    /// The ship's AI is recreated after the given number of ticks. Other
+    /// ships on the team are unaffected.
+    RestartShip { delay_ticks: u32 },
+    /// The team's VM is recreated after the given number of ticks, which
+    /// restarts the AI of every ship on the team.
+    ResetTeam { delay_ticks: u32 },
+}
+
+/// An error from a ship's tick, with the wasm call stack if available.
+struct Crash {
+    error: Error,
+    backtrace: Vec<String>,
+}
+
+impl From<Error> for Crash {
+    fn from(error: Error) -> Self {
         Self {
-            msg: format!("JS error: {:?}", err),
+            error,
+            backtrace: vec![],
         }
     }
 }
 
 impl From<wasmer::InstantiationError> for Error {
     fn from(err: wasmer::InstantiationError) -> Self {
-        Self {
-            msg: format!("Wasmer instantiation error: {err:?}"),
-        }
+        Error::Internal(format!("Wasmer instantiation error: {err:?}"))
     }
 }
-pub fn new_team_controller(code: &Code) -> Result<Box<TeamController>, Error> {
+pub fn new_team_controller(
+    code: &Code,
+    max_memory: usize,
+    gas_budget: u32,
+) -> Result<Box<TeamController>, Error> {
     match code {
-        Code::Wasm(_) => TeamController::create(code),
+        Code::Wasm(_) => TeamController::create(code, max_memory, gas_budget),
         #[cfg(feature = "precompile")]
-        Code::Precompiled(_) => TeamController::create(code),
+        Code::Precompiled(_) => TeamController::create(code, max_memory, gas_budget),
         Code::Builtin(name) => match builtin::load_compiled(name) {
-            Ok(code) => new_team_controller(&code),
-            Err(e) => Err(Error { msg: e }),
+            Ok(code) => new_team_controller(&code, max_memory, gas_budget),
+            Err(e) => Err(Error::Internal(e)),
         },
+        #[cfg(feature = "native")]
+        Code::Native(_) => TeamController::create(code, max_memory, gas_budget),
         _ => unreachable!(),
     }
 }
 
 pub struct TeamController {
-    vm: WasmVm,
+    code: CompiledCode,
+    max_memory: usize,
+    // Gas for running the AI's initialization.
+    gas_budget: u32,
+    vm: Vm,
     states: HashMap<ShipHandle, LocalSystemState>,
     next_id: u32,
+    environment: Environment,
+    // Tick at which each crashed ship will be restarted.
+    restart_ticks: HashMap<ShipHandle, u32>,
+    // Tick at which the VM will be recreated.
+    reset_tick: Option<u32>,
+}
+
+/// Saved state of a TeamController, including the contents of its VM.
+#[derive(Clone)]
+pub struct TeamControllerCheckpoint {
+    code: CompiledCode,
+    max_memory: usize,
+    gas_budget: u32,
+    vm: WasmVmCheckpoint,
+    states: HashMap<ShipHandle, LocalSystemState>,
+    next_id: u32,
+    environment: Environment,
+    restart_ticks: HashMap<ShipHandle, u32>,
+    reset_tick: Option<u32>,
 }
 
 impl TeamController {
-    pub fn create(code: &Code) -> Result<Box<TeamController>, Error> {
+    pub fn create(
+        code: &Code,
+        max_memory: usize,
+        gas_budget: u32,
+    ) -> Result<Box<TeamController>, Error> {
+        let code = CompiledCode::new(code, max_memory)?;
         Ok(Box::new(TeamController {
-            vm: WasmVm::create(code)?,
+            vm: Vm::create(&code, max_memory, gas_budget)?,
+            code,
+            max_memory,
+            gas_budget,
             states: HashMap::new(),
             next_id: 1,
+            environment: Environment::new(),
+            restart_ticks: HashMap::new(),
+            reset_tick: None,
         }))
     }
 
-    pub fn add_ship(&mut self, handle: ShipHandle, sim: &Simulation) -> Result<(), Error> {
-        let mut state = LocalSystemState::new();
+    pub fn checkpoint(&self) -> Result<TeamControllerCheckpoint, Error> {
+        Ok(TeamControllerCheckpoint {
+            code: self.code.clone(),
+            max_memory: self.max_memory,
+            gas_budget: self.gas_budget,
+            vm: self.vm.checkpoint()?,
+            states: self.states.clone(),
+            next_id: self.next_id,
+            environment: self.environment.clone(),
+            restart_ticks: self.restart_ticks.clone(),
+            reset_tick: self.reset_tick,
+        })
+    }
 
-        state.set(
-            SystemState::Seed,
-            (make_seed(sim.seed(), handle) & 0xffffff) as f64,
-        );
-        state.set(SystemState::Id, self.next_id as f64);
-        self.next_id += 1;
-        if let Some(radar) = sim.ship(handle).data().radar.as_ref() {
-            state.set(SystemState::RadarHeading, radar.heading);
-            state.set(SystemState::RadarWidth, radar.width);
-            state.set(SystemState::RadarMinDistance, radar.min_distance);
-            state.set(SystemState::RadarMaxDistance, radar.max_distance);
-        }
+    /// Recreates the team controller from a checkpoint. The module compiled
+    /// when the team controller was created is reused.
+    pub fn restore(checkpoint: &TeamControllerCheckpoint) -> Result<Box<TeamController>, Error> {
+        let module = match &checkpoint.code {
+            CompiledCode::Wasm(module) => module,
+            #[cfg(feature = "native")]
+            CompiledCode::Native(_) => {
+                return Err(Error::Internal(
+                    "Native AIs can't be checkpointed".to_string(),
+                ))
+            }
+        };
+        let vm = WasmVm::instantiate(module, checkpoint.max_memory, checkpoint.gas_budget)?;
+        vm.restore(&checkpoint.vm)?;
+        Ok(Box::new(TeamController {
+            code: checkpoint.code.clone(),
+            max_memory: checkpoint.max_memory,
+            gas_budget: checkpoint.gas_budget,
+            vm: Vm::Wasm(vm),
+            states: checkpoint.states.clone(),
+            next_id: checkpoint.next_id,
+            environment: checkpoint.environment.clone(),
+            restart_ticks: checkpoint.restart_ticks.clone(),
+            reset_tick: checkpoint.reset_tick,
+        }))
+    }
 
+    pub fn add_ship(&mut self, handle: ShipHandle, sim: &Simulation) -> Result<(), Error> {
+        let state = initial_system_state(sim, handle, self.next_id);
+        self.next_id += 1;
         self.states.insert(handle, state);
 
         Ok(())
     }
 
-    pub fn remove_ship(&mut self, handle: ShipHandle) {
+    /// Removes the ship, giving its AI the ship's gas budget to clean up.
+    pub fn remove_ship(&mut self, handle: ShipHandle, gas_budget: u32) {
         self.states.remove(&handle);
-        let (index, _) = handle.0.into_raw_parts();
-        let index = index as i32;
-        self.vm
-            .reset_gas
-            .call(&mut self.vm.store_mut(), &[GAS_PER_TICK.into()])
-            .unwrap();
-        if let Err(e) = translate_runtime_error(
-            self.vm
-                .delete_ship
-                .call(self.vm.store_mut().deref_mut(), &[index.into()]),
-        ) {
-            log::warn!("Failed to delete ship: {:?}", e);
+        self.restart_ticks.remove(&handle);
+        self.delete_ship(handle, gas_budget);
+    }
+
+    fn delete_ship(&mut self, handle: ShipHandle, gas_budget: u32) {
+        match &mut self.vm {
+            Vm::Wasm(vm) => vm.delete_ship(handle, gas_budget),
+            #[cfg(feature = "native")]
+            Vm::Native(vm) => vm.delete_ship(handle),
         }
     }
 
     pub fn tick(&mut self, sim: &mut Simulation) {
+        self.restart_crashed_ships(sim);
+
         let mut handles: Vec<_> = self.states.keys().cloned().collect();
         handles.sort_by_key(|x| x.0);
 
         for handle in handles {
-            if let Err(e) = self.tick_ship(sim, handle) {
-                log::warn!("{}", e.msg);
-                sim.emit_debug_text(handle, format!("Crashed: {}", e.msg.clone()));
-                sim.ship_mut(handle).data_mut().crash_message = Some(e.msg);
+            if let Err(crash) = self.tick_ship(sim, handle) {
+                let msg = crash.error.to_string();
+                log::warn!("{}", msg);
+                sim.emit_debug_text(handle, format!("Crashed: {}", msg.clone()));
+                sim.events.crashes.push(CrashEvent {
+                    ship: handle.into(),
+                    team: sim.ship(handle).data().team,
+                    tick: sim.tick(),
+                    error: crash.error,
+                    backtrace: crash.backtrace,
+                });
+                sim.ship_mut(handle).data_mut().crash_message = Some(msg);
+                match sim.crash_policy() {
+                    CrashPolicy::StayCrashed => {}
+                    CrashPolicy::RestartShip { delay_ticks } => {
+                        self.restart_ticks
+                            .insert(handle, sim.tick().saturating_add(delay_ticks));
+                    }
+                    CrashPolicy::ResetTeam { delay_ticks } => {
+                        if self.reset_tick.is_none() {
+                            self.reset_tick = Some(sim.tick().saturating_add(delay_ticks));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn restart_crashed_ships(&mut self, sim: &mut Simulation) {
+        if self.reset_tick.map_or(false, |tick| tick <= sim.tick()) {
+            self.reset_tick = None;
+            if let Err(e) = self.reset(sim) {
+                log::warn!("Failed to reset team: {}", e);
             }
         }
+
+        let mut handles: Vec<_> = self
+            .restart_ticks
+            .iter()
+            .filter(|(_, tick)| **tick <= sim.tick())
+            .map(|(&handle, _)| handle)
+            .collect();
+        handles.sort_by_key(|x| x.0);
+        for handle in handles {
+            self.restart_ticks.remove(&handle);
+            self.restart_ship(sim, handle);
+        }
+    }
+
+    /// Replaces the ship's AI with a fresh instance, keeping its ID.
+    fn restart_ship(&mut self, sim: &mut Simulation, handle: ShipHandle) {
+        let Some(state) = self.states.get(&handle) else {
+            return;
+        };
+        let id = state.get(SystemState::Id) as u32;
+        self.delete_ship(handle, sim.gas_budget(handle));
+        self.states
+            .insert(handle, initial_system_state(sim, handle, id));
+        sim.ship_mut(handle).data_mut().crash_message = None;
+    }
+
+    /// Recreates the VM, restarting the AI of every ship on the team.
+    fn reset(&mut self, sim: &mut Simulation) -> Result<(), Error> {
+        self.vm = Vm::create(&self.code, self.max_memory, self.gas_budget)?;
+        self.vm.update_environment(&self.environment)?;
+        for (&handle, state) in self.states.iter_mut() {
+            let id = state.get(SystemState::Id) as u32;
+            *state = initial_system_state(sim, handle, id);
+            sim.ship_mut(handle).data_mut().crash_message = None;
+        }
+        self.restart_ticks.clear();
+        Ok(())
     }
 
-    fn tick_ship(&mut self, sim: &mut Simulation, handle: ShipHandle) -> Result<(), Error> {
+    fn tick_ship(&mut self, sim: &mut Simulation, handle: ShipHandle) -> Result<(), Crash> {
         if let Some(msg) = sim.ship(handle).data().crash_message.as_ref() {
             sim.emit_debug_text(handle, format!("Crashed: {}", msg.clone()));
             let mut rng = new_rng(sim.tick());
@@ -147,126 +379,123 @@ impl TeamController {
             return Ok(());
         }
 
-        let vm = &mut self.vm;
         let state = self.states.get_mut(&handle).unwrap();
+        match &mut self.vm {
+            Vm::Wasm(vm) => vm.tick_ship(sim, handle, state),
+            #[cfg(feature = "native")]
+            Vm::Native(vm) => vm.tick_ship(sim, handle, state),
+        }
+    }
 
-        {
-            translate_runtime_error(
-                vm.reset_gas
-                    .call(vm.store_mut().deref_mut(), &[GAS_PER_TICK.into()]),
-            )?;
+    pub fn update_environment(&mut self, environment: &Environment) -> Result<(), Error> {
+        self.environment = environment.clone();
+        self.vm.update_environment(environment)
+    }
+}
 
-            generate_system_state(sim, handle, state);
+/// A team's AI, compiled once so that resetting or restoring its VM only
+/// needs to instantiate it.
+#[derive(Clone)]
+enum CompiledCode {
+    Wasm(WasmModule),
+    #[cfg(feature = "native")]
+    Native(native::NativeCode),
+}
 
-            let store = vm.store();
-            let memory_view = vm.memory.view(store.deref());
-            let slice = vm
-                .system_state_ptr
-                .slice(&memory_view, SystemState::Size as u32)
-                .expect("system state write");
-            slice.write_slice(&state.state).expect("system state write");
+impl CompiledCode {
+    fn new(code: &Code, max_memory: usize) -> Result<CompiledCode, Error> {
+        match code {
+            #[cfg(feature = "native")]
+            Code::Native(code) => Ok(CompiledCode::Native(code.clone())),
+            _ => Ok(CompiledCode::Wasm(WasmModule::compile(code, max_memory)?)),
         }
+    }
+}
 
-        let (index, _) = handle.0.into_raw_parts();
-        let index = index as i32;
-        let result = vm
-            .tick_ship
-            .call(vm.store_mut().deref_mut(), &[index.into()]);
-        if let Err(e) = result {
-            if let Ok(ret) = vm.get_gas.call(vm.store_mut().deref_mut(), &[]) {
-                if !ret.is_empty() {
-                    let gas: i32 = ret[0].i32().unwrap();
-                    if gas <= 0 {
-                        return Err(Error {
-                            msg: "Ship exceeded maximum number of instructions".to_string(),
-                        });
-                    }
-                }
-            }
+/// The VM running a team's AI.
+enum Vm {
+    Wasm(WasmVm),
+    #[cfg(feature = "native")]
+    Native(native::NativeVm),
+}
 
-            {
-                let store = vm.store();
-                let memory_view = vm.memory.view(store.deref());
-                if let Some(vec) = WasmVm::read_vec(
-                    &memory_view,
-                    vm.panic_buffer_ptr.offset(),
-                    oort_api::panic::PANIC_BUFFER_SIZE as u32,
-                ) {
-                    let null_pos = vec.iter().position(|&x| x == 0).unwrap_or(vec.len());
-                    let msg = String::from_utf8_lossy(&vec[0..null_pos]).to_string();
-                    if msg.is_empty() {
-                        return Err(Error {
-                            msg: "Ship exceeded maximum number of instructions".to_string(),
-                        });
-                    } else {
-                        return Err(Error { msg });
-                    }
-                }
-            }
+impl Vm {
+    fn create(code: &CompiledCode, max_memory: usize, gas_budget: u32) -> Result<Vm, Error> {
+        match code {
+            #[cfg(feature = "native")]
+            CompiledCode::Native(code) => Ok(Vm::Native(native::NativeVm::new(code))),
+            CompiledCode::Wasm(module) => Ok(Vm::Wasm(WasmVm::instantiate(
+                module, max_memory, gas_budget,
+            )?)),
+        }
+    }
 
-            return translate_runtime_error(Err(e));
+    fn checkpoint(&self) -> Result<WasmVmCheckpoint, Error> {
+        match self {
+            Vm::Wasm(vm) => vm.checkpoint(),
+            #[cfg(feature = "native")]
+            Vm::Native(_) => Err(Error::Internal(
+                "Native AIs can't be checkpointed".to_string(),
+            )),
         }
+    }
 
-        {
-            let store = vm.store();
-            let memory_view = vm.memory.view(store.deref());
-            let slice = vm
-                .system_state_ptr
-                .slice(&memory_view, SystemState::Size as u32)
-                .expect("system state read");
-            slice
-                .read_slice(&mut state.state)
-                .expect("system state read");
-            apply_system_state(sim, handle, state);
+    fn update_environment(&mut self, environment: &Environment) -> Result<(), Error> {
+        match self {
+            Vm::Wasm(vm) => vm.update_environment(environment),
+            #[cfg(feature = "native")]
+            Vm::Native(vm) => vm.update_environment(environment),
+        }
+    }
+}
 
-            if state.get(SystemState::DebugTextLength) > 0.0 {
-                let offset = state.get(SystemState::DebugTextPointer) as u32;
-                let length = state.get(SystemState::DebugTextLength) as u32;
-                if let Some(s) = WasmVm::read_string(&memory_view, offset, length) {
-                    sim.emit_debug_text(handle, s);
-                }
-            }
+fn initial_system_state(sim: &Simulation, handle: ShipHandle, id: u32) -> LocalSystemState {
+    let mut state = LocalSystemState::new();
 
-            if state.get(SystemState::DebugLinesLength) > 0.0 {
-                let offset = state.get(SystemState::DebugLinesPointer) as u32;
-                let length = state.get(SystemState::DebugLinesLength) as u32;
-                if length <= MAX_DEBUG_LINES {
-                    if let Some(lines) = WasmVm::read_vec::<Line>(&memory_view, offset, length) {
-                        if validate_lines(&lines) {
-                            sim.emit_debug_lines(
-                                handle,
-                                lines
-                                    .iter()
-                                    .map(|v| crate::debug::Line {
-                                        a: point![v.x0, v.y0],
-                                        b: point![v.x1, v.y1],
-                                        color: color::from_u24(v.color),
-                                    })
-                                    .collect::<Vec<debug::Line>>(),
-                            );
-                        }
-                    }
-                }
-            }
+    state.set(
+        SystemState::Seed,
+        (make_seed(sim.seed(), handle) & 0xffffff) as f64,
+    );
+    state.set(SystemState::Id, id as f64);
+    state.set(SystemState::GasBudget, sim.gas_budget(handle) as f64);
+    if let Some(radar) = sim.ship(handle).data().radar.as_ref() {
+        state.set(SystemState::RadarHeading, radar.heading);
+        state.set(SystemState::RadarWidth, radar.width);
+        state.set(SystemState::RadarMinDistance, radar.min_distance);
+        state.set(SystemState::RadarMaxDistance, radar.max_distance);
+        state.set(SystemState::RadarMaxContacts, radar.max_contacts as f64);
+    }
 
-            if state.get(SystemState::DrawnTextLength) > 0.0 {
-                let offset = state.get(SystemState::DrawnTextPointer) as u32;
-                let length = state.get(SystemState::DrawnTextLength) as u32;
-                if length <= MAX_DRAWN_TEXT {
-                    if let Some(texts) = WasmVm::read_vec::<Text>(&memory_view, offset, length) {
-                        if validate_texts(&texts) {
-                            sim.emit_drawn_text(Some(handle), &texts);
-                        }
-                    }
-                }
-            }
-        }
+    state
+}
 
-        Ok(())
-    }
+/// A compiled wasm module along with the engine that compiled it. Cloning is
+/// cheap since both are reference counted.
+#[derive(Clone)]
+struct WasmModule {
+    engine: wasmer::Engine,
+    module: Module,
+}
 
-    pub fn update_environment(&mut self, environment: &Environment) -> Result<(), Error> {
-        self.vm.update_environment(environment)
+impl WasmModule {
+    fn compile(code: &Code, max_memory: usize) -> Result<WasmModule, Error> {
+        let max_memory_pages = (max_memory / wasmer::WASM_PAGE_SIZE) as u32;
+        #[cfg(feature = "js")]
+        let engine = wasmer::Engine::default();
+        #[cfg(feature = "sys")]
+        let engine: wasmer::Engine = wasmer_compiler_singlepass::Singlepass::new().into();
+        let module = match code {
+            Code::Wasm(wasm) => {
+                let wasm = limiter::rewrite(wasm, max_memory_pages)?;
+                translate_error(Module::new(&engine, wasm))?
+            }
+            #[cfg(feature = "precompile")]
+            Code::Precompiled(bytes) => {
+                translate_error(unsafe { Module::deserialize(&engine, bytes.clone()) })?
+            }
+            _ => unreachable!(),
+        };
+        Ok(WasmModule { engine, module })
     }
 }
 
@@ -275,35 +504,81 @@ pub struct WasmVm {
     store: Rc<RefCell<wasmer::Store>>,
     memory: wasmer::Memory,
     system_state_ptr: WasmPtr<u64>,
+    // Number of system state slots the AI was built with.
+    system_state_size: u32,
     environment_ptr: WasmPtr<u8>,
     panic_buffer_ptr: WasmPtr<u8>,
+    // Missing in binaries built against older versions of oort_api.
+    radio_send_buffer_ptr: Option<WasmPtr<u8>>,
+    radio_receive_buffer_ptr: Option<WasmPtr<u8>>,
     tick_ship: wasmer::Function,
     delete_ship: wasmer::Function,
     reset_gas: wasmer::Function,
     get_gas: wasmer::Function,
+    // Missing in modules precompiled by older versions of the limiter.
+    get_trap_reason: Option<wasmer::Function>,
+    checkpoint_globals: Vec<wasmer::Global>,
+}
+
+#[derive(Clone)]
+struct WasmVmCheckpoint {
+    memory: Vec<u8>,
+    globals: Vec<GlobalValue>,
+}
+
+// Stored instead of wasmer::Value so that checkpoints are Send.
+#[derive(Clone, Copy)]
+enum GlobalValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
 }
 
 impl WasmVm {
-    pub fn create(code: &Code) -> Result<WasmVm, Error> {
-        #[cfg(feature = "js")]
-        let mut store = Store::default();
-        #[cfg(feature = "sys")]
-        let mut store = Store::new(wasmer_compiler_singlepass::Singlepass::new());
-        let module = match code {
-            Code::Wasm(wasm) => {
-                let wasm = limiter::rewrite(wasm)?;
-                translate_error(Module::new(&store, wasm))?
-            }
-            #[cfg(feature = "precompile")]
-            Code::Precompiled(bytes) => {
-                translate_error(unsafe { Module::deserialize(&store, bytes.clone()) })?
-            }
-            _ => unreachable!(),
-        };
+    /// Loads the AI and runs its initialization with the given gas.
+    pub fn create(code: &Code, max_memory: usize, gas_budget: u32) -> Result<WasmVm, Error> {
+        Self::instantiate(
+            &WasmModule::compile(code, max_memory)?,
+            max_memory,
+            gas_budget,
+        )
+    }
+
+    fn instantiate(
+        module: &WasmModule,
+        max_memory: usize,
+        gas_budget: u32,
+    ) -> Result<WasmVm, Error> {
+        let max_memory_pages = (max_memory / wasmer::WASM_PAGE_SIZE) as u32;
+        let mut store = Store::new(module.engine.clone());
         let import_object = imports! {};
-        let instance = Instance::new(&mut store, &module, &import_object)?;
+        let instance = Instance::new(&mut store, &module.module, &import_object)?;
 
         let memory = translate_error(instance.exports.get_memory("memory"))?.clone();
+        // Precompiled modules were limited to the default memory size when
+        // they were compiled, so apply this scenario's limit here.
+        if memory.view(&store).size().0 > max_memory_pages {
+            return Err(Error::OutOfMemory);
+        }
+        // Missing in modules precompiled by older versions of the limiter,
+        // which can only grow to the limit they were compiled with.
+        if let Ok(memory_limit) = instance.exports.get_global(limiter::MEMORY_LIMIT_EXPORT) {
+            translate_runtime_error(
+                memory_limit.set(&mut store, (max_memory_pages as i32).into()),
+            )?;
+        }
+        let abi_version = match instance.exports.get_global("ABI_VERSION") {
+            Ok(global) => {
+                let offset = global.get(&mut store).i32().unwrap();
+                let mut bytes = [0; 4];
+                translate_error(memory.view(&store).read(offset as u32 as u64, &mut bytes))?;
+                u32::from_le_bytes(bytes)
+            }
+            Err(_) => LEGACY_ABI_VERSION,
+        };
+        let system_state_size =
+            system_state_size(abi_version).ok_or(Error::IncompatibleAbi(abi_version))?;
         let system_state_offset: i32 =
             translate_error(instance.exports.get_global("SYSTEM_STATE"))?
                 .get(&mut store)
@@ -321,6 +596,17 @@ impl WasmVm {
                 .i32()
                 .unwrap();
         let panic_buffer_ptr: WasmPtr<u8> = WasmPtr::new(panic_buffer_offset as u32);
+        let mut get_optional_ptr = |name: &str| -> Option<WasmPtr<u8>> {
+            let offset = instance
+                .exports
+                .get_global(name)
+                .ok()?
+                .get(&mut store)
+                .i32()?;
+            Some(WasmPtr::new(offset as u32))
+        };
+        let radio_send_buffer_ptr = get_optional_ptr("RADIO_SEND_BUFFER");
+        let radio_receive_buffer_ptr = get_optional_ptr("RADIO_RECEIVE_BUFFER");
 
         let initialize =
             translate_error(instance.exports.get_function("export_initialize"))?.clone();
@@ -329,23 +615,289 @@ impl WasmVm {
             translate_error(instance.exports.get_function("export_delete_ship"))?.clone();
         let reset_gas = translate_error(instance.exports.get_function("reset_gas"))?.clone();
         let get_gas = translate_error(instance.exports.get_function("get_gas"))?.clone();
+        let get_trap_reason = instance
+            .exports
+            .get_function("get_trap_reason")
+            .ok()
+            .cloned();
 
-        translate_runtime_error(reset_gas.call(&mut store, &[GAS_PER_TICK.into()]))?;
+        let mut checkpoint_globals: Vec<(usize, wasmer::Global)> = instance
+            .exports
+            .iter()
+            .filter_map(|(name, ext)| {
+                let index = name.strip_prefix(limiter::CHECKPOINT_GLOBAL_PREFIX)?;
+                match ext {
+                    wasmer::Extern::Global(global) => Some((index.parse().ok()?, global.clone())),
+                    _ => None,
+                }
+            })
+            .collect();
+        checkpoint_globals.sort_by_key(|(index, _)| *index);
+        let checkpoint_globals = checkpoint_globals
+            .into_iter()
+            .map(|(_, global)| global)
+            .collect();
+
+        translate_runtime_error(reset_gas.call(&mut store, &[gas_to_i32(gas_budget).into()]))?;
         translate_runtime_error(initialize.call(&mut store, &[]))?;
 
         Ok(WasmVm {
             store: Rc::new(RefCell::new(store)),
             memory,
             system_state_ptr,
+            system_state_size,
             environment_ptr,
             panic_buffer_ptr,
+            radio_send_buffer_ptr,
+            radio_receive_buffer_ptr,
             tick_ship,
             delete_ship,
             reset_gas,
             get_gas,
+            get_trap_reason,
+            checkpoint_globals,
         })
     }
 
+    fn tick_ship(
+        &mut self,
+        sim: &mut Simulation,
+        handle: ShipHandle,
+        state: &mut LocalSystemState,
+    ) -> Result<(), Crash> {
+        let budget = sim.gas_budget(handle);
+
+        {
+            translate_runtime_error(
+                self.reset_gas
+                    .call(self.store_mut().deref_mut(), &[gas_to_i32(budget).into()]),
+            )?;
+
+            generate_system_state(sim, handle, state);
+
+            let store = self.store();
+            let memory_view = self.memory.view(store.deref());
+            let slice = self
+                .system_state_ptr
+                .slice(&memory_view, self.system_state_size)
+                .expect("system state write");
+            slice
+                .write_slice(&state.state[..self.system_state_size as usize])
+                .expect("system state write");
+
+            if let Some(ptr) = self.radio_receive_buffer_ptr {
+                for (i, radio) in sim.ship(handle).data().radios.iter().enumerate() {
+                    if let Some(bytes) = radio.get_received_bytes() {
+                        let offset = ptr.offset() + (i * oort_api::MAX_RADIO_MESSAGE_SIZE) as u32;
+                        WasmPtr::<u8>::new(offset)
+                            .slice(&memory_view, bytes.len() as u32)
+                            .and_then(|slice| slice.write_slice(bytes))
+                            .expect("radio receive buffer write");
+                    }
+                }
+            }
+        }
+
+        let (index, _) = handle.0.into_raw_parts();
+        let index = index as i32;
+        let result = self
+            .tick_ship
+            .call(self.store_mut().deref_mut(), &[index.into()]);
+        let remaining_gas = self
+            .get_gas
+            .call(self.store_mut().deref_mut(), &[])
+            .ok()
+            .and_then(|ret| ret.first().and_then(|v| v.i32()));
+        if let Some(gas) = remaining_gas {
+            let used = budget.saturating_sub(gas.max(0) as u32);
+            sim.ship_mut(handle)
+                .data_mut()
+                .gas_usage
+                .get_or_insert_with(Default::default)
+                .record(budget, used);
+        }
+        if let Err(e) = result {
+            let backtrace = e
+                .trace()
+                .iter()
+                .map(|frame| match frame.function_name() {
+                    Some(name) => name.to_string(),
+                    None => format!("<function {}>", frame.func_index()),
+                })
+                .collect();
+            return Err(Crash {
+                error: self.crash_error(remaining_gas, e),
+                backtrace,
+            });
+        }
+
+        {
+            let store = self.store();
+            let memory_view = self.memory.view(store.deref());
+            let slice = self
+                .system_state_ptr
+                .slice(&memory_view, self.system_state_size)
+                .expect("system state read");
+            slice
+                .read_slice(&mut state.state[..self.system_state_size as usize])
+                .expect("system state read");
+            apply_system_state(sim, handle, state);
+
+            if let Some(ptr) = self.radio_send_buffer_ptr {
+                for (i, radio) in sim
+                    .ship_mut(handle)
+                    .data_mut()
+                    .radios
+                    .iter_mut()
+                    .enumerate()
+                {
+                    let idxs = radio_internal::radio_indices(i);
+                    if state.get(idxs.send) != radio_internal::SEND_BYTES {
+                        continue;
+                    }
+                    let length = (state.get(idxs.send_length) as usize)
+                        .min(radio.get_max_message_size()) as u32;
+                    let offset = ptr.offset() + (i * oort_api::MAX_RADIO_MESSAGE_SIZE) as u32;
+                    if let Some(bytes) = Self::read_vec::<u8>(&memory_view, offset, length) {
+                        radio.set_sent_bytes(bytes);
+                    }
+                }
+            }
+
+            if state.get(SystemState::DebugTextLength) > 0.0 {
+                let offset = state.get(SystemState::DebugTextPointer) as u32;
+                let length = state.get(SystemState::DebugTextLength) as u32;
+                if let Some(s) = Self::read_string(&memory_view, offset, length) {
+                    sim.emit_debug_text(handle, s);
+                }
+            }
+
+            if state.get(SystemState::DebugLinesLength) > 0.0 {
+                let offset = state.get(SystemState::DebugLinesPointer) as u32;
+                let length = state.get(SystemState::DebugLinesLength) as u32;
+                if length <= MAX_DEBUG_LINES {
+                    if let Some(lines) = Self::read_vec::<Line>(&memory_view, offset, length) {
+                        emit_debug_lines(sim, handle, &lines);
+                    }
+                }
+            }
+
+            if state.get(SystemState::DrawnTextLength) > 0.0 {
+                let offset = state.get(SystemState::DrawnTextPointer) as u32;
+                let length = state.get(SystemState::DrawnTextLength) as u32;
+                if length <= MAX_DRAWN_TEXT {
+                    if let Some(texts) = Self::read_vec::<Text>(&memory_view, offset, length) {
+                        emit_drawn_text(sim, handle, &texts);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn delete_ship(&mut self, handle: ShipHandle, gas_budget: u32) {
+        let (index, _) = handle.0.into_raw_parts();
+        let index = index as i32;
+        self.reset_gas
+            .call(&mut self.store_mut(), &[gas_to_i32(gas_budget).into()])
+            .unwrap();
+        if let Err(e) = translate_runtime_error(
+            self.delete_ship
+                .call(self.store_mut().deref_mut(), &[index.into()]),
+        ) {
+            log::warn!("Failed to delete ship: {:?}", e);
+        }
+    }
+
+    /// Builds the error for a failed tick, using the reason recorded by the
+    /// limiter or the panic message if available.
+    fn crash_error(&self, remaining_gas: Option<i32>, err: wasmer::RuntimeError) -> Error {
+        let trap_reason = self.get_trap_reason.as_ref().and_then(|f| {
+            f.call(self.store_mut().deref_mut(), &[])
+                .ok()
+                .and_then(|ret| ret.first().and_then(|v| v.i32()))
+        });
+        match trap_reason {
+            Some(limiter::TRAP_REASON_OUT_OF_GAS) => return Error::OutOfGas,
+            Some(limiter::TRAP_REASON_OUT_OF_MEMORY) => return Error::OutOfMemory,
+            Some(limiter::TRAP_REASON_STACK_OVERFLOW) => return Error::StackOverflow,
+            // Modules limited before trap reasons were recorded.
+            None if remaining_gas.map_or(false, |gas| gas <= 0) => return Error::OutOfGas,
+            _ => {}
+        }
+
+        let store = self.store();
+        let memory_view = self.memory.view(store.deref());
+        if let Some(vec) = WasmVm::read_vec(
+            &memory_view,
+            self.panic_buffer_ptr.offset(),
+            oort_api::panic::PANIC_BUFFER_SIZE as u32,
+        ) {
+            let null_pos = vec.iter().position(|&x| x == 0).unwrap_or(vec.len());
+            let msg = String::from_utf8_lossy(&vec[0..null_pos]).to_string();
+            if !msg.is_empty() {
+                return Error::Panic(msg);
+            }
+        }
+
+        translate_trap(err)
+    }
+
+    fn checkpoint(&self) -> Result<WasmVmCheckpoint, Error> {
+        let mut store = self.store_mut();
+        let memory = {
+            let view = self.memory.view(store.deref());
+            let mut memory = vec![0; view.data_size() as usize];
+            translate_error(view.read(0, &mut memory))?;
+            memory
+        };
+        let mut globals = vec![];
+        for global in self.checkpoint_globals.iter() {
+            globals.push(match global.get(store.deref_mut()) {
+                wasmer::Value::I32(v) => GlobalValue::I32(v),
+                wasmer::Value::I64(v) => GlobalValue::I64(v),
+                wasmer::Value::F32(v) => GlobalValue::F32(v),
+                wasmer::Value::F64(v) => GlobalValue::F64(v),
+                v => {
+                    return Err(Error::Internal(format!(
+                        "Unsupported global in checkpoint: {v:?}"
+                    )))
+                }
+            });
+        }
+        Ok(WasmVmCheckpoint { memory, globals })
+    }
+
+    fn restore(&self, checkpoint: &WasmVmCheckpoint) -> Result<(), Error> {
+        let mut store = self.store_mut();
+        let current_size = self.memory.view(store.deref()).data_size() as usize;
+        if checkpoint.memory.len() > current_size {
+            let delta = (checkpoint.memory.len() - current_size) / wasmer::WASM_PAGE_SIZE;
+            translate_error(self.memory.grow(store.deref_mut(), delta as u32))?;
+        }
+        translate_error(self.memory.view(store.deref()).write(0, &checkpoint.memory))?;
+        if checkpoint.globals.len() != self.checkpoint_globals.len() {
+            return Err(Error::Internal(
+                "Checkpoint does not match VM globals".to_string(),
+            ));
+        }
+        for (global, value) in self
+            .checkpoint_globals
+            .iter()
+            .zip(checkpoint.globals.iter())
+        {
+            let value = match *value {
+                GlobalValue::I32(v) => wasmer::Value::I32(v),
+                GlobalValue::I64(v) => wasmer::Value::I64(v),
+                GlobalValue::F32(v) => wasmer::Value::F32(v),
+                GlobalValue::F64(v) => wasmer::Value::F64(v),
+            };
+            translate_runtime_error(global.set(store.deref_mut(), value))?;
+        }
+        Ok(())
+    }
+
     fn store(&self) -> Ref<'_, Store> {
         self.store.borrow()
     }
@@ -378,16 +930,7 @@ impl WasmVm {
     }
 
     fn update_environment(&self, environment: &Environment) -> Result<(), Error> {
-        let environment_string = environment
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<String>>()
-            .join("\n");
-        if environment_string.len() > oort_api::MAX_ENVIRONMENT_SIZE {
-            return Err(Error {
-                msg: "environment too large".to_string(),
-            });
-        }
+        let environment_string = encode_environment(environment)?;
         let store = self.store_mut();
         let view = self.memory.view(&store);
         let slice = self
@@ -403,6 +946,7 @@ impl WasmVm {
     }
 }
 
+#[derive(Clone)]
 struct LocalSystemState {
     pub state: [u64; SystemState::Size as usize],
 }
@@ -461,6 +1005,25 @@ fn generate_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut L
         state.set(SystemState::RadarWidth, radar.get_width());
         state.set(SystemState::RadarMinDistance, radar.get_min_distance());
         state.set(SystemState::RadarMaxDistance, radar.get_max_distance());
+        state.set(
+            SystemState::RadarMaxContacts,
+            radar.get_max_contacts() as f64,
+        );
+
+        state.set(
+            SystemState::RadarContactCount,
+            radar.scan_all().len() as f64,
+        );
+        for (i, contact) in radar.scan_all().iter().enumerate() {
+            let idxs = oort_api::prelude::radar_internal::radar_contact_indices(i);
+            state.set(idxs.class, translate_class(contact.class) as u32 as f64);
+            state.set(idxs.position[0], contact.position.x);
+            state.set(idxs.position[1], contact.position.y);
+            state.set(idxs.velocity[0], contact.velocity.x);
+            state.set(idxs.velocity[1], contact.velocity.y);
+            state.set(idxs.rssi, contact.rssi);
+            state.set(idxs.snr, contact.snr);
+        }
 
         if let Some(contact) = radar.scan() {
             state.set(SystemState::RadarContactFound, 1.0);
@@ -510,13 +1073,42 @@ fn generate_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut L
         );
         state.set(SystemState::Health, data.health);
         state.set(SystemState::Fuel, data.fuel.unwrap_or(f64::INFINITY));
+        let power = data.power.as_ref();
+        state.set(
+            SystemState::Energy,
+            power.map_or(f64::INFINITY, |x| x.energy),
+        );
+        state.set(
+            SystemState::MaxEnergy,
+            power.map_or(f64::INFINITY, |x| x.max_energy),
+        );
+        state.set(SystemState::Heat, power.map_or(0.0, |x| x.heat));
+        state.set(
+            SystemState::MaxHeat,
+            power.map_or(f64::INFINITY, |x| x.max_heat),
+        );
+        state.set_u64(
+            SystemState::DisabledSubsystems,
+            data.subsystems
+                .iter()
+                .filter(|x| x.health <= 0.0)
+                .fold(0, |bits, x| bits | x.subsystem.bit()),
+        );
     }
 
     for (i, radio) in sim.ship(handle).data().radios.iter().enumerate() {
-        let idxs = oort_api::prelude::radio_internal::radio_indices(i);
+        let idxs = radio_internal::radio_indices(i);
         state.set(idxs.channel, radio.get_channel() as f64);
+        state.set_u64(idxs.key, radio.get_key());
+        state.set(idxs.bandwidth, radio.get_bandwidth() as f64);
+        state.set(idxs.max_message_size, radio.get_max_message_size() as f64);
+        state.set(idxs.transmit_ticks, radio.get_transmit_ticks() as f64);
         if let Some(msg) = radio.get_received() {
             state.set(idxs.receive, 1.0);
+            state.set(
+                idxs.receive_length,
+                radio.get_received_bytes().map_or(0, |b| b.len()) as f64,
+            );
             state.set(idxs.data[0], msg[0]);
             state.set(idxs.data[1], msg[1]);
             state.set(idxs.data[2], msg[2]);
@@ -525,9 +1117,20 @@ fn generate_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut L
             state.set(idxs.receive, 0.0);
         }
         state.set(idxs.send, 0.0);
+        state.set(idxs.send_length, 0.0);
     }
 
     state.set(SystemState::CurrentTick, sim.tick() as f64);
+    state.set(SystemState::GasBudget, sim.gas_budget(handle) as f64);
+    state.set(
+        SystemState::GasUsed,
+        sim.ship(handle)
+            .data()
+            .gas_usage
+            .as_ref()
+            .map(|x| x.last)
+            .unwrap_or(0) as f64,
+    );
 
     for (i, idx) in [
         SystemState::ReloadTicks0,
@@ -543,6 +1146,13 @@ fn generate_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut L
 }
 
 fn apply_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut LocalSystemState) {
+    let loadout = state.get(SystemState::SelectLoadout);
+    if loadout > 0.0 {
+        sim.ship_mut(handle).select_loadout(loadout as usize - 1);
+        state.set(SystemState::SelectLoadout, 0.0);
+    }
+    sim.ship_mut(handle).data_mut().loadout_locked = true;
+
     sim.ship_mut(handle).accelerate(Vec2::new(
         state.get(SystemState::AccelerateX),
         state.get(SystemState::AccelerateY),
@@ -562,8 +1172,9 @@ fn apply_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut Loca
     .iter()
     .enumerate()
     {
+        // Laser turrets traverse towards the aim heading even when not firing.
+        sim.ship_mut(handle).aim(i as i64, state.get(*aim));
         if state.get(*fire) > 0.0 {
-            sim.ship_mut(handle).aim(i as i64, state.get(*aim));
             sim.ship_mut(handle).fire(i as i64);
             state.set(*fire, 0.0);
         }
@@ -575,6 +1186,7 @@ fn apply_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut Loca
         radar.set_min_distance(state.get(SystemState::RadarMinDistance));
         radar.set_max_distance(state.get(SystemState::RadarMaxDistance));
         radar.set_ecm_mode(translate_ecm_mode(state.get(SystemState::RadarEcmMode)));
+        radar.set_max_contacts(state.get(SystemState::RadarMaxContacts));
     }
 
     let active_abilities = ActiveAbilities(state.get_u64(SystemState::ActivateAbility));
@@ -602,8 +1214,9 @@ fn apply_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut Loca
         .iter_mut()
         .enumerate()
     {
-        let idxs = oort_api::prelude::radio_internal::radio_indices(i);
+        let idxs = radio_internal::radio_indices(i);
         radio.set_channel(state.get(idxs.channel) as usize);
+        radio.set_key(state.get_u64(idxs.key));
         if state.get(idxs.send) != 0.0 {
             let msg = [
                 state.get(idxs.data[0]),
@@ -611,13 +1224,16 @@ fn apply_system_state(sim: &mut Simulation, handle: ShipHandle, state: &mut Loca
                 state.get(idxs.data[2]),
                 state.get(idxs.data[3]),
             ];
-            radio.set_sent(Some(msg));
+            // Messages from send_bytes are read from guest memory after this.
+            if state.get(idxs.send) != radio_internal::SEND_BYTES {
+                radio.set_sent(Some(msg));
+            }
         }
     }
 }
 
 fn translate_class(class: ShipClass) -> Class {
-    match class {
+    match class.base() {
         ShipClass::Fighter => Class::Fighter,
         ShipClass::Frigate => Class::Frigate,
         ShipClass::Cruiser => Class::Cruiser,
@@ -635,30 +1251,99 @@ fn translate_ecm_mode(v: f64) -> EcmMode {
         EcmMode::None
     } else if v == EcmMode::Noise as u32 {
         EcmMode::Noise
+    } else if v == EcmMode::RangeGatePullOff as u32 {
+        EcmMode::RangeGatePullOff
+    } else if v == EcmMode::FalseTargets as u32 {
+        EcmMode::FalseTargets
     } else {
         EcmMode::None
     }
 }
 
+/// Returns the number of system state slots exchanged with an AI built for
+/// the given ABI version, or None if the version can't be run. Slots are only
+/// ever appended, so each older layout is a prefix of the current one.
+fn system_state_size(abi_version: u32) -> Option<u32> {
+    match abi_version {
+        LEGACY_ABI_VERSION => Some(109),
+        1 => Some(SystemState::SelectLoadout as u32),
+        2 => Some(SystemState::DisabledSubsystems as u32),
+        3 => Some(SystemState::Energy as u32),
+        oort_api::ABI_VERSION => Some(SystemState::Size as u32),
+        _ => None,
+    }
+}
+
 fn translate_error<T, U>(err: Result<T, U>) -> Result<T, Error>
 where
     U: std::fmt::Debug,
 {
     match err {
         Ok(val) => Ok(val),
-        Err(err) => Err(Error {
-            msg: format!("Wasmer error: {err:?}"),
-        }),
+        Err(err) => Err(Error::Internal(format!("Wasmer error: {err:?}"))),
     }
 }
 
 fn translate_runtime_error<T>(err: Result<T, wasmer::RuntimeError>) -> Result<T, Error> {
-    match err {
-        Ok(val) => Ok(val),
-        Err(err) => Err(Error {
-            msg: format!("Ship runtime error: {err:?}"),
-        }),
+    err.map_err(translate_trap)
+}
+
+/// Maps a trap to an error. The sys backend reports a trap code, while the js
+/// backend only has the browser's error message, so both are checked to get
+/// the same result on each.
+fn translate_trap(err: wasmer::RuntimeError) -> Error {
+    let message = err.message();
+    match err.to_trap() {
+        Some(wasmer::TrapCode::StackOverflow) => return Error::StackOverflow,
+        Some(wasmer::TrapCode::IntegerDivisionByZero) => return Error::DivisionByZero,
+        Some(wasmer::TrapCode::UnreachableCodeReached) => return Error::Unreachable,
+        Some(_) => return Error::Trap(message),
+        None => {}
+    }
+    let lowercase = message.to_lowercase();
+    if lowercase.contains("call stack") {
+        Error::StackOverflow
+    } else if lowercase.contains("divide by zero") || lowercase.contains("division by zero") {
+        Error::DivisionByZero
+    } else if lowercase.contains("unreachable") {
+        Error::Unreachable
+    } else {
+        Error::Trap(message)
+    }
+}
+
+fn emit_debug_lines(sim: &mut Simulation, handle: ShipHandle, lines: &[Line]) {
+    if validate_lines(lines) {
+        sim.emit_debug_lines(
+            handle,
+            lines
+                .iter()
+                .map(|v| crate::debug::Line {
+                    a: point![v.x0, v.y0],
+                    b: point![v.x1, v.y1],
+                    color: color::from_u24(v.color),
+                })
+                .collect::<Vec<debug::Line>>(),
+        );
+    }
+}
+
+fn emit_drawn_text(sim: &mut Simulation, handle: ShipHandle, texts: &[Text]) {
+    if validate_texts(texts) {
+        sim.emit_drawn_text(Some(handle), texts);
+    }
+}
+
+fn encode_environment(environment: &Environment) -> Result<String, Error> {
+    let environment_string = environment
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join("\n");
+    if environment_string.len() > oort_api::MAX_ENVIRONMENT_SIZE {
+        return Err(Error::Internal("environment too large".to_string()));
     }
+    Ok(environment_string)
 }
 
 fn validate_floats(vs: &[f64]) -> bool {
@@ -679,12 +1364,16 @@ fn validate_texts(texts: &[Text]) -> bool {
 
 #[cfg(feature = "precompile")]
 pub fn precompile(code: &[u8]) -> Result<Code, Error> {
-    let code = limiter::rewrite(code)?;
+    let code = limiter::rewrite(code, (DEFAULT_MAX_MEMORY / wasmer::WASM_PAGE_SIZE) as u32)?;
     let store = Store::default();
     let module = translate_error(Module::new(&store, code))?;
     Ok(Code::Precompiled(translate_error(module.serialize())?))
 }
 
+fn gas_to_i32(gas: u32) -> i32 {
+    gas.min(i32::MAX as u32) as i32
+}
+
 fn make_seed(sim_seed: u32, handle: ShipHandle) -> i64 {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::Hasher;
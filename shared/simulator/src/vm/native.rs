@@ -0,0 +1,201 @@
+//! Runs ship AIs compiled into the simulator as native Rust code.
+//!
+//! This is meant for local development, where being able to use a debugger,
+//! `cargo test` and sanitizers on AI logic is worth more than sandboxing. The
+//! AI talks to the simulator through the same SystemState exchange as a wasm
+//! AI, except that the buffers are oort_api's globals in this process.
+//!
+//! Because those globals are shared by every native AI, ships are ticked one
+//! at a time under a lock and their state is copied in and out around each
+//! tick. There's no gas limit and native AIs can't be checkpointed.
+use super::{
+    apply_system_state, emit_debug_lines, emit_drawn_text, encode_environment,
+    generate_system_state, Crash, Environment, Error, LocalSystemState, MAX_DEBUG_LINES,
+    MAX_DRAWN_TEXT,
+};
+use crate::ship::ShipHandle;
+use crate::simulation::Simulation;
+use oort_api::prelude::radio_internal;
+use oort_api::rng_state::{self, RngState};
+use oort_api::{MAX_ENVIRONMENT_SIZE, MAX_RADIO_MESSAGE_SIZE};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+
+/// A ship's AI, like the `Ship` struct in an AI's `user.rs`.
+pub trait NativeShip {
+    fn tick(&mut self);
+}
+
+type Factory = dyn Fn() -> Box<dyn NativeShip> + Send + Sync;
+
+/// Creates a native AI for each ship. Compared and hashed by name.
+#[derive(Clone)]
+pub struct NativeCode {
+    name: String,
+    factory: Arc<Factory>,
+}
+
+impl NativeCode {
+    pub fn new(
+        name: &str,
+        factory: impl Fn() -> Box<dyn NativeShip> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            factory: Arc::new(factory),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl std::fmt::Debug for NativeCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeCode")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for NativeCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for NativeCode {}
+
+impl std::hash::Hash for NativeCode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+// Held while oort_api's globals belong to a ship.
+static LOCK: Mutex<()> = Mutex::new(());
+
+struct ShipWrapper {
+    ship: Box<dyn NativeShip>,
+    rng: RngState,
+}
+
+pub(super) struct NativeVm {
+    code: NativeCode,
+    ships: HashMap<ShipHandle, ShipWrapper>,
+    environment: Box<[u8; MAX_ENVIRONMENT_SIZE]>,
+}
+
+impl NativeVm {
+    pub(super) fn new(code: &NativeCode) -> Self {
+        Self {
+            code: code.clone(),
+            ships: HashMap::new(),
+            environment: Box::new([0; MAX_ENVIRONMENT_SIZE]),
+        }
+    }
+
+    pub(super) fn tick_ship(
+        &mut self,
+        sim: &mut Simulation,
+        handle: ShipHandle,
+        state: &mut LocalSystemState,
+    ) -> Result<(), Crash> {
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        generate_system_state(sim, handle, state);
+        unsafe {
+            let system_state = &mut oort_api::sys::SYSTEM_STATE;
+            system_state.fill(0);
+            system_state[..state.state.len()].copy_from_slice(&state.state);
+            oort_api::sys::ENVIRONMENT.copy_from_slice(&self.environment[..]);
+            oort_api::sys::RADIO_SEND_BUFFER.fill(0);
+            let receive_buffer = &mut oort_api::sys::RADIO_RECEIVE_BUFFER;
+            receive_buffer.fill(0);
+            for (i, radio) in sim.ship(handle).data().radios.iter().enumerate() {
+                if let Some(bytes) = radio.get_received_bytes() {
+                    let offset = i * MAX_RADIO_MESSAGE_SIZE;
+                    receive_buffer[offset..(offset + bytes.len())].copy_from_slice(bytes);
+                }
+            }
+        }
+        oort_api::dbg::reset();
+
+        let factory = &self.code.factory;
+        let ships = &mut self.ships;
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            let wrapper = ships.entry(handle).or_insert_with(|| {
+                let rng = RngState::new();
+                rng_state::set(rng.clone());
+                ShipWrapper {
+                    ship: factory(),
+                    rng,
+                }
+            });
+            rng_state::set(wrapper.rng.clone());
+            wrapper.ship.tick();
+            wrapper.rng = rng_state::get().clone();
+        }));
+        if let Err(payload) = result {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            return Err(Error::Panic(format!("ship panicked at '{msg}'")).into());
+        }
+
+        unsafe {
+            let system_state = &oort_api::sys::SYSTEM_STATE;
+            state
+                .state
+                .copy_from_slice(&system_state[..state.state.len()]);
+        }
+        apply_system_state(sim, handle, state);
+
+        for (i, radio) in sim
+            .ship_mut(handle)
+            .data_mut()
+            .radios
+            .iter_mut()
+            .enumerate()
+        {
+            let idxs = radio_internal::radio_indices(i);
+            if state.get(idxs.send) != radio_internal::SEND_BYTES {
+                continue;
+            }
+            let length = (state.get(idxs.send_length) as usize).min(radio.get_max_message_size());
+            let offset = i * MAX_RADIO_MESSAGE_SIZE;
+            let bytes = unsafe { &oort_api::sys::RADIO_SEND_BUFFER[offset..(offset + length)] };
+            radio.set_sent_bytes(bytes.to_vec());
+        }
+
+        let text = oort_api::dbg::text_buffer();
+        if !text.is_empty() {
+            sim.emit_debug_text(handle, text.to_string());
+        }
+        let lines = oort_api::dbg::line_buffer();
+        if lines.len() <= MAX_DEBUG_LINES as usize {
+            emit_debug_lines(sim, handle, lines);
+        }
+        let texts = oort_api::dbg::drawn_text_buffer();
+        if texts.len() <= MAX_DRAWN_TEXT as usize {
+            emit_drawn_text(sim, handle, texts);
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn delete_ship(&mut self, handle: ShipHandle) {
+        self.ships.remove(&handle);
+    }
+
+    pub(super) fn update_environment(&mut self, environment: &Environment) -> Result<(), Error> {
+        let environment_string = encode_environment(environment)?;
+        self.environment.fill(0);
+        self.environment[..environment_string.len()].copy_from_slice(environment_string.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use nalgebra::vector;
+use oort_api::SystemState;
+use oort_simulator::ship::{self, fighter};
+use oort_simulator::simulation::{self, Code};
+use oort_simulator::vm::{Error, WasmVm, DEFAULT_GAS_PER_TICK, DEFAULT_MAX_MEMORY};
+use test_log::test;
+
+const SYSTEM_STATE: u32 = 1024;
+const MARKER: u64 = 0x0123456789abcdef;
+
+// Minimal AI exporting the given ABI version, or none for a legacy AI. Like a
+// real AI, other data follows its system state array. The tick traps if the
+// simulator wrote past the end of the array.
+fn make_ai(abi_version: Option<u32>) -> Code {
+    let system_state_slots = match abi_version {
+        Some(_) => SystemState::MaxSize as u32,
+        None => 128,
+    };
+    let marker_offset = SYSTEM_STATE + system_state_slots * 8;
+    let marker: String = MARKER
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("\\{b:02x}"))
+        .collect();
+    let abi_export = match abi_version {
+        Some(version) => {
+            let bytes: String = version
+                .to_le_bytes()
+                .iter()
+                .map(|b| format!("\\{b:02x}"))
+                .collect();
+            format!(
+                r#"(global (export "ABI_VERSION") i32 (i32.const 4096))
+  (data (i32.const 4096) "{bytes}")"#
+            )
+        }
+        None => String::new(),
+    };
+    let wat = format!(
+        r#"
+(module
+  (memory (export "memory") 2)
+  {abi_export}
+  (global (export "SYSTEM_STATE") i32 (i32.const {SYSTEM_STATE}))
+  (data (i32.const {marker_offset}) "{marker}")
+  (global (export "ENVIRONMENT") i32 (i32.const 8192))
+  (global (export "PANIC_BUFFER") i32 (i32.const 16384))
+  (func (export "export_initialize"))
+  (func (export "export_delete_ship") (param i32))
+  (func (export "export_tick_ship") (param i32)
+    i32.const {marker_offset}
+    i64.load
+    i64.const {MARKER}
+    i64.ne
+    (if (then unreachable))))
+"#
+    );
+    Code::Wasm(
+        wabt::Wat2Wasm::new()
+            .convert(wat)
+            .unwrap()
+            .as_ref()
+            .to_vec(),
+    )
+}
+
+// AI that checks gas_remaining() is within its budget and decreases.
+fn make_gas_ai() -> Code {
+    let budget_offset = SYSTEM_STATE + SystemState::GasBudget as u32 * 8;
+    let wat = format!(
+        r#"
+(module
+  (import "oort" "gas_remaining" (func $gas_remaining (result i32)))
+  (memory (export "memory") 2)
+  (global (export "SYSTEM_STATE") i32 (i32.const {SYSTEM_STATE}))
+  (global (export "ENVIRONMENT") i32 (i32.const 8192))
+  (global (export "PANIC_BUFFER") i32 (i32.const 16384))
+  (func (export "export_initialize"))
+  (func (export "export_delete_ship") (param i32))
+  (func (export "export_tick_ship") (param i32)
+    (local i32)
+    call $gas_remaining
+    local.tee 1
+    i32.const {budget_offset}
+    f64.load
+    i32.trunc_f64_u
+    i32.gt_u
+    (if (then unreachable))
+    (block i32.const 1 drop)
+    local.get 1
+    call $gas_remaining
+    i32.le_u
+    (if (then unreachable))))
+"#
+    );
+    Code::Wasm(
+        wabt::Wat2Wasm::new()
+            .convert(wat)
+            .unwrap()
+            .as_ref()
+            .to_vec(),
+    )
+}
+
+fn tick(code: Code) -> Option<Error> {
+    let mut sim = simulation::Simulation::new("test", 0, &[code, Code::None]);
+    ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(0),
+    );
+    sim.step();
+    sim.events()
+        .crashes
+        .first()
+        .map(|crash| crash.error.clone())
+}
+
+#[test]
+fn test_legacy_abi() {
+    assert!(WasmVm::create(&make_ai(None), DEFAULT_MAX_MEMORY, DEFAULT_GAS_PER_TICK).is_ok());
+    assert_eq!(tick(make_ai(None)), None);
+}
+
+#[test]
+fn test_current_abi() {
+    assert!(WasmVm::create(
+        &make_ai(Some(oort_api::ABI_VERSION)),
+        DEFAULT_MAX_MEMORY,
+        DEFAULT_GAS_PER_TICK
+    )
+    .is_ok());
+    assert_eq!(tick(make_ai(Some(oort_api::ABI_VERSION))), None);
+}
+
+#[test]
+fn test_older_abis() {
+    for version in 1..oort_api::ABI_VERSION {
+        assert!(WasmVm::create(
+            &make_ai(Some(version)),
+            DEFAULT_MAX_MEMORY,
+            DEFAULT_GAS_PER_TICK
+        )
+        .is_ok());
+        assert_eq!(tick(make_ai(Some(version))), None);
+    }
+}
+
+#[test]
+fn test_gas_remaining() {
+    assert_eq!(tick(make_gas_ai()), None);
+}
+
+#[test]
+fn test_future_abi() {
+    let version = oort_api::ABI_VERSION + 1;
+    assert_eq!(
+        WasmVm::create(
+            &make_ai(Some(version)),
+            DEFAULT_MAX_MEMORY,
+            DEFAULT_GAS_PER_TICK
+        )
+        .err(),
+        Some(Error::IncompatibleAbi(version))
+    );
+}
+
+// Compiled AIs depend on this layout. If this test fails, bump
+// oort_api::ABI_VERSION and keep older versions working in the VM.
+#[test]
+fn test_system_state_layout() {
+    assert_eq!(oort_api::ABI_VERSION, 4);
+    assert_eq!(SystemState::Class as usize, 0);
+    assert_eq!(SystemState::DebugTextPointer as usize, 28);
+    assert_eq!(SystemState::DrawnTextPointer as usize, 97);
+    assert_eq!(SystemState::Id as usize, 108);
+    assert_eq!(SystemState::GasBudget as usize, 215);
+    assert_eq!(SystemState::SelectLoadout as usize, 217);
+    assert_eq!(SystemState::DisabledSubsystems as usize, 218);
+    assert_eq!(SystemState::MaxHeat as usize, 222);
+    assert_eq!(SystemState::Size as usize, 223);
+    assert_eq!(SystemState::MaxSize as usize, 256);
+    assert_eq!(oort_api::MAX_ENVIRONMENT_SIZE, 1024);
+    assert_eq!(oort_api::MAX_RADIO_MESSAGE_SIZE, 1024);
+    assert_eq!(oort_api::panic::PANIC_BUFFER_SIZE, 1024);
+}
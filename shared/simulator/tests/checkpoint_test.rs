@@ -0,0 +1,49 @@
+use oort_simulator::scenario::{self, Status};
+use oort_simulator::simulation;
+use test_log::test;
+
+fn run_to_end(mut sim: Box<simulation::Simulation>) -> (Status, u32, u64) {
+    while sim.status() == Status::Running {
+        sim.step();
+    }
+    (sim.status(), sim.tick(), sim.hash())
+}
+
+#[test]
+fn test_restore() {
+    let scenario_name = "fighter_duel";
+    let codes = scenario::load(scenario_name).solution_codes();
+    let mut sim = simulation::Simulation::new(scenario_name, 0, &codes);
+    for _ in 0..100 {
+        sim.step();
+    }
+    let checkpoint = sim.checkpoint().unwrap();
+    assert_eq!(checkpoint.tick(), 100);
+
+    let restored = simulation::Simulation::restore(&checkpoint).unwrap();
+    assert_eq!(restored.tick(), 100);
+    assert_eq!(restored.hash(), sim.hash());
+
+    let restored_result = run_to_end(restored);
+    let original_result = run_to_end(sim);
+    assert_eq!(restored_result, original_result);
+
+    // A checkpoint can be restored more than once.
+    let restored_again = simulation::Simulation::restore(&checkpoint).unwrap();
+    assert_eq!(run_to_end(restored_again), original_result);
+}
+
+#[test]
+fn test_replace_code() {
+    let scenario_name = "fighter_duel";
+    let codes = scenario::load(scenario_name).solution_codes();
+    let mut sim = simulation::Simulation::new(scenario_name, 0, &codes);
+    for _ in 0..100 {
+        sim.step();
+    }
+    let checkpoint = sim.checkpoint().unwrap();
+
+    let mut sim = simulation::Simulation::restore(&checkpoint).unwrap();
+    sim.replace_code(0, &scenario::empty_ai());
+    assert_eq!(run_to_end(sim).0, Status::Victory { team: 1 });
+}
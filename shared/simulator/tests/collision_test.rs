@@ -1,4 +1,5 @@
 use nalgebra::vector;
+use oort_simulator::scenario::SHOTS_HIT;
 use oort_simulator::ship::{fighter, missile};
 use oort_simulator::simulation::{self, Code};
 use oort_simulator::{bullet, collision, ship};
@@ -93,6 +94,7 @@ fn test_fighter_bullet_collision_same_team() {
             team: 0,
             color: BULLET_COLOR,
             ttl: 5.0,
+            hit: false,
         },
     );
 
@@ -124,6 +126,7 @@ fn test_fighter_bullet_collision_different_team() {
             team: 1,
             color: BULLET_COLOR,
             ttl: 5.0,
+            hit: false,
         },
     );
 
@@ -155,6 +158,7 @@ fn test_missile_bullet_collision_same_team() {
             team: 0,
             color: BULLET_COLOR,
             ttl: 5.0,
+            hit: false,
         },
     );
 
@@ -186,6 +190,7 @@ fn test_missile_bullet_collision_different_team() {
             team: 1,
             color: BULLET_COLOR,
             ttl: 5.0,
+            hit: false,
         },
     );
 
@@ -273,6 +278,7 @@ fn test_bullet_continuous_collision_detection() {
                 team: 1,
                 color: BULLET_COLOR,
                 ttl: 1.5,
+                hit: false,
             },
         );
 
@@ -285,6 +291,41 @@ fn test_bullet_continuous_collision_detection() {
     }
 }
 
+#[test]
+fn test_bullet_hits_counted_once() {
+    let mut sim = simulation::Simulation::new("test", 0, &[Code::None, Code::None]);
+
+    for i in 1..=3 {
+        ship::create(
+            &mut sim,
+            vector![0.0, 100.0 * i as f64],
+            vector![0.0, 0.0],
+            0.0,
+            missile(0),
+        );
+    }
+    bullet::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 1000.0],
+        bullet::BulletData {
+            mass: 10.0,
+            team: 1,
+            color: BULLET_COLOR,
+            ttl: 1.5,
+            hit: false,
+        },
+    );
+
+    for _ in 0..60 {
+        sim.step();
+    }
+
+    // The bullet destroys every missile but is a single shot.
+    assert_eq!(sim.ships.len(), 0);
+    assert_eq!(sim.score(1, SHOTS_HIT), 1.0);
+}
+
 #[test]
 fn test_ship_wall_collision() {
     let mut sim = simulation::Simulation::new("test", 0, &[Code::None]);
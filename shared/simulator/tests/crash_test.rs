@@ -1,6 +1,8 @@
 use nalgebra::vector;
+use oort_simulator::scenario;
 use oort_simulator::ship::{self, fighter};
 use oort_simulator::simulation::{self, Code};
+use oort_simulator::vm;
 use serial_test::serial;
 use std::collections::BTreeMap;
 
@@ -71,3 +73,77 @@ fn test_infinite_loop() {
         );
     });
 }
+
+fn crash_policy_sim(crash_policy: &str) -> (simulation::Simulation, ship::ShipHandle) {
+    let definition = format!(
+        r#"
+name = "crash_policy"
+victory = "none"
+initial_code = ["test"]
+crash_policy = {crash_policy}
+
+[[ships]]
+class = "fighter"
+team = 0
+"#
+    );
+    let scenario = scenario::load_definition(&definition).unwrap();
+    let codes = scenario.initial_code();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &codes);
+    let mut env = BTreeMap::new();
+    env.insert("TESTCASE".to_string(), "panic".to_string());
+    sim.update_environment(0, env);
+    let handle = sim.ships.iter().next().cloned().unwrap();
+    (sim, handle)
+}
+
+#[test]
+#[serial]
+fn test_stay_crashed() {
+    let (mut sim, handle) = crash_policy_sim(r#"{ type = "stay_crashed" }"#);
+    sim.step();
+    assert_eq!(sim.events().crashes.len(), 1);
+    let crash = &sim.events().crashes[0];
+    assert_eq!(crash.ship, u64::from(handle));
+    assert_eq!(crash.team, 0);
+    assert_eq!(
+        crash.error,
+        vm::Error::Panic("ship panicked at 'Panic!', lib.rs:17:24".to_string())
+    );
+
+    for _ in 0..10 {
+        sim.step();
+        assert!(sim.events().crashes.is_empty());
+    }
+    assert!(sim.ship(handle).data().crash_message.is_some());
+}
+
+#[test]
+#[serial]
+fn test_restart_ship() {
+    let (mut sim, handle) = crash_policy_sim(r#"{ type = "restart_ship", delay_ticks = 5 }"#);
+    sim.step();
+    assert_eq!(sim.events().crashes.len(), 1);
+    for _ in 0..4 {
+        sim.step();
+        assert!(sim.events().crashes.is_empty());
+        assert!(sim.ship(handle).data().crash_message.is_some());
+    }
+    // The restarted AI panics again.
+    sim.step();
+    assert_eq!(sim.events().crashes.len(), 1);
+    assert_eq!(sim.events().crashes[0].tick, 5);
+}
+
+#[test]
+#[serial]
+fn test_reset_team() {
+    let (mut sim, _) = crash_policy_sim(r#"{ type = "reset_team", delay_ticks = 2 }"#);
+    sim.step();
+    assert_eq!(sim.events().crashes.len(), 1);
+    sim.step();
+    assert!(sim.events().crashes.is_empty());
+    // The environment is preserved across the reset, so the AI panics again.
+    sim.step();
+    assert_eq!(sim.events().crashes.len(), 1);
+}
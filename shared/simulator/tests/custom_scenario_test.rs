@@ -0,0 +1,172 @@
+use oort_simulator::scenario::{self, Status};
+use oort_simulator::ship::ShipClass;
+use oort_simulator::simulation;
+use oort_simulator::vm;
+use test_log::test;
+
+const TOML_DEFINITION: &str = r#"
+name = "custom_duel"
+world_size = 20000
+max_ticks = 5000
+initial_code = ["reference", "reference"]
+
+[[ships]]
+class = "fighter"
+team = 0
+position = [-5000, [-1000, 1000]]
+
+[[ships]]
+class = "fighter"
+team = 1
+position = [5000, [-1000, 1000]]
+heading = 3.14159
+
+[[ships]]
+class = "asteroid"
+count = 5
+position = [[-8000, 8000], [-8000, 8000]]
+velocity = [[-50, 50], [-50, 50]]
+heading = [0, 6.28]
+
+[[lines]]
+a = [-10000, 0]
+b = [10000, 0]
+"#;
+
+#[test]
+fn test_toml() {
+    let scenario = scenario::load_definition(TOML_DEFINITION).unwrap();
+    assert_eq!(scenario.name(), "custom_duel");
+    assert_eq!(scenario.world_size(), 20000.0);
+    assert_eq!(scenario.lines().len(), 1);
+
+    let codes = scenario.initial_code();
+    assert_eq!(codes.len(), 2);
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &codes);
+    assert_eq!(sim.ships.len(), 7);
+    let fighters: Vec<_> = sim
+        .ships
+        .iter()
+        .filter(|&&handle| sim.ship(handle).data().class == ShipClass::Fighter)
+        .map(|&handle| sim.ship(handle).position().vector)
+        .collect();
+    assert_eq!(fighters.len(), 2);
+    assert_eq!(fighters[0].x, -5000.0);
+    assert!(fighters[0].y.abs() <= 1000.0);
+
+    while sim.status() == Status::Running {
+        sim.step();
+    }
+    assert!(sim.tick() <= 5000);
+}
+
+#[test]
+fn test_json() {
+    let scenario = scenario::load_definition(
+        r#"{
+            "name": "custom_json",
+            "victory": "none",
+            "max_ticks": 10,
+            "ships": [{ "class": "target", "team": 1, "position": [100, 0] }]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(scenario.name(), "custom_json");
+    let codes = scenario.initial_code();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &codes);
+    assert_eq!(sim.ships.len(), 1);
+    while sim.status() == Status::Running {
+        sim.step();
+    }
+    assert_eq!(sim.status(), Status::Draw);
+    assert_eq!(sim.tick(), 9);
+}
+
+#[test]
+fn test_invalid() {
+    assert!(scenario::load_definition("name = \"x\"\nworld_size = -1").is_err());
+    assert!(scenario::load_definition("name = \"x\"\nmax_ticks = 0").is_err());
+    assert!(scenario::load_definition("name = \"x\"\nunknown = 1").is_err());
+    assert!(scenario::load_definition(
+        "name = \"x\"\n[[ships]]\nclass = \"fighter\"\nposition = [[1, 0], 0]"
+    )
+    .is_err());
+    assert!(scenario::load_definition("name = \"x\"\n[[ships]]\nclass = \"battleship\"").is_err());
+    assert!(scenario::load_definition("name = \"x\"\n[gas_budgets]\nbattleship = 1").is_err());
+    assert!(scenario::load_definition("name = \"x\"\n[gas_budgets]\ncruiser = 0").is_err());
+}
+
+#[test]
+fn test_too_many_ships() {
+    let ships = |counts: &[u32]| {
+        let mut definition = "name = \"x\"\n".to_string();
+        for count in counts {
+            definition.push_str(&format!(
+                "[[ships]]\nclass = \"fighter\"\ncount = {count}\n"
+            ));
+        }
+        scenario::load_definition(&definition)
+    };
+    assert!(ships(&[500, 500]).is_ok());
+    assert!(ships(&[500, 501]).is_err());
+    // Would wrap around to 1 ship if the counts weren't checked.
+    assert!(ships(&[u32::MAX, 2]).is_err());
+}
+
+#[test]
+fn test_gas_budgets() {
+    let scenario = scenario::load_definition(
+        r#"
+name = "custom_gas"
+victory = "none"
+max_ticks = 100
+initial_code = ["reference", "reference"]
+
+[gas_budgets]
+cruiser = 2000000
+
+[[ships]]
+class = "cruiser"
+team = 0
+
+[[ships]]
+class = "fighter"
+team = 1
+position = [5000, 0]
+"#,
+    )
+    .unwrap();
+    let codes = scenario.initial_code();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &codes);
+    for _ in 0..10 {
+        sim.step();
+    }
+
+    let snapshot = sim.snapshot(0);
+    for &handle in sim.ships.iter() {
+        let expected_budget = match sim.ship(handle).data().class {
+            ShipClass::Cruiser => 2_000_000,
+            ShipClass::Fighter => vm::DEFAULT_GAS_PER_TICK,
+            _ => continue,
+        };
+        let usage = &snapshot.gas_usage[&handle.into()];
+        assert_eq!(usage.budget, expected_budget);
+        assert_eq!(sim.gas_budget(handle), expected_budget);
+        assert!(usage.last > 0 && usage.last <= usage.budget);
+        assert_eq!(usage.histogram.iter().sum::<u32>(), 10);
+    }
+}
+
+#[test]
+fn test_deterministic() {
+    let positions = |seed| {
+        let scenario = scenario::load_definition(TOML_DEFINITION).unwrap();
+        let sim = simulation::Simulation::new_with_scenario(scenario, seed, &[]);
+        sim.ships
+            .iter()
+            .map(|&handle| sim.ship(handle).position().vector)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(positions(1), positions(1));
+    assert_ne!(positions(1), positions(2));
+}
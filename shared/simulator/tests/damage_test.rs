@@ -1,4 +1,5 @@
 use nalgebra::vector;
+use oort_simulator::scenario;
 use oort_simulator::ship;
 use oort_simulator::ship::{cruiser, fighter, frigate, target};
 use oort_simulator::simulation::{self, Code};
@@ -10,7 +11,16 @@ fn high_health_target(team: i32) -> ship::ShipData {
     data
 }
 
-fn find_gun_dps(mut ship_data: ship::ShipData, gun: i64) -> f64 {
+fn find_gun_dps(ship_data: ship::ShipData, gun: i64) -> f64 {
+    find_gun_dps_against(ship_data, gun, high_health_target(1), 0.0)
+}
+
+fn find_gun_dps_against(
+    mut ship_data: ship::ShipData,
+    gun: i64,
+    target_data: ship::ShipData,
+    target_heading: f64,
+) -> f64 {
     let mut sim = simulation::Simulation::new("test", 0, &[Code::None, Code::None]);
 
     let offset = ship_data.guns[gun as usize].offset;
@@ -27,8 +37,8 @@ fn find_gun_dps(mut ship_data: ship::ShipData, gun: i64) -> f64 {
         &mut sim,
         vector![100.0, 0.0] + offset,
         vector![0.0, 0.0],
-        0.0,
-        high_health_target(1),
+        target_heading,
+        target_data,
     );
 
     assert!(sim.ships.contains(ship0));
@@ -63,3 +73,159 @@ fn test_dps() {
     approx::assert_abs_diff_eq!(find_gun_dps(frigate(0), 2), 73.5, epsilon = 1.0);
     approx::assert_abs_diff_eq!(find_gun_dps(cruiser(0), 0), 75.7, epsilon = 1.0);
 }
+
+#[test]
+fn test_armor() {
+    let mut armored_target = high_health_target(1);
+    armored_target.armor = Some(ship::Armor {
+        front: 0.5,
+        side: 0.25,
+        rear: 0.0,
+    });
+    let rear_dps = find_gun_dps_against(fighter(0), 0, armored_target.clone(), 0.0);
+    let front_dps = find_gun_dps_against(fighter(0), 0, armored_target, std::f64::consts::PI);
+    approx::assert_abs_diff_eq!(rear_dps, 73.5, epsilon = 1.0);
+    approx::assert_abs_diff_eq!(front_dps, 73.5 / 2.0, epsilon = 1.0);
+}
+
+#[test]
+fn test_subsystems() {
+    let mut sim = simulation::Simulation::new("test", 0, &[Code::None, Code::None]);
+
+    let mut target_data = fighter(1);
+    target_data.health = 1e6;
+    ship::add_subsystems(&mut target_data, vector![-10.0, 0.0], 15.0, 10.0);
+    let max_forward_acceleration = target_data.max_forward_acceleration;
+
+    let ship0 = ship::create(
+        &mut sim,
+        vector![-100.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(0),
+    );
+    // Facing away from the shooter, so hits land near its engines.
+    let ship1 = ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        target_data,
+    );
+
+    for _ in 0..60 {
+        sim.ship_mut(ship0).fire_gun(0);
+        sim.step();
+    }
+
+    let data = sim.ship(ship1).data();
+    assert!(data.is_disabled(oort_api::Subsystem::Engines));
+    assert!(!data.is_disabled(oort_api::Subsystem::Weapon(0)));
+    assert_eq!(
+        data.max_forward_acceleration,
+        max_forward_acceleration * 0.25
+    );
+
+    // A disabled gun can't fire.
+    for subsystem in sim.ship_mut(ship1).data_mut().subsystems.iter_mut() {
+        if subsystem.subsystem == oort_api::Subsystem::Weapon(0) {
+            subsystem.health = 0.0;
+        }
+    }
+    sim.ship_mut(ship1).fire_gun(0);
+    assert_eq!(sim.ship(ship1).data().guns[0].reload_ticks_remaining, 0);
+}
+
+#[test]
+fn test_builtin_cruiser_armor() {
+    let hull_dps = |target_heading: f64| {
+        let mut sim = simulation::Simulation::new("test", 0, &[Code::None, Code::None]);
+
+        let mut shooter_data = fighter(0);
+        shooter_data.guns[0].inaccuracy = 0.0;
+        let mut target_data = cruiser(1);
+        target_data.health = 1e6;
+        ship::add_component_damage(&mut target_data);
+
+        let ship0 = ship::create(
+            &mut sim,
+            vector![-500.0, 0.0],
+            vector![0.0, 0.0],
+            0.0,
+            shooter_data,
+        );
+        let ship1 = ship::create(
+            &mut sim,
+            vector![0.0, 0.0],
+            vector![0.0, 0.0],
+            target_heading,
+            target_data,
+        );
+
+        let ticks = 600;
+        for _ in 0..ticks {
+            sim.ship_mut(ship0).fire_gun(0);
+            sim.step();
+        }
+        for _ in 0..100 {
+            sim.step();
+        }
+
+        let data = sim.ship(ship1).data();
+        assert!(!data.subsystems.is_empty());
+        (1e6 - data.health) / (ticks as f64 * simulation::PHYSICS_TICK_LENGTH)
+    };
+
+    let armor = {
+        let mut data = cruiser(0);
+        ship::add_component_damage(&mut data);
+        data.armor.unwrap()
+    };
+    // Facing away from the shooter, so hits land on the rear.
+    let rear_dps = hull_dps(0.0);
+    let front_dps = hull_dps(std::f64::consts::PI);
+    approx::assert_abs_diff_eq!(rear_dps, 73.5 * (1.0 - armor.rear), epsilon = 1.0);
+    approx::assert_abs_diff_eq!(front_dps, 73.5 * (1.0 - armor.front), epsilon = 1.0);
+}
+
+#[test]
+fn test_component_damage_scenario() {
+    let definition = r#"
+name = "component_damage"
+victory = "none"
+component_damage = true
+
+[[ships]]
+class = "fighter"
+team = 0
+
+[[ships]]
+class = "frigate"
+team = 0
+
+[[ships]]
+class = "cruiser"
+team = 1
+"#;
+    let scenario = scenario::load_definition(definition).unwrap();
+    let sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None, Code::None]);
+    for &handle in sim.ships.iter() {
+        let data = sim.ship(handle).data();
+        if data.class == ship::ShipClass::Fighter {
+            assert!(data.armor.is_none());
+            assert!(data.subsystems.is_empty());
+        } else {
+            assert!(data.armor.is_some());
+            assert!(data
+                .subsystems
+                .iter()
+                .any(|x| x.subsystem == oort_api::Subsystem::Engines));
+        }
+    }
+
+    let scenario = scenario::load_definition(&definition.replace("true", "false")).unwrap();
+    let sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None, Code::None]);
+    for &handle in sim.ships.iter() {
+        assert!(sim.ship(handle).data().armor.is_none());
+    }
+}
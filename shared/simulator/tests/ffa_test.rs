@@ -0,0 +1,74 @@
+use oort_simulator::scenario::{self, Placements, Status, SURVIVAL_TIME};
+use oort_simulator::ship::ShipClass;
+use oort_simulator::simulation::{self, Code};
+use std::collections::HashSet;
+use test_log::test;
+
+#[test]
+fn test_placements() {
+    let placements = Placements::new(&[(0, 2), (1, 1), (2, 3), (3, 3)]).unwrap();
+    assert_eq!(placements.rank(1), Some(1));
+    assert_eq!(placements.rank(3), Some(3));
+    assert_eq!(placements.rank(4), None);
+    assert_eq!(placements.order(), vec![(1, 1), (0, 2), (2, 3), (3, 3)]);
+    assert_eq!(placements.winner(), Some(1));
+    assert_eq!(Status::Ranked { placements }.winner(), Some(1));
+
+    let tied = Placements::new(&[(0, 1), (1, 1), (2, 3)]).unwrap();
+    assert_eq!(tied.winner(), None);
+}
+
+#[test]
+fn test_invalid_placements() {
+    assert!(Placements::new(&[(-1, 1)]).is_err());
+    assert!(Placements::new(&[(scenario::MAX_TEAMS as i32, 1)]).is_err());
+    assert!(Placements::new(&[(0, 0)]).is_err());
+    assert!(Placements::new(&[(0, 1000)]).is_err());
+}
+
+#[test]
+fn test_spawn() {
+    let scenario = scenario::load("fighter_ffa");
+    let codes = scenario.initial_code();
+    assert_eq!(codes.len(), 4);
+    let sim = simulation::Simulation::new("fighter_ffa", 0, &codes);
+    let teams: HashSet<i32> = sim
+        .ships
+        .iter()
+        .map(|&handle| sim.ship(handle).data())
+        .filter(|data| data.class == ShipClass::Fighter)
+        .map(|data| data.team)
+        .collect();
+    assert_eq!(teams, (0..4).collect());
+    for &handle in sim.ships.iter() {
+        for &other in sim.ships.iter() {
+            if handle != other && sim.ship(handle).data().team != sim.ship(other).data().team {
+                let distance = (sim.ship(handle).position().vector
+                    - sim.ship(other).position().vector)
+                    .magnitude();
+                assert!(distance > 1000.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_ranked() {
+    let scenario = scenario::load("fighter_ffa");
+    let codes: Vec<Code> = (0..4).map(|_| scenario.solution()).collect();
+    let mut sim = simulation::Simulation::new("fighter_ffa", 0, &codes);
+    while sim.status() == Status::Running {
+        sim.step();
+    }
+    let placements = match sim.status() {
+        Status::Ranked { placements } => placements,
+        status => panic!("Unexpected status {status:?}"),
+    };
+    let order = placements.order();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0].1, 1);
+    for window in order.windows(2) {
+        let (a, b) = (window[0].0, window[1].0);
+        assert!(sim.score(a, SURVIVAL_TIME) >= sim.score(b, SURVIVAL_TIME));
+    }
+}
@@ -0,0 +1,195 @@
+use approx::assert_abs_diff_eq;
+use nalgebra::vector;
+use oort_simulator::laser::Laser;
+use oort_simulator::scenario::{self, SHOTS_FIRED, SHOTS_HIT};
+use oort_simulator::ship::{self, ShipHandle};
+use oort_simulator::simulation::{self, Code, Simulation};
+use std::f64::consts::PI;
+use test_log::test;
+
+// Fire and aim index of the first laser in the point defense loadout.
+const LASER: i64 = 1;
+
+fn point_defense_frigate(sim: &mut Simulation) -> ShipHandle {
+    let mut data = ship::frigate(0);
+    let loadout = data.loadouts[1].clone();
+    assert_eq!(loadout.name, "point_defense");
+    data.guns = loadout.guns();
+    data.lasers = loadout.lasers();
+    data.missile_launchers = loadout.missile_launchers();
+    ship::create(sim, vector![0.0, 0.0], vector![0.0, 0.0], 0.0, data)
+}
+
+#[test]
+fn test_damage_falloff() {
+    let laser = Laser::default();
+    assert_eq!(laser.damage_at(0.0), laser.damage);
+    assert_eq!(laser.damage_at(laser.falloff_range), laser.damage);
+    assert_abs_diff_eq!(
+        laser.damage_at((laser.falloff_range + laser.max_range) / 2.0),
+        laser.damage / 2.0,
+        epsilon = 1e-9
+    );
+    assert_eq!(laser.damage_at(laser.max_range), 0.0);
+    assert_eq!(laser.damage_at(laser.max_range * 2.0), 0.0);
+}
+
+#[test]
+fn test_hit() {
+    let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+    let frigate = point_defense_frigate(&mut sim);
+    // In line with the laser mounted at y=15.
+    let target = ship::create(
+        &mut sim,
+        vector![1000.0, 15.0],
+        vector![0.0, 0.0],
+        0.0,
+        ship::fighter(1),
+    );
+
+    sim.ship_mut(frigate).aim(LASER, 0.0);
+    sim.ship_mut(frigate).fire(LASER);
+    sim.step();
+    assert_eq!(sim.snapshot(0).beams.len(), 1);
+    assert!(sim.ship(target).data().health < 100.0);
+    assert_eq!(sim.bullets.len(), 0);
+
+    for _ in 0..60 {
+        if !sim.ships.contains(target) {
+            break;
+        }
+        sim.ship_mut(frigate).aim(LASER, 0.0);
+        sim.ship_mut(frigate).fire(LASER);
+        sim.step();
+    }
+    assert!(!sim.ships.contains(target));
+
+    // Nothing is drawn on ticks the laser doesn't fire.
+    sim.step();
+    assert_eq!(sim.snapshot(0).beams.len(), 0);
+}
+
+#[test]
+fn test_friendly_ships() {
+    let mut sim = Simulation::new("test", 0, &[Code::None, Code::None]);
+    let frigate = point_defense_frigate(&mut sim);
+    let friend = ship::create(
+        &mut sim,
+        vector![500.0, 15.0],
+        vector![0.0, 0.0],
+        0.0,
+        ship::fighter(0),
+    );
+
+    sim.ship_mut(frigate).fire(LASER);
+    sim.step();
+    assert_eq!(sim.ship(friend).data().health, 100.0);
+    assert_eq!(sim.score(0, SHOTS_FIRED), 1.0);
+    assert_eq!(sim.score(0, SHOTS_HIT), 0.0);
+
+    // The beam passes through the friendly ship to the enemy behind it.
+    let enemy = ship::create(
+        &mut sim,
+        vector![1000.0, 15.0],
+        vector![0.0, 0.0],
+        0.0,
+        ship::fighter(1),
+    );
+    sim.ship_mut(frigate).fire(LASER);
+    sim.step();
+    assert_eq!(sim.ship(friend).data().health, 100.0);
+    assert!(sim.ship(enemy).data().health < 100.0);
+    assert_eq!(sim.score(0, SHOTS_FIRED), 2.0);
+    assert_eq!(sim.score(0, SHOTS_HIT), 1.0);
+}
+
+#[test]
+fn test_overheat() {
+    let mut sim = Simulation::new("test", 0, &[Code::None]);
+    let frigate = point_defense_frigate(&mut sim);
+    let max_heat = Laser::default().max_heat;
+
+    let mut ticks = 0;
+    while !sim.ship(frigate).data().lasers[0].overheated {
+        assert!(ticks < 1000);
+        assert_eq!(sim.ship(frigate).get_reload_ticks(LASER as usize), 0);
+        sim.ship_mut(frigate).fire(LASER);
+        sim.step();
+        ticks += 1;
+    }
+    // Cooling lets it fire for longer than max_heat / heat_per_tick ticks.
+    assert!(ticks as f64 > max_heat);
+
+    assert!(sim.ship(frigate).get_reload_ticks(LASER as usize) > 0);
+    while sim.ship(frigate).get_reload_ticks(LASER as usize) > 0 {
+        assert!(ticks < 1000);
+        sim.ship_mut(frigate).fire(LASER);
+        sim.step();
+        assert_eq!(sim.snapshot(0).beams.len(), 0);
+        ticks += 1;
+    }
+    sim.ship_mut(frigate).fire(LASER);
+    sim.step();
+    assert_eq!(sim.snapshot(0).beams.len(), 1);
+}
+
+#[test]
+fn test_traverse() {
+    let mut sim = Simulation::new("test", 0, &[Code::None]);
+    let frigate = point_defense_frigate(&mut sim);
+    let traverse_rate = Laser::default().traverse_rate;
+
+    sim.ship_mut(frigate).aim(LASER, PI / 2.0);
+    sim.step();
+    assert_abs_diff_eq!(
+        sim.ship(frigate).data().lasers[0].heading,
+        traverse_rate * simulation::PHYSICS_TICK_LENGTH,
+        epsilon = 1e-9
+    );
+
+    for _ in 0..60 {
+        sim.ship_mut(frigate).aim(LASER, PI / 2.0);
+        sim.step();
+    }
+    assert_abs_diff_eq!(
+        sim.ship(frigate).data().lasers[0].heading,
+        PI / 2.0,
+        epsilon = 1e-9
+    );
+
+    // Takes the short way around.
+    for _ in 0..10 {
+        sim.ship_mut(frigate).aim(LASER, 0.0);
+        sim.step();
+    }
+    assert!(sim.ship(frigate).data().lasers[0].heading < PI / 2.0);
+}
+
+#[test]
+fn test_point_defense_loadout() {
+    let scenario = scenario::load_definition(
+        r#"
+name = "point_defense"
+victory = "none"
+
+[loadouts]
+frigate = ["standard", "point_defense"]
+
+[[ships]]
+class = "frigate"
+team = 0
+"#,
+    )
+    .unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    sim.ship_mut(handle).select_loadout(1);
+    let data = sim.ship(handle).data();
+    assert_eq!(data.guns.len(), 1);
+    assert_eq!(data.lasers.len(), 2);
+    assert_eq!(data.missile_launchers.len(), 1);
+
+    // The missile launcher keeps the same index as in the standard loadout.
+    sim.ship_mut(handle).fire(3);
+    assert_eq!(sim.ships.len(), 2);
+}
@@ -0,0 +1,94 @@
+use oort_simulator::scenario;
+use oort_simulator::ship;
+use oort_simulator::simulation::{self, Code};
+use test_log::test;
+
+const DEFINITION: &str = r#"
+name = "loadouts"
+victory = "none"
+
+[loadouts]
+fighter = ["standard", "gunship"]
+
+[[ships]]
+class = "fighter"
+team = 0
+
+[[ships]]
+class = "fighter"
+team = 0
+position = [100, 0]
+"#;
+
+#[test]
+fn test_builtin_loadouts() {
+    for data in [ship::fighter(0), ship::frigate(0), ship::cruiser(0)] {
+        for loadout in data.loadouts.iter() {
+            assert!(
+                loadout.weapons.len() <= 4,
+                "{:?} {}",
+                data.class,
+                loadout.name
+            );
+        }
+        if let Some(standard) = data.loadouts.first() {
+            assert_eq!(standard.name, "standard");
+            assert_eq!(standard.guns().len(), data.guns.len());
+            assert_eq!(standard.lasers().len(), data.lasers.len());
+            assert_eq!(
+                standard.missile_launchers().len(),
+                data.missile_launchers.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_select_loadout() {
+    let scenario = scenario::load_definition(DEFINITION).unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handles = sim.ships.iter().copied().collect::<Vec<_>>();
+
+    // Gunship is allowed.
+    sim.ship_mut(handles[0]).select_loadout(1);
+    let data = sim.ship(handles[0]).data();
+    assert_eq!(data.guns.len(), 2);
+    assert_eq!(data.missile_launchers.len(), 0);
+    assert!(data.loadout_locked);
+
+    // The loadout can't be changed again.
+    sim.ship_mut(handles[0]).select_loadout(0);
+    assert_eq!(sim.ship(handles[0]).data().guns.len(), 2);
+
+    // Missile boat isn't allowed by the scenario.
+    sim.ship_mut(handles[1]).select_loadout(2);
+    let data = sim.ship(handles[1]).data();
+    assert_eq!(data.guns.len(), 1);
+    assert_eq!(data.missile_launchers.len(), 1);
+
+    // Out of range.
+    sim.ship_mut(handles[1]).select_loadout(10);
+    assert_eq!(sim.ship(handles[1]).data().guns.len(), 1);
+}
+
+#[test]
+fn test_builtin_scenario_disallows_loadouts() {
+    let mut sim = simulation::Simulation::new("fighter_duel", 0, &[Code::None, Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    let guns = sim.ship(handle).data().guns.len();
+    sim.ship_mut(handle).select_loadout(1);
+    assert_eq!(sim.ship(handle).data().guns.len(), guns);
+}
+
+#[test]
+fn test_invalid() {
+    assert!(scenario::load_definition("name = \"x\"\n[loadouts]\nfighter = [\"bogus\"]").is_err());
+    assert!(
+        scenario::load_definition("name = \"x\"\n[loadouts]\nbattleship = [\"standard\"]").is_err()
+    );
+    // Cruisers have no preset loadouts.
+    assert!(ship::cruiser(0).loadouts.is_empty());
+    assert!(
+        scenario::load_definition("name = \"x\"\n[loadouts]\ncruiser = [\"standard\"]").is_err()
+    );
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "native")]
+use nalgebra::vector;
+use oort_api::prelude::*;
+use oort_simulator::ship::{self, fighter};
+use oort_simulator::simulation::{self, Code};
+use oort_simulator::vm::native::{NativeCode, NativeShip};
+use oort_simulator::vm::Error;
+use test_log::test;
+
+struct Ship {
+    ticks: u32,
+}
+
+impl NativeShip for Ship {
+    fn tick(&mut self) {
+        self.ticks += 1;
+        debug!("id={} ticks={}", id(), self.ticks);
+        accelerate(vec2(100.0, 0.0));
+    }
+}
+
+struct PanickingShip;
+
+impl NativeShip for PanickingShip {
+    fn tick(&mut self) {
+        panic!("oops");
+    }
+}
+
+struct RadioShip {
+    ticks: u32,
+}
+
+impl NativeShip for RadioShip {
+    fn tick(&mut self) {
+        self.ticks += 1;
+        match self.ticks {
+            1 => send([1.0, 2.0, 3.0, 4.0]),
+            2 => {
+                assert_eq!(receive(), Some([1.0, 2.0, 3.0, 4.0]));
+                assert_eq!(receive_message_bytes().map(|bytes| bytes.len()), Some(32));
+                send_bytes(&[]);
+            }
+            3 => {
+                // An empty message, not the one sent on the first tick.
+                assert_eq!(receive_message_bytes(), Some(&[][..]));
+                assert_eq!(receive_bytes(), Some([0; 32]));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn run(code: Code, ticks: u32) -> Box<simulation::Simulation> {
+    let mut sim = simulation::Simulation::new("test", 0, &[code, Code::None]);
+    ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(0),
+    );
+    for _ in 0..ticks {
+        sim.step();
+    }
+    sim
+}
+
+#[test]
+fn test_native() {
+    let code = Code::Native(NativeCode::new("test", || Box::new(Ship { ticks: 0 })));
+    let sim = run(code, 10);
+    let handle = *sim.ships.iter().next().unwrap();
+    assert!(sim.ship(handle).velocity().x > 0.0);
+    assert_eq!(
+        sim.events()
+            .debug_text
+            .get(&u64::from(handle))
+            .map(|s| s.as_str()),
+        Some("id=1 ticks=10\n")
+    );
+    assert!(sim.events().crashes.is_empty());
+}
+
+#[test]
+fn test_native_panic() {
+    let code = Code::Native(NativeCode::new("panic", || Box::new(PanickingShip)));
+    let sim = run(code, 1);
+    assert_eq!(
+        sim.events()
+            .crashes
+            .first()
+            .map(|crash| crash.error.clone()),
+        Some(Error::Panic("ship panicked at 'oops'".to_string()))
+    );
+}
+
+#[test]
+fn test_native_radio() {
+    let code = Code::Native(NativeCode::new("radio", || {
+        Box::new(RadioShip { ticks: 0 })
+    }));
+    let sim = run(code, 4);
+    assert!(sim.events().crashes.is_empty());
+}
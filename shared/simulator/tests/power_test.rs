@@ -0,0 +1,149 @@
+use oort_simulator::scenario;
+use oort_simulator::ship::{self, Power};
+use oort_simulator::simulation::{self, Code, Simulation};
+use test_log::test;
+
+const DEFINITION: &str = r#"
+name = "power"
+victory = "none"
+power = true
+
+[[ships]]
+class = "fighter"
+team = 0
+"#;
+
+fn count_shots(sim: &mut Simulation, ticks: u32) -> u32 {
+    let handle = *sim.ships.iter().next().unwrap();
+    let mut shots = 0;
+    for _ in 0..ticks {
+        let ready = sim.ship(handle).data().guns[0].reload_ticks_remaining == 0;
+        sim.ship_mut(handle).fire_gun(0);
+        if ready && sim.ship(handle).data().guns[0].reload_ticks_remaining > 0 {
+            shots += 1;
+        }
+        sim.step();
+    }
+    shots
+}
+
+#[test]
+fn test_power() {
+    let mut power = Power::new(100.0, 0.0, 50.0, 10.0);
+    assert!(power.use_energy(20.0, 10.0));
+    assert_eq!(power.throttle(), 1.0);
+    assert!(power.use_energy(20.0, 27.5));
+    assert_eq!(power.energy, 60.0);
+    assert_eq!(power.heat, 37.5);
+    assert_eq!(power.throttle(), 0.5);
+    assert!(power.use_energy(20.0, 100.0));
+    assert_eq!(power.heat, 50.0);
+    assert_eq!(power.throttle(), 0.0);
+
+    let mut power = Power::new(10.0, 0.0, 100.0, 10.0);
+    assert!(!power.use_energy(20.0, 0.0));
+    assert_eq!(power.energy, 10.0);
+}
+
+#[test]
+fn test_power_scenario() {
+    let scenario = scenario::load_definition(DEFINITION).unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    let power = sim.ship(handle).data().power.as_ref().unwrap();
+    assert_eq!(power.energy, power.max_energy);
+    let limited = count_shots(&mut sim, 600);
+
+    let scenario =
+        scenario::load_definition(&DEFINITION.replace("power = true", "power = false")).unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    assert!(sim.ship(handle).data().power.is_none());
+    let unlimited = count_shots(&mut sim, 600);
+
+    assert!(limited > 0);
+    assert!(
+        limited < unlimited,
+        "limited={limited} unlimited={unlimited}"
+    );
+}
+
+#[test]
+fn test_heat_recovery() {
+    let scenario = scenario::load_definition(DEFINITION).unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    let mut power = ship::default_power(ship::ShipClass::Fighter).unwrap();
+    power.heat = power.max_heat;
+    sim.ship_mut(handle).data_mut().power = Some(power);
+
+    sim.ship_mut(handle).fire_gun(0);
+    assert_eq!(sim.ship(handle).data().guns[0].reload_ticks_remaining, 0);
+
+    for _ in 0..600 {
+        sim.step();
+    }
+    let power = sim.ship(handle).data().power.as_ref().unwrap();
+    assert_eq!(power.throttle(), 1.0);
+    sim.ship_mut(handle).fire_gun(0);
+    assert!(sim.ship(handle).data().guns[0].reload_ticks_remaining > 0);
+}
+
+#[test]
+fn test_heat_limits_fire_rate() {
+    // Plenty of energy, so only heat can slow the guns down.
+    let mut power = ship::default_power(ship::ShipClass::Fighter).unwrap();
+    power.max_energy = 1e9;
+    power.energy = 1e9;
+    power.recharge_rate = 1e9;
+
+    let scenario = scenario::load_definition(DEFINITION).unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    sim.ship_mut(handle).data_mut().power = Some(power.clone());
+    let hot = count_shots(&mut sim, 600);
+    let power_after = sim.ship(handle).data().power.as_ref().unwrap();
+    assert!(power_after.energy > power.max_energy / 2.0);
+    assert!(power_after.throttle() < 1.0, "heat={}", power_after.heat);
+
+    // The same ship fires more when it can't heat up.
+    let scenario = scenario::load_definition(DEFINITION).unwrap();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+    let handle = *sim.ships.iter().next().unwrap();
+    power.cooling_rate = 1e9;
+    sim.ship_mut(handle).data_mut().power = Some(power);
+    let cool = count_shots(&mut sim, 600);
+
+    assert!(hot > 0);
+    assert!(hot < cool, "hot={hot} cool={cool}");
+}
+
+#[test]
+fn test_heat_throttles_radar() {
+    let energy_used = |heat_fraction: f64| {
+        let scenario = scenario::load_definition(DEFINITION).unwrap();
+        let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &[Code::None]);
+        let handle = *sim.ships.iter().next().unwrap();
+        let mut power = ship::default_power(ship::ShipClass::Fighter).unwrap();
+        power.recharge_rate = 0.0;
+        power.cooling_rate = 0.0;
+        power.heat = heat_fraction * power.max_heat;
+        sim.ship_mut(handle).data_mut().power = Some(power.clone());
+        sim.step();
+        power.energy - sim.ship(handle).data().power.as_ref().unwrap().energy
+    };
+
+    let cool = energy_used(0.0);
+    let hot = energy_used(0.9);
+    assert!(cool > 0.0);
+    assert!(hot > 0.0 && hot < cool, "hot={hot} cool={cool}");
+    assert_eq!(energy_used(1.0), 0.0);
+}
+
+#[test]
+fn test_builtin_scenario_unlimited() {
+    let sim = simulation::Simulation::new("fighter_duel", 0, &[Code::None, Code::None]);
+    for &handle in sim.ships.iter() {
+        assert!(sim.ship(handle).data().power.is_none());
+    }
+}
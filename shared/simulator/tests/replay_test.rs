@@ -0,0 +1,94 @@
+use oort_simulator::scenario;
+use oort_simulator::simulation;
+use oort_simulator::snapshot::{Replay, Snapshot};
+use test_log::test;
+
+fn strip(mut snapshot: Snapshot) -> Snapshot {
+    snapshot.debug_lines.clear();
+    snapshot.debug_text.clear();
+    snapshot.drawn_text.clear();
+    snapshot.ships.sort_by_key(|ship| ship.id);
+    snapshot
+}
+
+fn check_equal(a: Snapshot, b: Snapshot) {
+    let a = strip(a);
+    let b = strip(b);
+    assert_eq!(a.time, b.time);
+    assert_eq!(a.status, b.status);
+    assert_eq!(a.ships, b.ships);
+    assert_eq!(a.bullets.len(), b.bullets.len());
+}
+
+#[test]
+fn test_replay() {
+    let scenario_name = "fighter_duel";
+    let codes = scenario::load(scenario_name).solution_codes();
+    let mut sim = simulation::Simulation::new(scenario_name, 0, &codes);
+    sim.start_recording();
+
+    let mut snapshots = vec![sim.snapshot(0)];
+    for _ in 0..200 {
+        sim.step();
+        snapshots.push(sim.snapshot(0));
+    }
+
+    let replay = sim.finish_recording().unwrap();
+    assert_eq!(replay.scenario_name, scenario_name);
+    assert_eq!(replay.seed, 0);
+    assert_eq!(replay.code_hashes, sim.code_hashes());
+    assert_eq!(replay.num_frames(), snapshots.len());
+
+    for tick in [0, 1, 59, 60, 61, 150, 200] {
+        check_equal(
+            replay.snapshot(tick).unwrap(),
+            snapshots[tick as usize].clone(),
+        );
+    }
+    assert!(replay.snapshot(201).is_none());
+
+    let decoded = Replay::decode(&replay.encode().unwrap()).unwrap();
+    assert_eq!(decoded.num_frames(), replay.num_frames());
+    check_equal(decoded.snapshot(123).unwrap(), snapshots[123].clone());
+}
+
+#[test]
+fn test_replay_mid_game() {
+    let scenario_name = "fighter_duel";
+    let codes = scenario::load(scenario_name).solution_codes();
+    let mut sim = simulation::Simulation::new(scenario_name, 0, &codes);
+    for _ in 0..100 {
+        sim.step();
+    }
+    sim.start_recording();
+
+    let mut snapshots = vec![sim.snapshot(0)];
+    for _ in 0..100 {
+        sim.step();
+        snapshots.push(sim.snapshot(0));
+    }
+
+    let replay = sim.finish_recording().unwrap();
+    assert_eq!(replay.start_tick, 100);
+    assert!(replay.snapshot(99).is_none());
+    for tick in [100, 101, 160, 200] {
+        check_equal(
+            replay.snapshot(tick).unwrap(),
+            snapshots[tick as usize - 100].clone(),
+        );
+    }
+    assert!(replay.snapshot(201).is_none());
+}
+
+#[test]
+fn test_code_hashes() {
+    let codes = scenario::load("fighter_duel").solution_codes();
+    let sim = simulation::Simulation::new("fighter_duel", 0, &codes);
+    let hashes = sim.code_hashes();
+    assert_eq!(hashes.len(), codes.len());
+    assert!(hashes.iter().all(|hash| hash.len() == 64));
+
+    // The same code hashes the same way in a new simulation.
+    let sim = simulation::Simulation::new("fighter_duel", 1, &codes);
+    assert_eq!(sim.code_hashes(), hashes);
+}
@@ -0,0 +1,221 @@
+use nalgebra::vector;
+use oort_simulator::scenario::{self, Status};
+use oort_simulator::ship::{self, ShipClass};
+use oort_simulator::ship_definition::{BaseClass, ShipClasses, ShipDefinition};
+use oort_simulator::simulation::Code;
+use oort_simulator::snapshot::Snapshot;
+use oort_simulator::{model, simulation};
+use std::collections::BTreeMap;
+use test_log::test;
+
+const HEAVY_FIGHTER: &str = r#"
+name = "heavy_fighter"
+base = "fighter"
+health = 300
+mass = 20000
+max_forward_acceleration = 40
+max_backward_acceleration = 20
+max_lateral_acceleration = 20
+max_angular_acceleration = 3.14
+hull = [[-10, -10], [15, 0], [-10, 10]]
+
+[[guns]]
+offset = [15, 0]
+reload_ticks = 6
+magazine_size = 20
+bullet_mass = 0.2
+
+[[missile_launchers]]
+class = "missile"
+offset = [15, 0]
+
+[radar]
+
+[[radios]]
+
+[[abilities]]
+ability = "Boost"
+active_time = 2
+reload_time = 10
+"#;
+
+#[test]
+fn test_register() {
+    let definition = ShipDefinition::from_toml(HEAVY_FIGHTER).unwrap();
+    let mut classes = ShipClasses::new();
+    let class = classes
+        .register(definition.clone(), &BTreeMap::new())
+        .unwrap();
+    assert!(matches!(
+        class,
+        ShipClass::Custom {
+            base: BaseClass::Fighter,
+            ..
+        }
+    ));
+    assert_eq!(classes.name(class), "heavy_fighter");
+    assert_eq!(classes.name(ShipClass::Frigate), "frigate");
+    assert_eq!(class.base(), ShipClass::Fighter);
+    assert_eq!(ShipClass::Frigate.base(), ShipClass::Frigate);
+    assert_eq!(model::load(class, &classes).len(), 3);
+    assert_eq!(model::radius(class, &classes), 15.0);
+
+    // Registering the same definition again reuses the class.
+    assert_eq!(classes.register(definition, &BTreeMap::new()), Ok(class));
+
+    let data = classes.ship_data(class, 1).unwrap();
+    assert_eq!(data.class, class);
+    assert_eq!(data.team, 1);
+    assert_eq!(data.health, 300.0);
+    assert_eq!(data.guns.len(), 1);
+    assert_eq!(data.guns[0].reload_ticks, 6);
+    assert_eq!(data.guns[0].speed, 1000.0);
+    assert_eq!(data.missile_launchers.len(), 1);
+    assert_eq!(data.missile_launchers[0].class, ShipClass::Missile);
+    assert!(data.radar.is_some());
+    assert_eq!(data.radios.len(), 1);
+    assert_eq!(data.abilities.len(), 1);
+    assert!(classes.ship_data(ShipClass::Fighter, 0).is_none());
+}
+
+#[test]
+fn test_invalid() {
+    let check = |replace: &str, with: &str| {
+        let s = HEAVY_FIGHTER.replace(replace, with);
+        assert_ne!(s, HEAVY_FIGHTER);
+        assert!(ShipDefinition::from_toml(&s).is_err(), "{with}");
+    };
+    check("health = 300", "health = 0");
+    check("mass = 20000", "mass = -1");
+    check(
+        "hull = [[-10, -10], [15, 0], [-10, 10]]",
+        "hull = [[-10, 0], [15, 0]]",
+    );
+    check(
+        "hull = [[-10, -10], [15, 0], [-10, 10]]",
+        "hull = [[-10, 0], [0, 0], [10, 0]]",
+    );
+    check("magazine_size = 20", "magazine_size = 0");
+    check("ability = \"Boost\"", "ability = \"Teleport\"");
+    check("base = \"fighter\"", "base = \"battleship\"");
+
+    let definition = ShipDefinition::from_toml(
+        &HEAVY_FIGHTER.replace("class = \"missile\"", "class = \"unregistered_missile\""),
+    )
+    .unwrap();
+    assert!(ShipClasses::new()
+        .register(definition, &BTreeMap::new())
+        .is_err());
+}
+
+#[test]
+fn test_custom_scenario() {
+    let class = serde_json::to_string(&ShipDefinition::from_toml(HEAVY_FIGHTER).unwrap()).unwrap();
+    let scenario = scenario::load_definition(&format!(
+        r#"{{
+            "name": "custom_classes",
+            "max_ticks": 3000,
+            "initial_code": ["reference", "reference"],
+            "gas_budgets": {{ "heavy_fighter": 2000000 }},
+            "ships": [
+                {{ "class": "heavy_fighter", "team": 0, "position": [-2000, 0] }},
+                {{ "class": "fighter", "team": 1, "position": [2000, 0], "heading": 3.14159 }}
+            ],
+            "ship_classes": [{class}]
+        }}"#
+    ))
+    .unwrap();
+
+    let codes = scenario.initial_code();
+    let mut sim = simulation::Simulation::new_with_scenario(scenario, 0, &codes);
+    let heavy_fighter = sim
+        .ships
+        .iter()
+        .copied()
+        .find(|&handle| sim.ship_classes().name(sim.ship(handle).data().class) == "heavy_fighter")
+        .unwrap();
+    assert_eq!(sim.gas_budget(heavy_fighter), 2_000_000);
+    assert_eq!(sim.ship(heavy_fighter).data().health, 300.0);
+
+    while sim.status() == Status::Running {
+        sim.step();
+    }
+    assert!(sim.tick() <= 3000);
+}
+
+#[test]
+fn test_custom_scenario_invalid() {
+    assert!(
+        scenario::load_definition("name = \"x\"\n[[ships]]\nclass = \"heavy_fighter\"").is_err()
+    );
+    assert!(scenario::load_definition(
+        "name = \"x\"\n[[ship_classes]]\nname = \"fighter\"\nbase = \"fighter\"\nhealth = 1\nmass = 1\nmax_forward_acceleration = 1\nmax_backward_acceleration = 1\nmax_lateral_acceleration = 1\nmax_angular_acceleration = 1\nhull = [[0, 0], [1, 0], [0, 1]]"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_snapshot_classes() {
+    let definition = ShipDefinition::from_toml(HEAVY_FIGHTER).unwrap();
+    let mut sim = simulation::Simulation::new("test", 0, &[Code::None]);
+    let class = sim
+        .ship_classes_mut()
+        .register(definition, &BTreeMap::new())
+        .unwrap();
+    let data = sim.ship_classes().ship_data(class, 0).unwrap();
+    ship::create(&mut sim, vector![0.0, 0.0], vector![0.0, 0.0], 0.0, data);
+
+    // Definitions are sent once per stream rather than with every snapshot.
+    let mut snapshot = sim.snapshot(0);
+    assert!(snapshot.ship_classes.is_empty());
+    snapshot.ship_classes = sim.ship_classes().definitions();
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(snapshot.ships[0].class, class);
+    assert_eq!(snapshot.ship_classes.len(), 1);
+    assert_eq!(snapshot.ship_classes[0].definition.name, "heavy_fighter");
+
+    // The class ID is derived from the definition, so loading it elsewhere
+    // resolves the same class.
+    let mut classes = ShipClasses::new();
+    assert_eq!(classes.load(&snapshot.ship_classes), Ok(()));
+    assert_eq!(classes.lookup(class), Some(&snapshot.ship_classes[0]));
+    assert_eq!(classes.name(class), "heavy_fighter");
+}
+
+#[test]
+fn test_replay_classes() {
+    let definition = ShipDefinition::from_toml(HEAVY_FIGHTER).unwrap();
+    let mut sim = simulation::Simulation::new("test", 0, &[Code::None]);
+    let class = sim
+        .ship_classes_mut()
+        .register(definition, &BTreeMap::new())
+        .unwrap();
+    let data = sim.ship_classes().ship_data(class, 0).unwrap();
+    ship::create(&mut sim, vector![0.0, 0.0], vector![0.0, 0.0], 0.0, data);
+
+    sim.start_recording();
+    for _ in 0..10 {
+        sim.step();
+    }
+    let replay = sim.finish_recording().unwrap();
+    assert_eq!(replay.ship_classes, sim.ship_classes().definitions());
+    assert!(replay.snapshot(0).unwrap().ship_classes.is_empty());
+}
+
+#[test]
+fn test_unregistered_class() {
+    let classes = ShipClasses::new();
+    let class = ShipClass::Custom {
+        id: 0,
+        base: BaseClass::Frigate,
+    };
+    assert!(classes.lookup(class).is_none());
+    assert!(classes.ship_data(class, 0).is_none());
+    assert_eq!(classes.name(class), "frigate");
+    assert_eq!(
+        model::load(class, &classes),
+        model::load(ShipClass::Frigate, &classes)
+    );
+}
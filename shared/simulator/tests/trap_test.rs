@@ -0,0 +1,165 @@
+use nalgebra::vector;
+use oort_simulator::ship::{self, fighter};
+use oort_simulator::simulation::{self, Code};
+use oort_simulator::vm::{Error, DEFAULT_GAS_PER_TICK};
+use test_log::test;
+
+// Minimal AI that runs the given instructions on each tick.
+fn make_ai(tick_body: &str) -> Code {
+    let wat = format!(
+        r#"
+(module
+  (memory (export "memory") 2)
+  (global (export "SYSTEM_STATE") i32 (i32.const 1024))
+  (global (export "ENVIRONMENT") i32 (i32.const 8192))
+  (global (export "PANIC_BUFFER") i32 (i32.const 16384))
+  (func (export "export_initialize"))
+  (func (export "export_delete_ship") (param i32))
+  (func $recurse (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.add
+    call $recurse)
+  (func $count (param i32) (result i32)
+    local.get 0
+    (if (result i32)
+      (then local.get 0 i32.const 1 i32.sub call $count)
+      (else i32.const 0)))
+  (func (export "export_tick_ship") (param i32)
+    {tick_body}))
+"#
+    );
+    Code::Wasm(
+        wabt::Wat2Wasm::new()
+            .convert(wat)
+            .unwrap()
+            .as_ref()
+            .to_vec(),
+    )
+}
+
+fn crash_error(tick_body: &str) -> Option<Error> {
+    let mut sim = simulation::Simulation::new("test", 0, &[make_ai(tick_body), Code::None]);
+    ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(0),
+    );
+    sim.step();
+    sim.events()
+        .crashes
+        .first()
+        .map(|crash| crash.error.clone())
+}
+
+#[test]
+fn test_no_trap() {
+    assert_eq!(crash_error("nop"), None);
+}
+
+#[test]
+fn test_out_of_gas() {
+    assert_eq!(crash_error("(loop br 0)"), Some(Error::OutOfGas));
+}
+
+#[test]
+fn test_stack_overflow() {
+    assert_eq!(
+        crash_error("i32.const 0 call $recurse drop"),
+        Some(Error::StackOverflow)
+    );
+}
+
+#[test]
+fn test_recursion() {
+    // Deeper than the original limit of 1000 calls.
+    assert_eq!(crash_error("i32.const 5000 call $count drop"), None);
+}
+
+#[test]
+fn test_out_of_memory() {
+    assert_eq!(
+        crash_error("i32.const 10000 memory.grow i32.const -1 i32.eq (if (then unreachable))"),
+        Some(Error::OutOfMemory)
+    );
+}
+
+#[test]
+fn test_division_by_zero() {
+    assert_eq!(
+        crash_error("i32.const 1 i32.const 0 i32.div_s drop"),
+        Some(Error::DivisionByZero)
+    );
+}
+
+#[test]
+fn test_unreachable() {
+    assert_eq!(crash_error("unreachable"), Some(Error::Unreachable));
+}
+
+#[test]
+fn test_memory_limit() {
+    let wasm = match make_ai("nop") {
+        Code::Wasm(wasm) => wasm,
+        _ => unreachable!(),
+    };
+    assert!(oort_simulator::vm::WasmVm::create(
+        &Code::Wasm(wasm.clone()),
+        1 << 20,
+        DEFAULT_GAS_PER_TICK,
+    )
+    .is_ok());
+    assert_eq!(
+        oort_simulator::vm::WasmVm::create(&Code::Wasm(wasm), 65536, DEFAULT_GAS_PER_TICK).err(),
+        Some(Error::OutOfMemory)
+    );
+}
+
+#[cfg(feature = "precompile")]
+#[test]
+fn test_precompiled_memory_limit() {
+    let wasm = match make_ai("nop") {
+        Code::Wasm(wasm) => wasm,
+        _ => unreachable!(),
+    };
+    // Precompiled with the default limit, which is higher than these.
+    let code = oort_simulator::vm::precompile(&wasm).unwrap();
+    assert!(oort_simulator::vm::WasmVm::create(&code, 1 << 20, DEFAULT_GAS_PER_TICK).is_ok());
+    assert_eq!(
+        oort_simulator::vm::WasmVm::create(&code, 65536, DEFAULT_GAS_PER_TICK).err(),
+        Some(Error::OutOfMemory)
+    );
+}
+
+#[cfg(feature = "precompile")]
+#[test]
+fn test_precompiled_without_memory_limit() {
+    // Like a module precompiled before the limiter exported the memory limit.
+    let wat = r#"
+(module
+  (memory (export "memory") 2)
+  (global (export "SYSTEM_STATE") i32 (i32.const 1024))
+  (global (export "ENVIRONMENT") i32 (i32.const 8192))
+  (global (export "PANIC_BUFFER") i32 (i32.const 16384))
+  (func (export "export_initialize"))
+  (func (export "export_delete_ship") (param i32))
+  (func (export "export_tick_ship") (param i32))
+  (func (export "reset_gas") (param i32))
+  (func (export "get_gas") (result i32)
+    i32.const 1000000))
+"#;
+    let wasm = wabt::Wat2Wasm::new()
+        .convert(wat)
+        .unwrap()
+        .as_ref()
+        .to_vec();
+    let module = wasmer::Module::new(&wasmer::Store::default(), wasm).unwrap();
+    let code = Code::Precompiled(module.serialize().unwrap());
+    assert!(oort_simulator::vm::WasmVm::create(&code, 1 << 20, DEFAULT_GAS_PER_TICK).is_ok());
+    assert_eq!(
+        oort_simulator::vm::WasmVm::create(&code, 65536, DEFAULT_GAS_PER_TICK).err(),
+        Some(Error::OutOfMemory)
+    );
+}
@@ -0,0 +1,205 @@
+use nalgebra::vector;
+use oort_simulator::scenario::{
+    capture_time_counter, Condition, Status, VictoryConditions, CAPTURE_TIME, DAMAGE_DEALT,
+};
+use oort_simulator::ship::{self, fighter, target, ShipAccessor, ShipClass};
+use oort_simulator::simulation::{self, Code};
+use test_log::test;
+
+fn new_sim() -> Box<simulation::Simulation> {
+    simulation::Simulation::new("test", 0, &[Code::None, Code::None])
+}
+
+#[test]
+fn test_damage_dealt() {
+    let mut sim = new_sim();
+    let mut ship_data = fighter(0);
+    ship_data.guns[0].inaccuracy = 0.0;
+    let ship0 = ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        ship_data,
+    );
+    let mut target_data = target(1);
+    target_data.health = 1e6;
+    let ship1 = ship::create(
+        &mut sim,
+        vector![100.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        target_data,
+    );
+
+    for _ in 0..60 {
+        sim.ship_mut(ship0).fire_gun(0);
+        sim.step();
+    }
+    for _ in 0..100 {
+        sim.step();
+    }
+
+    let damage = 1e6 - sim.ship(ship1).data().health;
+    assert!(damage > 0.0);
+    approx::assert_abs_diff_eq!(sim.score(0, DAMAGE_DEALT), damage, epsilon = 1e-6);
+    assert_eq!(sim.score(1, DAMAGE_DEALT), 0.0);
+    assert_eq!(sim.snapshot(0).scores, *sim.scores());
+}
+
+#[test]
+fn test_capture_zone() {
+    let mut sim = new_sim();
+    let ship0 = ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(0),
+    );
+    ship::create(
+        &mut sim,
+        vector![5000.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(1),
+    );
+
+    let conditions = VictoryConditions::new(10000).with(Condition::CaptureZone {
+        center: vector![0.0, 0.0],
+        radius: 1000.0,
+        hold_time: 1.0,
+        filter: |ship| ship.data().class == ShipClass::Fighter,
+    });
+
+    for _ in 0..30 {
+        conditions.tick(&mut sim);
+        sim.step();
+    }
+    assert_eq!(conditions.status(&sim), Status::Running);
+    approx::assert_abs_diff_eq!(sim.score(0, CAPTURE_TIME), 0.5, epsilon = 1e-6);
+
+    // Contesting the zone resets progress.
+    let ship2 = ship::create(
+        &mut sim,
+        vector![500.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(1),
+    );
+    conditions.tick(&mut sim);
+    sim.step();
+    assert_eq!(sim.score(0, CAPTURE_TIME), 0.0);
+    assert_eq!(sim.score(1, CAPTURE_TIME), 0.0);
+
+    sim.ship_mut(ship2).data_mut().destroyed = true;
+    sim.step();
+    for _ in 0..59 {
+        assert_eq!(conditions.status(&sim), Status::Running);
+        conditions.tick(&mut sim);
+        sim.step();
+    }
+    for _ in 0..2 {
+        conditions.tick(&mut sim);
+        sim.step();
+    }
+    assert!(sim.ships.contains(ship0));
+    assert_eq!(conditions.status(&sim), Status::Victory { team: 0 });
+}
+
+#[test]
+fn test_two_capture_zones() {
+    let mut sim = new_sim();
+    ship::create(
+        &mut sim,
+        vector![-5000.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(0),
+    );
+    ship::create(
+        &mut sim,
+        vector![5000.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        fighter(1),
+    );
+
+    let filter: fn(&ShipAccessor) -> bool = |ship| ship.data().class == ShipClass::Fighter;
+    let conditions = VictoryConditions::new(10000)
+        .with(Condition::CaptureZone {
+            center: vector![-5000.0, 0.0],
+            radius: 1000.0,
+            hold_time: 1.0,
+            filter,
+        })
+        .with(Condition::CaptureZone {
+            center: vector![5000.0, 0.0],
+            radius: 1000.0,
+            hold_time: 2.0,
+            filter,
+        });
+
+    // Each team holds a different zone. Neither resets the other's progress.
+    for _ in 0..30 {
+        conditions.tick(&mut sim);
+        sim.step();
+    }
+    assert_eq!(conditions.status(&sim), Status::Running);
+    approx::assert_abs_diff_eq!(sim.score(0, &capture_time_counter(0)), 0.5, epsilon = 1e-6);
+    approx::assert_abs_diff_eq!(sim.score(1, &capture_time_counter(1)), 0.5, epsilon = 1e-6);
+    assert_eq!(sim.score(1, &capture_time_counter(0)), 0.0);
+    assert_eq!(sim.score(0, &capture_time_counter(1)), 0.0);
+
+    // Team 0 wins first since its zone has a shorter hold time.
+    for _ in 0..32 {
+        conditions.tick(&mut sim);
+        sim.step();
+    }
+    assert_eq!(conditions.status(&sim), Status::Victory { team: 0 });
+}
+
+#[test]
+fn test_escort() {
+    let mut sim = new_sim();
+    let vip = ship::create(
+        &mut sim,
+        vector![0.0, 0.0],
+        vector![0.0, 0.0],
+        0.0,
+        target(0),
+    );
+    let conditions = VictoryConditions::new(10000).with(Condition::Escort { vip, attacker: 1 });
+
+    sim.step();
+    assert_eq!(conditions.status(&sim), Status::Running);
+
+    sim.ship_mut(vip).data_mut().destroyed = true;
+    sim.step();
+    assert_eq!(conditions.status(&sim), Status::Victory { team: 1 });
+}
+
+#[test]
+fn test_score_at_timeout() {
+    let mut sim = new_sim();
+    let decisive = VictoryConditions::new(10).with(Condition::ScoreAtTimeout {
+        counter: "kills".to_string(),
+        min_ratio: 1.5,
+    });
+    let close = VictoryConditions::new(10).with(Condition::ScoreAtTimeout {
+        counter: "kills".to_string(),
+        min_ratio: 3.0,
+    });
+
+    sim.set_score(0, "kills", 4.0);
+    sim.add_score(1, "kills", 1.0);
+    sim.add_score(1, "kills", 1.0);
+    assert_eq!(sim.score(1, "kills"), 2.0);
+
+    while sim.tick() < 9 {
+        assert_eq!(decisive.status(&sim), Status::Running);
+        sim.step();
+    }
+    assert_eq!(decisive.status(&sim), Status::Victory { team: 0 });
+    assert_eq!(close.status(&sim), Status::Draw);
+}
@@ -1,4 +1,5 @@
 use clap::Parser;
+use oort_simulator::scenario::{Placements, Scenario};
 use oort_simulator::simulation::Code;
 use oort_simulator::{scenario, simulation};
 use rayon::prelude::*;
@@ -8,6 +9,7 @@ use std::path::PathBuf;
 #[derive(Parser, Debug)]
 #[clap()]
 struct Arguments {
+    /// Scenario name, or path to a TOML or JSON scenario definition.
     scenario: String,
     shortcodes: Vec<String>,
 
@@ -27,7 +29,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .init();
 
     let args = Arguments::parse();
-    scenario::load_safe(&args.scenario).expect("Unknown scenario");
+    let scenario = oort_tools::load_scenario(&args.scenario)?;
     if args.shortcodes.len() < 2 {
         panic!("Expected at least two shortcodes");
     }
@@ -42,13 +44,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     )
     .await?;
 
+    let num_teams = scenario.initial_code().len();
+    if num_teams > 2 {
+        if ais.len() != num_teams {
+            panic!("Expected {num_teams} shortcodes for a free-for-all scenario");
+        }
+        log::info!("Running free-for-all simulations");
+        let codes: Vec<Code> = ais.iter().map(|ai| ai.compiled_code.clone()).collect();
+        let placements = run_ffa_simulations(&*scenario, codes, args.rounds);
+        for (team, ai) in ais.iter().enumerate() {
+            let ranks: Vec<u32> = placements
+                .iter()
+                .map(|p| p.rank(team as i32).unwrap_or(num_teams as u32))
+                .collect();
+            println!("{}:", ai.name);
+            println!(
+                "  Wins: {}",
+                placements
+                    .iter()
+                    .filter(|p| p.winner() == Some(team as i32))
+                    .count()
+            );
+            println!(
+                "  Average placement: {:.2}",
+                ranks.iter().sum::<u32>() as f64 / ranks.len() as f64
+            );
+        }
+        return Ok(());
+    }
+
     log::info!("Running simulations");
     let player0 = &ais[0];
     let results_per_opponent = ais[1..]
         .par_iter()
         .map(|player1| {
             let codes = vec![player0.compiled_code.clone(), player1.compiled_code.clone()];
-            let results = run_simulations(&args.scenario, codes, args.rounds);
+            let results = run_simulations(&*scenario, codes, args.rounds);
             (player1, results)
         })
         .collect::<Vec<_>>();
@@ -97,10 +128,10 @@ struct Results {
     times: Vec<f64>,
 }
 
-fn run_simulations(scenario_name: &str, codes: Vec<Code>, rounds: u32) -> Results {
+fn run_simulations(scenario: &dyn Scenario, codes: Vec<Code>, rounds: u32) -> Results {
     let seed_statuses: Vec<(u32, (scenario::Status, f64))> = (0..rounds)
         .into_par_iter()
-        .map(|seed| (seed, run_simulation(scenario_name, seed, codes.clone())))
+        .map(|seed| (seed, run_simulation(scenario, seed, codes.clone())))
         .collect();
     let mut results: Results = Default::default();
     for (seed, (status, time)) in seed_statuses {
@@ -116,8 +147,20 @@ fn run_simulations(scenario_name: &str, codes: Vec<Code>, rounds: u32) -> Result
     results
 }
 
-fn run_simulation(scenario_name: &str, seed: u32, codes: Vec<Code>) -> (scenario::Status, f64) {
-    let mut sim = simulation::Simulation::new(scenario_name, seed, &codes);
+fn run_ffa_simulations(scenario: &dyn Scenario, codes: Vec<Code>, rounds: u32) -> Vec<Placements> {
+    (0..rounds)
+        .into_par_iter()
+        .map(
+            |seed| match run_simulation(scenario, seed, codes.clone()).0 {
+                scenario::Status::Ranked { placements } => placements,
+                status => panic!("Unexpected status {status:?} in free-for-all scenario"),
+            },
+        )
+        .collect()
+}
+
+fn run_simulation(scenario: &dyn Scenario, seed: u32, codes: Vec<Code>) -> (scenario::Status, f64) {
+    let mut sim = simulation::Simulation::new_with_scenario(scenario.clone_box(), seed, &codes);
     while sim.status() == scenario::Status::Running && sim.tick() < scenario::MAX_TICKS {
         sim.step();
     }
@@ -0,0 +1,89 @@
+//! Runs the AI in shared/ai/src/user.rs as native code instead of wasm, so it
+//! can be run under a debugger or sanitizers.
+//!
+//! The AI plays team 0 and the opponent (by default the scenario's own AI)
+//! plays team 1.
+use clap::Parser;
+use oort_simulator::scenario;
+use oort_simulator::simulation::{self, Code};
+use oort_simulator::vm::native::{NativeCode, NativeShip};
+use std::path::PathBuf;
+
+#[path = "../../../shared/ai/src/user.rs"]
+mod user;
+
+impl NativeShip for user::Ship {
+    fn tick(&mut self) {
+        user::Ship::tick(self)
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap()]
+struct Arguments {
+    /// Scenario name, or path to a TOML or JSON scenario definition.
+    scenario: String,
+
+    /// Shortcode or path of the opponent's AI.
+    #[clap(short, long)]
+    opponent: Option<String>,
+
+    #[clap(short, long, default_value = "0")]
+    seed: u32,
+
+    #[clap(short, long, default_value = "1")]
+    rounds: u32,
+
+    #[clap(short, long)]
+    dev: bool,
+
+    #[clap(long, default_value = "/tmp/oort-wasm-cache")]
+    wasm_cache: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("native=info"))
+        .init();
+
+    let args = Arguments::parse();
+    let scenario = oort_tools::load_scenario(&args.scenario)?;
+
+    let mut codes = scenario.initial_code();
+    codes[0] = Code::Native(NativeCode::new("user", || Box::new(user::Ship::new())));
+    if let Some(opponent) = args.opponent.as_ref() {
+        if codes.len() < 2 {
+            anyhow::bail!("Scenario {:?} has no opponent", args.scenario);
+        }
+        let http = reqwest::Client::new();
+        let ai =
+            oort_tools::fetch_and_compile(&http, opponent, args.dev, args.wasm_cache.as_deref())
+                .await?;
+        codes[1] = ai.compiled_code;
+    }
+
+    for seed in args.seed..(args.seed + args.rounds) {
+        let mut sim = simulation::Simulation::new_with_scenario(scenario.clone_box(), seed, &codes);
+        while sim.status() == scenario::Status::Running && sim.tick() < scenario::MAX_TICKS {
+            sim.step();
+            for crash in sim.events().crashes.iter() {
+                log::warn!(
+                    "Team {} ship {} crashed at tick {}: {}",
+                    crash.team,
+                    crash.ship,
+                    crash.tick,
+                    crash.error
+                );
+            }
+        }
+        println!(
+            "Seed {}: {:?} at tick {} (time {:.3})",
+            seed,
+            sim.status(),
+            sim.tick(),
+            sim.score_time()
+        );
+    }
+
+    Ok(())
+}
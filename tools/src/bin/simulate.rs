@@ -0,0 +1,128 @@
+//! Runs a scenario with local AIs and prints the result of each seed as a
+//! line of JSON. Doesn't need network access, so it can be used to
+//! regression-test an AI against the builtin AIs in CI.
+use clap::Parser;
+use oort_simulator::scenario::{self, Scenario, DAMAGE_DEALT, SHOTS_FIRED, SHOTS_HIT};
+use oort_simulator::simulation::{self, Code};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug)]
+#[clap()]
+struct Arguments {
+    /// Scenario name, or path to a TOML or JSON scenario definition.
+    scenario: String,
+
+    /// Rust source or wasm file for each team, in order. Teams without one
+    /// use the scenario's AI.
+    ais: Vec<PathBuf>,
+
+    /// Comma-separated seeds to run. Defaults to 0 up to `rounds`.
+    #[clap(short, long, value_delimiter = ',')]
+    seeds: Vec<u32>,
+
+    #[clap(short, long, default_value = "10")]
+    rounds: u32,
+}
+
+#[derive(Serialize, Debug)]
+struct SeedResult {
+    seed: u32,
+    status: scenario::Status,
+    winner: Option<i32>,
+    ticks: u32,
+    score_time: f64,
+    teams: Vec<TeamResult>,
+}
+
+#[derive(Serialize, Debug)]
+struct TeamResult {
+    team: i32,
+    damage_dealt: f64,
+    shots_fired: u64,
+    shots_hit: u64,
+    /// Fraction of shots that hit, or None if the team didn't fire.
+    hit_rate: Option<f64>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("simulate=info"))
+        .init();
+
+    let args = Arguments::parse();
+    let scenario = oort_tools::load_scenario(&args.scenario)?;
+
+    let mut codes = scenario.initial_code();
+    if args.ais.len() > codes.len() {
+        anyhow::bail!(
+            "Scenario {:?} has {} teams but {} AIs were given",
+            args.scenario,
+            codes.len(),
+            args.ais.len()
+        );
+    }
+    let mut compiler = oort_compiler::Compiler::new();
+    for (team, path) in args.ais.iter().enumerate() {
+        codes[team] = load_ai(&mut compiler, path)?;
+    }
+
+    let seeds = if args.seeds.is_empty() {
+        (0..args.rounds).collect()
+    } else {
+        args.seeds.clone()
+    };
+    log::info!("Running {} simulations", seeds.len());
+    let results: Vec<SeedResult> = seeds
+        .par_iter()
+        .map(|&seed| run_simulation(&*scenario, seed, &codes))
+        .collect();
+
+    for result in results.iter() {
+        println!("{}", serde_json::to_string(result)?);
+    }
+
+    Ok(())
+}
+
+fn load_ai(compiler: &mut oort_compiler::Compiler, path: &Path) -> anyhow::Result<Code> {
+    let wasm = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("wasm") => std::fs::read(path)?,
+        Some("rs") => {
+            log::info!("Compiling {:?}", path);
+            compiler.compile(&std::fs::read_to_string(path)?)?
+        }
+        _ => anyhow::bail!("Expected a .rs or .wasm file, got {:?}", path),
+    };
+    oort_simulator::vm::precompile(&wasm).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))
+}
+
+fn run_simulation(scenario: &dyn Scenario, seed: u32, codes: &[Code]) -> SeedResult {
+    let mut sim = simulation::Simulation::new_with_scenario(scenario.clone_box(), seed, codes);
+    while sim.status() == scenario::Status::Running && sim.tick() < scenario::MAX_TICKS {
+        sim.step();
+    }
+
+    let status = sim.status();
+    let teams = (0..codes.len() as i32)
+        .map(|team| {
+            let shots_fired = sim.score(team, SHOTS_FIRED);
+            let shots_hit = sim.score(team, SHOTS_HIT);
+            TeamResult {
+                team,
+                damage_dealt: sim.score(team, DAMAGE_DEALT),
+                shots_fired: shots_fired as u64,
+                shots_hit: shots_hit as u64,
+                hit_rate: (shots_fired > 0.0).then_some(shots_hit / shots_fired),
+            }
+        })
+        .collect();
+    SeedResult {
+        seed,
+        status,
+        winner: status.winner(),
+        ticks: sim.tick(),
+        score_time: sim.score_time(),
+        teams,
+    }
+}
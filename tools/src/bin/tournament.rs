@@ -5,16 +5,20 @@ use comfy_table::Table;
 use firestore::*;
 use itertools::Itertools;
 use oort_proto::{ShortcodeUpload, TournamentCompetitor, TournamentResults, TournamentSubmission};
+use oort_simulator::snapshot::Replay;
 use oort_simulator::{scenario, simulation};
 use oort_tools::AI;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use rayon::prelude::*;
 use skillratings::{
     glicko2::{glicko2, Glicko2Config, Glicko2Rating},
-    Outcomes,
+    weng_lin::{weng_lin_multi_team, WengLinConfig, WengLinRating},
+    MultiTeamOutcome, Outcomes,
 };
+use std::collections::HashMap;
 use std::default::Default;
-use std::{collections::HashMap, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[clap()]
@@ -37,6 +41,10 @@ enum SubCommand {
 
         #[clap(short, long)]
         dry_run: bool,
+
+        /// Save replays of decisive games to this directory.
+        #[clap(long)]
+        replay_dir: Option<PathBuf>,
     },
     RunUnofficial {
         scenario: String,
@@ -50,6 +58,10 @@ enum SubCommand {
 
         #[clap(long, default_value = "/tmp/oort-wasm-cache")]
         wasm_cache: Option<PathBuf>,
+
+        /// Save replays of decisive games to this directory.
+        #[clap(long)]
+        replay_dir: Option<PathBuf>,
     },
     Fetch {
         scenario: String,
@@ -80,14 +92,26 @@ async fn main() -> anyhow::Result<()> {
             usernames,
             rounds,
             dry_run,
-        } => cmd_run(&args.project_id, &scenario, &usernames, rounds, dry_run).await,
+            replay_dir,
+        } => {
+            cmd_run(
+                &args.project_id,
+                &scenario,
+                &usernames,
+                rounds,
+                dry_run,
+                replay_dir,
+            )
+            .await
+        }
         SubCommand::RunUnofficial {
             scenario,
             shortcodes,
             rounds,
             dev,
             wasm_cache,
-        } => cmd_run_unofficial(&scenario, &shortcodes, rounds, dev, wasm_cache).await,
+            replay_dir,
+        } => cmd_run_unofficial(&scenario, &shortcodes, rounds, dev, wasm_cache, replay_dir).await,
         SubCommand::Fetch { scenario, out_dir } => {
             cmd_fetch(&args.project_id, &scenario, &out_dir).await
         }
@@ -105,6 +129,7 @@ async fn cmd_run(
     usernames: &[String],
     rounds: i32,
     dry_run: bool,
+    replay_dir: Option<PathBuf>,
 ) -> anyhow::Result<()> {
     let db = FirestoreDb::new(project_id).await?;
     scenario::load_safe(scenario_name).expect("Unknown scenario");
@@ -127,7 +152,7 @@ async fn cmd_run(
     let ais: Vec<AI> = results.into_iter().collect::<anyhow::Result<Vec<AI>>>()?;
 
     log::info!("Running tournament");
-    let results = run_tournament(scenario_name, &ais, rounds);
+    let results = run_tournament(scenario_name, &ais, rounds, replay_dir.as_deref());
 
     display_results(&results);
 
@@ -144,6 +169,7 @@ async fn cmd_run_unofficial(
     rounds: i32,
     dev: bool,
     wasm_cache: Option<PathBuf>,
+    replay_dir: Option<PathBuf>,
 ) -> anyhow::Result<()> {
     scenario::load_safe(scenario_name).expect("Unknown scenario");
 
@@ -152,14 +178,26 @@ async fn cmd_run_unofficial(
         .await?;
 
     log::info!("Running tournament");
-    let results = run_tournament(scenario_name, &ais, rounds);
+    let results = run_tournament(scenario_name, &ais, rounds, replay_dir.as_deref());
 
     display_results(&results);
 
     Ok(())
 }
 
-fn run_tournament(scenario_name: &str, ais: &[AI], rounds: i32) -> TournamentResults {
+fn run_tournament(
+    scenario_name: &str,
+    ais: &[AI],
+    rounds: i32,
+    replay_dir: Option<&Path>,
+) -> TournamentResults {
+    if let Some(replay_dir) = replay_dir {
+        std::fs::create_dir_all(replay_dir).expect("Failed to create replay directory");
+    }
+    let num_teams = scenario::load(scenario_name).initial_code().len();
+    if num_teams > 2 {
+        return run_ffa_tournament(scenario_name, ais, rounds, num_teams, replay_dir);
+    }
     let seeds: Vec<u32> = (0..rounds).map(|_| rand::thread_rng().gen()).collect();
     let mut pairings: HashMap<(String, String), f64> = HashMap::new();
     let config = Glicko2Config::new();
@@ -175,7 +213,7 @@ fn run_tournament(scenario_name: &str, ais: &[AI], rounds: i32) -> TournamentRes
             let seed = seeds[*round as usize];
             let ai0: &AI = &ais[indices[0]];
             let ai1: &AI = &ais[indices[1]];
-            let r = run_simulation(scenario_name, seed, &[ai0, ai1]);
+            let r = run_simulation(scenario_name, seed, &[ai0, ai1], replay_dir);
             progress.inc(1);
             (*round, indices.clone(), r)
         })
@@ -208,13 +246,108 @@ fn run_tournament(scenario_name: &str, ais: &[AI], rounds: i32) -> TournamentRes
         }
     }
 
+    let ratings: Vec<f64> = ratings.iter().map(|r| r.rating).collect();
+    make_results(scenario_name, ais, &ratings, &pairings)
+}
+
+/// Runs games with every team slot filled, rotating competitors through the
+/// slots, and rates them with the Weng-Lin multiplayer rating system.
+fn run_ffa_tournament(
+    scenario_name: &str,
+    ais: &[AI],
+    rounds: i32,
+    num_teams: usize,
+    replay_dir: Option<&Path>,
+) -> TournamentResults {
+    if ais.len() < num_teams {
+        panic!("Expected at least {num_teams} competitors for a free-for-all scenario");
+    }
+    let mut rng = rand::thread_rng();
+    let mut games: Vec<(u32, Vec<usize>)> = vec![];
+    for _ in 0..rounds {
+        let seed: u32 = rng.gen();
+        let mut order: Vec<usize> = (0..ais.len()).collect();
+        order.shuffle(&mut rng);
+        for i in 0..ais.len() {
+            let indices = (0..num_teams).map(|j| order[(i + j) % ais.len()]).collect();
+            games.push((seed, indices));
+        }
+    }
+    let progress = indicatif::ProgressBar::new(games.len() as u64);
+    let outcomes: Vec<(u32, Vec<usize>, scenario::Placements)> = games
+        .par_iter()
+        .map(|(seed, indices)| {
+            let game_ais: Vec<&AI> = indices.iter().map(|&i| &ais[i]).collect();
+            let r = run_ffa_simulation(scenario_name, *seed, &game_ais, replay_dir);
+            progress.inc(1);
+            (*seed, indices.clone(), r)
+        })
+        .collect();
+    progress.finish_and_clear();
+
+    let config = WengLinConfig::new();
+    let mut ratings: Vec<WengLinRating> = vec![WengLinRating::new(); ais.len()];
+    let mut wins: HashMap<(String, String), f64> = HashMap::new();
+    let mut games_played: HashMap<(String, String), f64> = HashMap::new();
+    for (seed, indices, placements) in outcomes {
+        log::debug!(
+            "{} seed {}: {:?}",
+            indices.iter().map(|&i| &ais[i].name).join(" vs "),
+            seed,
+            placements.order()
+        );
+        let ranks: Vec<u32> = (0..indices.len())
+            .map(|team| placements.rank(team as i32).unwrap_or(num_teams as u32))
+            .collect();
+        let teams: Vec<[WengLinRating; 1]> = indices.iter().map(|&i| [ratings[i]]).collect();
+        let teams_and_ranks: Vec<(&[WengLinRating], MultiTeamOutcome)> = teams
+            .iter()
+            .zip(ranks.iter())
+            .map(|(team, &rank)| (&team[..], MultiTeamOutcome::new(rank as usize)))
+            .collect();
+        let new_ratings = weng_lin_multi_team(&teams_and_ranks, &config);
+        for (&i, rating) in indices.iter().zip(new_ratings) {
+            ratings[i] = rating[0];
+        }
+
+        for (a, &i0) in indices.iter().enumerate() {
+            for (b, &i1) in indices.iter().enumerate() {
+                if a == b {
+                    continue;
+                }
+                let key = (ais[i0].name.clone(), ais[i1].name.clone());
+                *games_played.entry(key.clone()).or_default() += 1.0;
+                if ranks[a] < ranks[b] {
+                    *wins.entry(key).or_default() += 1.0;
+                }
+            }
+        }
+    }
+
+    let pairings: HashMap<(String, String), f64> = wins
+        .into_iter()
+        .map(|(key, wins)| {
+            let n = games_played[&key];
+            (key, wins / n)
+        })
+        .collect();
+    let ratings: Vec<f64> = ratings.iter().map(|r| r.rating).collect();
+    make_results(scenario_name, ais, &ratings, &pairings)
+}
+
+fn make_results(
+    scenario_name: &str,
+    ais: &[AI],
+    ratings: &[f64],
+    pairings: &HashMap<(String, String), f64>,
+) -> TournamentResults {
     let mut competitors: Vec<_> = ais
         .iter()
         .enumerate()
         .map(|(i, x)| TournamentCompetitor {
             username: x.name.clone(),
             shortcode: "".to_string(),
-            rating: ratings[i].rating,
+            rating: ratings[i],
         })
         .collect();
     competitors.sort_by_key(|c| (-c.rating * 1e6) as i64);
@@ -241,17 +374,82 @@ fn run_tournament(scenario_name: &str, ais: &[AI], rounds: i32) -> TournamentRes
     }
 }
 
-fn run_simulation(scenario_name: &str, seed: u32, ais: &[&AI]) -> Outcomes {
+fn run_simulation(
+    scenario_name: &str,
+    seed: u32,
+    ais: &[&AI],
+    replay_dir: Option<&Path>,
+) -> Outcomes {
     let codes: Vec<_> = ais.iter().map(|x| x.compiled_code.clone()).collect();
     let mut sim = simulation::Simulation::new(scenario_name, seed, &codes);
+    if replay_dir.is_some() {
+        sim.start_recording();
+    }
     while sim.status() == scenario::Status::Running && sim.tick() < scenario::MAX_TICKS {
         sim.step();
     }
-    match sim.status() {
+    let outcome = match sim.status() {
         scenario::Status::Victory { team: 0 } => Outcomes::WIN,
         scenario::Status::Victory { team: 1 } => Outcomes::LOSS,
         scenario::Status::Draw => Outcomes::DRAW,
         _ => unreachable!(),
+    };
+    if let (Some(replay_dir), Some(replay)) = (replay_dir, sim.finish_recording()) {
+        let (winner, loser) = match outcome {
+            Outcomes::WIN => (ais[0], ais[1]),
+            Outcomes::LOSS => (ais[1], ais[0]),
+            _ => return outcome,
+        };
+        let path = replay_dir.join(format!(
+            "{}.{}.lost-to.{}.{}.replay",
+            scenario_name, loser.name, winner.name, seed
+        ));
+        save_replay(&path, &replay);
+    }
+    outcome
+}
+
+fn run_ffa_simulation(
+    scenario_name: &str,
+    seed: u32,
+    ais: &[&AI],
+    replay_dir: Option<&Path>,
+) -> scenario::Placements {
+    let codes: Vec<_> = ais.iter().map(|x| x.compiled_code.clone()).collect();
+    let mut sim = simulation::Simulation::new(scenario_name, seed, &codes);
+    if replay_dir.is_some() {
+        sim.start_recording();
+    }
+    while sim.status() == scenario::Status::Running && sim.tick() < scenario::MAX_TICKS {
+        sim.step();
+    }
+    let placements = match sim.status() {
+        scenario::Status::Ranked { placements } => placements,
+        status => panic!("Unexpected status {status:?} in free-for-all scenario"),
+    };
+    if let (Some(replay_dir), Some(replay)) = (replay_dir, sim.finish_recording()) {
+        if let Some(winner) = placements.winner() {
+            let path = replay_dir.join(format!(
+                "{}.{}.won-by.{}.{}.replay",
+                scenario_name,
+                ais.iter().map(|ai| &ai.name).join("-"),
+                ais[winner as usize].name,
+                seed
+            ));
+            save_replay(&path, &replay);
+        }
+    }
+    placements
+}
+
+fn save_replay(path: &Path, replay: &Replay) {
+    match replay.encode() {
+        Ok(data) => {
+            if let Err(e) = std::fs::write(path, data) {
+                log::warn!("Failed to write replay {:?}: {:?}", path, e);
+            }
+        }
+        Err(e) => log::warn!("Failed to encode replay: {}", e),
     }
 }
 
@@ -32,6 +32,12 @@ async fn main() -> anyhow::Result<()> {
         #[clap(long)]
         wasm_cache: Option<PathBuf>,
 
+        /// Run the original player code up to this tick once per seed, then
+        /// evaluate candidates from that point. Candidates start with fresh
+        /// AI state at the fork.
+        #[clap(long)]
+        fork_tick: Option<u32>,
+
         scenario_name: String,
         player_code: String,
         enemy_code: String,
@@ -55,8 +61,24 @@ async fn main() -> anyhow::Result<()> {
         .map(|ai| ai.compiled_code)
         .collect::<Vec<_>>();
 
+    let forks = if let Some(fork_tick) = args.fork_tick {
+        log::info!("Running simulations up to fork at tick {}", fork_tick);
+        create_forks(
+            &args.scenario_name,
+            codes.clone(),
+            args.num_seeds,
+            fork_tick,
+        )
+    } else {
+        vec![]
+    };
+
     log::info!("Running initial simulations");
-    let initial_fitness = run_simulations(&args.scenario_name, codes.clone(), args.num_seeds);
+    let initial_fitness = if args.fork_tick.is_some() {
+        run_forked_simulations(&forks, &codes[0])
+    } else {
+        run_simulations(&args.scenario_name, codes.clone(), args.num_seeds)
+    };
     log::info!(
         "Initial fitness: {} for {:?}",
         initial_fitness,
@@ -76,6 +98,7 @@ async fn main() -> anyhow::Result<()> {
         bounds: bounds.to_vec(),
         enemy_code: codes[1].clone(),
         num_seeds: args.num_seeds,
+        forks,
     };
 
     let pool = generate_pool(&initial_values);
@@ -117,6 +140,12 @@ struct ObjectiveFunction {
     bounds: Vec<[f64; 2]>,
     enemy_code: Code,
     num_seeds: u32,
+    forks: Vec<Fork>,
+}
+
+struct Fork {
+    player_team: i32,
+    checkpoint: simulation::Checkpoint,
 }
 
 impl Bounded for ObjectiveFunction {
@@ -142,11 +171,15 @@ impl ObjFunc for ObjectiveFunction {
         let compile_duration = std::time::Instant::now() - compile_start_time;
 
         let sim_start_time = std::time::Instant::now();
-        let fitness = run_simulations(
-            &self.scenario_name,
-            vec![player_code, self.enemy_code.clone()],
-            self.num_seeds,
-        );
+        let fitness = if self.forks.is_empty() {
+            run_simulations(
+                &self.scenario_name,
+                vec![player_code, self.enemy_code.clone()],
+                self.num_seeds,
+            )
+        } else {
+            run_forked_simulations(&self.forks, &player_code)
+        };
         let sim_duration = std::time::Instant::now() - sim_start_time;
 
         log::debug!(
@@ -224,18 +257,63 @@ fn run_simulations(scenario_name: &str, codes: Vec<Code>, num_seeds: u32) -> f64
             let codes = if reverse { &reverse_codes } else { &codes };
             let player_team = if reverse { 1 } else { 0 };
             let (status, time) = run_simulation(scenario_name, seed, codes.clone());
-            match status {
-                scenario::Status::Victory { team } if team == player_team => time,
-                _ => 1e6,
-            }
+            score(status, time, player_team)
         })
         .sum()
 }
 
 fn run_simulation(scenario_name: &str, seed: u32, codes: Vec<Code>) -> (scenario::Status, f64) {
-    let mut sim = simulation::Simulation::new(scenario_name, seed, &codes);
+    finish_simulation(simulation::Simulation::new(scenario_name, seed, &codes))
+}
+
+fn create_forks(
+    scenario_name: &str,
+    codes: Vec<Code>,
+    num_seeds: u32,
+    fork_tick: u32,
+) -> Vec<Fork> {
+    let reverse_codes = codes.iter().rev().cloned().collect::<Vec<_>>();
+    (0..num_seeds)
+        .into_par_iter()
+        .flat_map(|seed| [(seed, false), (seed, true)])
+        .map(|(seed, reverse)| {
+            let codes = if reverse { &reverse_codes } else { &codes };
+            let player_team = if reverse { 1 } else { 0 };
+            let mut sim = simulation::Simulation::new(scenario_name, seed, codes);
+            while sim.status() == scenario::Status::Running && sim.tick() < fork_tick {
+                sim.step();
+            }
+            Fork {
+                player_team,
+                checkpoint: sim.checkpoint().unwrap(),
+            }
+        })
+        .collect()
+}
+
+fn run_forked_simulations(forks: &[Fork], player_code: &Code) -> f64 {
+    forks
+        .par_iter()
+        .map(|fork| {
+            let mut sim = simulation::Simulation::restore(&fork.checkpoint).unwrap();
+            sim.replace_code(fork.player_team, player_code);
+            let (status, time) = finish_simulation(sim);
+            score(status, time, fork.player_team)
+        })
+        .sum()
+}
+
+fn finish_simulation(mut sim: Box<simulation::Simulation>) -> (scenario::Status, f64) {
     while sim.status() == scenario::Status::Running && sim.tick() < scenario::MAX_TICKS {
         sim.step();
     }
     (sim.status(), sim.time())
 }
+
+fn score(status: scenario::Status, time: f64, player_team: i32) -> f64 {
+    if status.winner() == Some(player_team) {
+        time
+    } else {
+        1e6
+    }
+}
@@ -1,7 +1,20 @@
 use std::{fs, path::Path, path::PathBuf};
 
+use oort_simulator::scenario::{self, Scenario};
 use oort_simulator::simulation::Code;
 
+/// Loads a builtin scenario by name, or a scenario definition from a TOML or
+/// JSON file.
+pub fn load_scenario(name_or_path: &str) -> anyhow::Result<Box<dyn Scenario>> {
+    if let Some(scenario) = scenario::load_safe(name_or_path) {
+        return Ok(scenario);
+    }
+    let contents = fs::read_to_string(name_or_path)
+        .map_err(|e| anyhow::anyhow!("Unknown scenario {:?}: {}", name_or_path, e))?;
+    scenario::load_definition(&contents)
+        .map_err(|e| anyhow::anyhow!("Failed to load scenario {:?}: {}", name_or_path, e))
+}
+
 pub struct AI {
     pub name: String,
     pub source_code: String,